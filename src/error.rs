@@ -2,7 +2,7 @@
 pub enum Error {
     #[error("bootrom is enabled but no bootrom was provided")]
     MissingBootrom,
-    #[error("bootrom size isn't 0x100 bytes")]
+    #[error("bootrom size isn't 0x100 or 0x900 bytes")]
     InvalidBootRom,
     #[error("save size does not match the size expected by the loaded rom")]
     InvalidSave,
@@ -3,7 +3,7 @@ use std::ops::SubAssign;
 use num::PrimInt;
 use serde::{Deserialize, Serialize};
 
-use super::cpu::Cpu;
+use super::Gameboy;
 
 // TODO: Find write only/unused bit read value
 
@@ -341,10 +341,16 @@ impl Channel4 {
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub(crate) struct Apu {
-    left_deltas: [i32; 12],
-    right_deltas: [i32; 12],
-    delta_offsets: [usize; 12],
+    /// Output buffers, not saved in savestates and filled again from the next tick.
+    #[serde(skip)]
+    left_deltas: Vec<i32>,
+    #[serde(skip)]
+    right_deltas: Vec<i32>,
+    #[serde(skip)]
+    delta_offsets: Vec<usize>,
+    #[serde(skip)]
     delta_offset: usize,
+    #[serde(skip)]
     delta_count: usize,
     master_vol_vin_pan: MasterVolVinPan,
     sound_panning: SoundPanning,
@@ -395,6 +401,9 @@ impl Apu {
 
     const WAVELEN_MAX: u16 = 1 << 11;
 
+    /// Deltas not fetched for about two frames are dropped.
+    const MAX_DELTA_OFFSET: usize = 2 * Gameboy::CYCLES_PER_FRAME as usize;
+
     pub(crate) fn new() -> Self {
         Self {
             ..Default::default()
@@ -492,7 +501,7 @@ impl Apu {
 
     pub(crate) fn tick(&mut self) {
         // TODO: Refactor once all channels work properly.
-        if self.delta_offset >= Cpu::MAX_TICKS_PER_INSTR * 2 {
+        if self.delta_offset >= Self::MAX_DELTA_OFFSET {
             // Samples were not fetched, we skip them.
             self.delta_count = 0;
             self.delta_offset = 0;
@@ -677,9 +686,13 @@ impl Apu {
             self.amplitude_right = amplitude_right;
 
             if delta_left != 0 || delta_right != 0 {
-                self.left_deltas[self.delta_count] = delta_left;
-                self.right_deltas[self.delta_count] = delta_right;
-                self.delta_offsets[self.delta_count] = self.delta_offset;
+                // Deltas already fetched are only dropped here, as they are still borrowed until then.
+                self.left_deltas.truncate(self.delta_count);
+                self.right_deltas.truncate(self.delta_count);
+                self.delta_offsets.truncate(self.delta_count);
+                self.left_deltas.push(delta_left);
+                self.right_deltas.push(delta_right);
+                self.delta_offsets.push(self.delta_offset);
                 self.delta_count += 1;
             }
             self.delta_offset += 2;
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    MapperOps, HIGH_BANK_END, HIGH_BANK_START, LOW_BANK_END, LOW_BANK_START, RAM_BANK_SIZE,
+    ROM_BANK_SIZE,
+};
+
+// TODO: Infrared communication with another cartridge, no light is ever received for now.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Huc1 {
+    rom_bank: u8,
+    ram_bank: u8,
+    ir_mode: bool,
+    ir_led: bool,
+}
+
+impl Huc1 {
+    const IR_SELECT_START: u16 = 0x0000;
+    const IR_SELECT_END: u16 = 0x1FFF;
+    const ROM_BANK_START: u16 = 0x2000;
+    const ROM_BANK_END: u16 = 0x3FFF;
+    const RAM_BANK_START: u16 = 0x4000;
+    const RAM_BANK_END: u16 = 0x5FFF;
+
+    const IR_MODE: u8 = 0x0E;
+    const IR_NO_LIGHT: u8 = 0xC0;
+
+    pub(crate) const fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ir_mode: false,
+            ir_led: false,
+        }
+    }
+
+    fn ram_address(&self, ram: &[u8], address: u16) -> Option<usize> {
+        (!ram.is_empty())
+            .then(|| (address as usize + self.ram_bank as usize * RAM_BANK_SIZE) % ram.len())
+    }
+}
+
+impl MapperOps for Huc1 {
+    fn read_rom(&mut self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            LOW_BANK_START..=LOW_BANK_END => rom[address as usize],
+            HIGH_BANK_START..=HIGH_BANK_END => {
+                let offset = self.rom_bank as usize * ROM_BANK_SIZE;
+                rom[((address - HIGH_BANK_START) as usize + offset) % rom.len()]
+            }
+            _ => panic!("Tried to read Huc1 rom out of range"),
+        }
+    }
+
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, self.rom_bank as usize)
+    }
+
+    fn mapped_ram_bank(&self) -> usize {
+        self.ram_bank as usize
+    }
+
+    fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
+        match address {
+            // Unlike the MBCs, RAM is always enabled when infrared is not selected.
+            Self::IR_SELECT_START..=Self::IR_SELECT_END => {
+                self.ir_mode = value & 0x0F == Self::IR_MODE
+            }
+            Self::ROM_BANK_START..=Self::ROM_BANK_END => {
+                self.rom_bank = u8::max(value & 0b11_1111, 1);
+            }
+            Self::RAM_BANK_START..=Self::RAM_BANK_END => self.ram_bank = value & 0b11,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&mut self, ram: &[u8], address: u16) -> u8 {
+        if self.ir_mode {
+            Self::IR_NO_LIGHT
+        } else {
+            self.ram_address(ram, address)
+                .map_or(0xFF, |address| ram[address])
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if self.ir_mode {
+            self.ir_led = value & 1 != 0;
+        } else if let Some(address) = self.ram_address(ram, address) {
+            ram[address] = value;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,414 @@
+use std::{
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use cookie_factory as cf;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    MapperOps, HIGH_BANK_END, HIGH_BANK_START, LOW_BANK_END, LOW_BANK_START, RAM_BANK_SIZE,
+    ROM_BANK_SIZE,
+};
+use crate::gameboy::Gameboy;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    RamReadOnly,
+    Ram,
+    RtcCommand,
+    RtcResponse,
+    RtcSemaphore,
+    Infrared,
+    Unknown,
+}
+
+// TODO: Infrared communication and tone playback, tones are only recorded for now.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Huc3 {
+    rom_bank: u8,
+    ram_bank: u8,
+    mode: Mode,
+    /// RTC chip memory, accessed one nibble at a time through commands.
+    rtc_memory: Vec<u8>,
+    rtc_address: u8,
+    rtc_response: u8,
+    minutes: u16,
+    days: u16,
+    seconds: u8,
+    cycles: usize,
+    tone: Option<u8>,
+}
+
+impl Huc3 {
+    const MODE_START: u16 = 0x0000;
+    const MODE_END: u16 = 0x1FFF;
+    const ROM_BANK_START: u16 = 0x2000;
+    const ROM_BANK_END: u16 = 0x3FFF;
+    const RAM_BANK_START: u16 = 0x4000;
+    const RAM_BANK_END: u16 = 0x5FFF;
+
+    const COMMAND_READ: u8 = 0x1;
+    const COMMAND_WRITE: u8 = 0x2;
+    const COMMAND_WRITE_INCREMENT: u8 = 0x3;
+    const COMMAND_ADDRESS_LOW: u8 = 0x4;
+    const COMMAND_ADDRESS_HIGH: u8 = 0x5;
+    const COMMAND_EXTENDED: u8 = 0x6;
+
+    const EXTENDED_READ_TIME: u8 = 0x0;
+    const EXTENDED_WRITE_TIME: u8 = 0x1;
+    const EXTENDED_STATUS: u8 = 0x2;
+    const EXTENDED_TONE: u8 = 0xE;
+
+    const RTC_MEMORY_SIZE: usize = 0x100;
+    const TIME_ADDRESS: usize = 0x00;
+    const MINUTES_NIBBLES: usize = 3;
+    const DAYS_NIBBLES: usize = 4;
+    const ALARM_ADDRESS: usize = 0x58;
+    const ALARM_ENABLED_ADDRESS: usize = 0x5F;
+    const TONE_ADDRESS: usize = 0x27;
+
+    const MINUTES_PER_DAY: u16 = 24 * 60;
+    const IR_NO_LIGHT: u8 = 0xC0;
+
+    const RTC_DATA_SIZE: usize = 17;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            mode: Mode::RamReadOnly,
+            rtc_memory: vec![0; Self::RTC_MEMORY_SIZE],
+            rtc_address: 0,
+            rtc_response: 0,
+            minutes: 0,
+            days: 0,
+            seconds: 0,
+            cycles: 0,
+            tone: None,
+        }
+    }
+
+    fn read_nibbles(&self, address: usize, count: usize) -> u16 {
+        (0..count).fold(0, |value, i| {
+            value | ((self.rtc_memory[address + i] as u16 & 0xF) << (i * 4))
+        })
+    }
+
+    fn write_nibbles(&mut self, address: usize, count: usize, value: u16) {
+        for i in 0..count {
+            self.rtc_memory[address + i] = (value >> (i * 4)) as u8 & 0xF;
+        }
+    }
+
+    fn run_command(&mut self, value: u8) {
+        let argument = value & 0x0F;
+        let address = self.rtc_address as usize;
+        match (value >> 4) & 0b111 {
+            Self::COMMAND_READ => {
+                self.rtc_response = self.rtc_memory[address];
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            Self::COMMAND_WRITE => self.rtc_memory[address] = argument,
+            Self::COMMAND_WRITE_INCREMENT => {
+                self.rtc_memory[address] = argument;
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            Self::COMMAND_ADDRESS_LOW => self.rtc_address = (self.rtc_address & 0xF0) | argument,
+            Self::COMMAND_ADDRESS_HIGH => {
+                self.rtc_address = (self.rtc_address & 0x0F) | (argument << 4)
+            }
+            Self::COMMAND_EXTENDED => match argument {
+                Self::EXTENDED_READ_TIME => {
+                    self.write_nibbles(Self::TIME_ADDRESS, Self::MINUTES_NIBBLES, self.minutes);
+                    self.write_nibbles(
+                        Self::TIME_ADDRESS + Self::MINUTES_NIBBLES,
+                        Self::DAYS_NIBBLES,
+                        self.days,
+                    );
+                }
+                Self::EXTENDED_WRITE_TIME => {
+                    self.minutes = self.read_nibbles(Self::TIME_ADDRESS, Self::MINUTES_NIBBLES)
+                        % Self::MINUTES_PER_DAY;
+                    self.days = self.read_nibbles(
+                        Self::TIME_ADDRESS + Self::MINUTES_NIBBLES,
+                        Self::DAYS_NIBBLES,
+                    );
+                    self.seconds = 0;
+                }
+                Self::EXTENDED_STATUS => self.rtc_response = 1,
+                Self::EXTENDED_TONE => self.tone = Some(self.rtc_memory[Self::TONE_ADDRESS]),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn ram_address(&self, ram: &[u8], address: u16) -> Option<usize> {
+        (!ram.is_empty())
+            .then(|| (address as usize + self.ram_bank as usize * RAM_BANK_SIZE) % ram.len())
+    }
+
+    fn advance_minutes(&mut self, minutes: u64) {
+        let minutes = self.minutes as u64 + minutes;
+        self.minutes = (minutes % Self::MINUTES_PER_DAY as u64) as u16;
+        self.days = self
+            .days
+            .wrapping_add((minutes / Self::MINUTES_PER_DAY as u64) as u16);
+    }
+
+    fn serialize_rtc<W>(&self, timestamp: u64) -> impl cf::SerializeFn<W>
+    where
+        W: io::Write,
+    {
+        cf::sequence::tuple((
+            cf::bytes::le_u64(timestamp),
+            cf::bytes::le_u16(self.minutes),
+            cf::bytes::le_u16(self.days),
+            cf::bytes::le_u16(self.read_nibbles(Self::ALARM_ADDRESS, Self::MINUTES_NIBBLES)),
+            cf::bytes::le_u16(self.read_nibbles(
+                Self::ALARM_ADDRESS + Self::MINUTES_NIBBLES,
+                Self::DAYS_NIBBLES,
+            )),
+            cf::bytes::le_u8(self.rtc_memory[Self::ALARM_ENABLED_ADDRESS] & 1),
+        ))
+    }
+}
+
+impl MapperOps for Huc3 {
+    fn read_rom(&mut self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            LOW_BANK_START..=LOW_BANK_END => rom[address as usize],
+            HIGH_BANK_START..=HIGH_BANK_END => {
+                let offset = self.rom_bank as usize * ROM_BANK_SIZE;
+                rom[((address - HIGH_BANK_START) as usize + offset) % rom.len()]
+            }
+            _ => panic!("Tried to read Huc3 rom out of range"),
+        }
+    }
+
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, self.rom_bank as usize)
+    }
+
+    fn mapped_ram_bank(&self) -> usize {
+        self.ram_bank as usize
+    }
+
+    fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
+        match address {
+            Self::MODE_START..=Self::MODE_END => {
+                self.mode = match value & 0x0F {
+                    0x0 => Mode::RamReadOnly,
+                    0xA => Mode::Ram,
+                    0xB => Mode::RtcCommand,
+                    0xC => Mode::RtcResponse,
+                    0xD => Mode::RtcSemaphore,
+                    0xE => Mode::Infrared,
+                    _ => Mode::Unknown,
+                }
+            }
+            Self::ROM_BANK_START..=Self::ROM_BANK_END => {
+                self.rom_bank = u8::max(value & 0b111_1111, 1);
+            }
+            Self::RAM_BANK_START..=Self::RAM_BANK_END => self.ram_bank = value & 0b11,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&mut self, ram: &[u8], address: u16) -> u8 {
+        match self.mode {
+            Mode::RamReadOnly | Mode::Ram => self
+                .ram_address(ram, address)
+                .map_or(0xFF, |address| ram[address]),
+            Mode::RtcResponse => self.rtc_response,
+            // Commands are executed immediately, the RTC is always ready.
+            Mode::RtcSemaphore => 1,
+            Mode::Infrared => Self::IR_NO_LIGHT,
+            Mode::RtcCommand | Mode::Unknown => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        match self.mode {
+            Mode::Ram => {
+                if let Some(address) = self.ram_address(ram, address) {
+                    ram[address] = value;
+                }
+            }
+            Mode::RtcCommand => self.run_command(value),
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, _ram: &mut [u8]) {
+        self.cycles += 4;
+        if self.cycles == Gameboy::CYCLES_PER_SECOND as usize {
+            self.cycles = 0;
+            self.seconds += 1;
+            if self.seconds == 60 {
+                self.seconds = 0;
+                self.advance_minutes(1);
+            }
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn rtc_data_size(&self) -> usize {
+        Self::RTC_DATA_SIZE
+    }
+
+    // Same layout as SameBoy, a timestamp followed by the time and alarm registers.
+    fn set_rtc_data<'a>(&mut self, rtc_data: &'a [u8]) -> nom::IResult<&'a [u8], ()> {
+        let (input, timestamp) = nom::number::complete::le_u64(rtc_data)?;
+        let (input, minutes) = nom::number::complete::le_u16(input)?;
+        let (input, days) = nom::number::complete::le_u16(input)?;
+        let (input, alarm_minutes) = nom::number::complete::le_u16(input)?;
+        let (input, alarm_days) = nom::number::complete::le_u16(input)?;
+        let (input, alarm_enabled) = nom::number::complete::u8(input)?;
+        let timestamp_now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        self.minutes = minutes % Self::MINUTES_PER_DAY;
+        self.days = days;
+        self.write_nibbles(Self::ALARM_ADDRESS, Self::MINUTES_NIBBLES, alarm_minutes);
+        self.write_nibbles(
+            Self::ALARM_ADDRESS + Self::MINUTES_NIBBLES,
+            Self::DAYS_NIBBLES,
+            alarm_days,
+        );
+        self.rtc_memory[Self::ALARM_ENABLED_ADDRESS] = alarm_enabled & 1;
+        if timestamp_now > timestamp {
+            self.advance_minutes((timestamp_now - timestamp) / 60);
+        }
+
+        Ok((input, ()))
+    }
+
+    fn rtc_data(&self) -> Option<Vec<u8>> {
+        let mut buf: Vec<u8> = vec![];
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        cf::gen(self.serialize_rtc(timestamp), &mut buf).unwrap();
+        Some(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODE_RTC_COMMAND: u8 = 0x0B;
+    const MODE_RTC_RESPONSE: u8 = 0x0C;
+
+    fn command(huc3: &mut Huc3, value: u8) {
+        huc3.write_rom(&mut [], Huc3::MODE_START, MODE_RTC_COMMAND);
+        huc3.write_ram(&mut [], 0x0000, value);
+    }
+
+    fn set_address(huc3: &mut Huc3, address: usize) {
+        command(huc3, Huc3::COMMAND_ADDRESS_LOW << 4 | (address as u8 & 0xF));
+        command(huc3, Huc3::COMMAND_ADDRESS_HIGH << 4 | (address as u8 >> 4));
+    }
+
+    fn write(huc3: &mut Huc3, address: usize, nibbles: usize, value: u16) {
+        set_address(huc3, address);
+        for i in 0..nibbles {
+            command(
+                huc3,
+                Huc3::COMMAND_WRITE_INCREMENT << 4 | ((value >> (i * 4)) as u8 & 0xF),
+            );
+        }
+    }
+
+    fn read(huc3: &mut Huc3, address: usize, nibbles: usize) -> u16 {
+        set_address(huc3, address);
+        (0..nibbles).fold(0, |value, i| {
+            command(huc3, Huc3::COMMAND_READ << 4);
+            huc3.write_rom(&mut [], Huc3::MODE_START, MODE_RTC_RESPONSE);
+            value | ((huc3.read_ram(&[], 0x0000) as u16 & 0xF) << (i * 4))
+        })
+    }
+
+    #[test]
+    fn rtc_save_and_reload() {
+        let minutes = 12 * 60 + 34;
+        let days = 1234;
+        let alarm_minutes = 8 * 60;
+        let alarm_days = 2;
+
+        let mut huc3 = Huc3::new();
+        write(
+            &mut huc3,
+            Huc3::TIME_ADDRESS,
+            Huc3::MINUTES_NIBBLES,
+            minutes,
+        );
+        write(
+            &mut huc3,
+            Huc3::TIME_ADDRESS + Huc3::MINUTES_NIBBLES,
+            Huc3::DAYS_NIBBLES,
+            days,
+        );
+        command(
+            &mut huc3,
+            Huc3::COMMAND_EXTENDED << 4 | Huc3::EXTENDED_WRITE_TIME,
+        );
+        write(
+            &mut huc3,
+            Huc3::ALARM_ADDRESS,
+            Huc3::MINUTES_NIBBLES,
+            alarm_minutes,
+        );
+        write(
+            &mut huc3,
+            Huc3::ALARM_ADDRESS + Huc3::MINUTES_NIBBLES,
+            Huc3::DAYS_NIBBLES,
+            alarm_days,
+        );
+        write(&mut huc3, Huc3::ALARM_ENABLED_ADDRESS, 1, 1);
+
+        let rtc_data = huc3.rtc_data().unwrap();
+        assert_eq!(rtc_data.len(), Huc3::RTC_DATA_SIZE);
+        let mut huc3 = Huc3::new();
+        let (rest, ()) = huc3.set_rtc_data(&rtc_data).unwrap();
+        assert!(rest.is_empty());
+
+        command(
+            &mut huc3,
+            Huc3::COMMAND_EXTENDED << 4 | Huc3::EXTENDED_READ_TIME,
+        );
+        assert_eq!(
+            read(&mut huc3, Huc3::TIME_ADDRESS, Huc3::MINUTES_NIBBLES),
+            minutes
+        );
+        assert_eq!(
+            read(
+                &mut huc3,
+                Huc3::TIME_ADDRESS + Huc3::MINUTES_NIBBLES,
+                Huc3::DAYS_NIBBLES
+            ),
+            days
+        );
+        assert_eq!(
+            read(&mut huc3, Huc3::ALARM_ADDRESS, Huc3::MINUTES_NIBBLES),
+            alarm_minutes
+        );
+        assert_eq!(
+            read(
+                &mut huc3,
+                Huc3::ALARM_ADDRESS + Huc3::MINUTES_NIBBLES,
+                Huc3::DAYS_NIBBLES
+            ),
+            alarm_days
+        );
+        assert_eq!(read(&mut huc3, Huc3::ALARM_ENABLED_ADDRESS, 1), 1);
+    }
+}
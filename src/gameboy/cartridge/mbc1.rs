@@ -18,6 +18,8 @@ pub struct Mbc1 {
     rom_bank: u8,
     ram_bank: u8,
     bank_mode: BankMode,
+    /// MBC1M multicart, with the secondary bank bits wired one bit lower.
+    multicart: bool,
 }
 
 impl Mbc1 {
@@ -35,7 +37,22 @@ impl Mbc1 {
     const WRITE_MODE_START: u16 = 0x6000;
     const WRITE_MODE_END: u16 = 0x7FFF;
 
-    pub(crate) const fn new(rom_bank_count: u16, has_ram: bool, has_battery: bool) -> Self {
+    const MULTICART_ROM_BANK_COUNT: u16 = 64;
+    const MULTICART_GAME_BANK_COUNT: usize = 16;
+    const LOGO_START: usize = 0x104;
+    const LOGO: [u8; 48] = [
+        0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00,
+        0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD,
+        0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB,
+        0xB9, 0x33, 0x3E,
+    ];
+
+    pub(crate) const fn new(
+        rom_bank_count: u16,
+        has_ram: bool,
+        has_battery: bool,
+        multicart: bool,
+    ) -> Self {
         Self {
             has_ram,
             has_battery,
@@ -45,14 +62,36 @@ impl Mbc1 {
             ram_bank: 0,
             bank_mode: BankMode::Rom,
             rom_bank_mask: rom_bank_count as u8 - 1,
+            multicart,
+        }
+    }
+
+    /// Multicarts are 1 MiB compilations with a Nintendo logo at the start of each game,
+    /// the game selection menu being the one in the first 16 banks.
+    pub(crate) fn is_multicart(rom: &[u8], rom_bank_count: u16) -> bool {
+        let logo_start = Self::MULTICART_GAME_BANK_COUNT * ROM_BANK_SIZE + Self::LOGO_START;
+        rom_bank_count == Self::MULTICART_ROM_BANK_COUNT
+            && rom.get(logo_start..logo_start + Self::LOGO.len()) == Some(&Self::LOGO)
+    }
+
+    const fn secondary_bank_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        } else {
+            5
         }
     }
 
+    const fn primary_bank_mask(&self) -> u8 {
+        (1 << self.secondary_bank_shift()) - 1
+    }
+
     const fn rom_address_low(&self, address: u16) -> usize {
         match self.bank_mode {
             BankMode::Rom => address as usize,
             BankMode::Ram => {
-                address as usize + (self.rom_bank & 0b1110_0000) as usize * ROM_BANK_SIZE
+                address as usize
+                    + (self.rom_bank & !self.primary_bank_mask()) as usize * ROM_BANK_SIZE
             }
         }
     }
@@ -78,6 +117,21 @@ impl MapperOps for Mbc1 {
         }
     }
 
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        let low_bank = match self.bank_mode {
+            BankMode::Rom => 0,
+            BankMode::Ram => (self.rom_bank & !self.primary_bank_mask()) as usize,
+        };
+        (low_bank, self.rom_bank as usize)
+    }
+
+    fn mapped_ram_bank(&self) -> usize {
+        match self.bank_mode {
+            BankMode::Rom => 0,
+            BankMode::Ram => self.ram_bank as usize,
+        }
+    }
+
     fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
         match address {
             Self::WRITE_RAM_ENABLE_START..=Self::WRITE_RAM_ENABLE_END => {
@@ -88,12 +142,15 @@ impl MapperOps for Mbc1 {
                 if lower_bits as u16 >= self.rom_bank_count {
                     lower_bits &= self.rom_bank_mask;
                 }
-                self.rom_bank = (self.rom_bank & 0b1110_0000) | u8::max(lower_bits, 1);
+                // Bank 0 is checked on all 5 bits, even when multicarts only wire 4 of them.
+                let lower_bits = u8::max(lower_bits, 1) & self.primary_bank_mask();
+                self.rom_bank = (self.rom_bank & !self.primary_bank_mask()) | lower_bits;
             }
             Self::WRITE_MODE_BANK_START..=Self::WRITE_MODE_BANK_END => {
                 let bits = value & 0b11;
                 self.ram_bank = bits;
-                self.rom_bank = (self.rom_bank & 0b11111) | (bits << 5);
+                self.rom_bank = (self.rom_bank & self.primary_bank_mask())
+                    | (bits << self.secondary_bank_shift());
             }
             Self::WRITE_MODE_START..=Self::WRITE_MODE_END => {
                 self.bank_mode = match value & 0b1 {
@@ -124,3 +181,57 @@ impl MapperOps for Mbc1 {
         self.has_battery
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1 MiB multicart whose banks start with their own number, with a game at bank 0x10.
+    fn multicart_rom() -> Vec<u8> {
+        let mut rom = vec![0; Mbc1::MULTICART_ROM_BANK_COUNT as usize * ROM_BANK_SIZE];
+        for (bank, data) in rom.chunks_exact_mut(ROM_BANK_SIZE).enumerate() {
+            data[0] = bank as u8;
+        }
+        let logo_start = Mbc1::MULTICART_GAME_BANK_COUNT * ROM_BANK_SIZE + Mbc1::LOGO_START;
+        rom[logo_start..logo_start + Mbc1::LOGO.len()].copy_from_slice(&Mbc1::LOGO);
+        rom
+    }
+
+    fn banks(mbc: &mut Mbc1, rom: &[u8]) -> (u8, u8) {
+        (mbc.read_rom(rom, 0x0000), mbc.read_rom(rom, 0x4000))
+    }
+
+    #[test]
+    fn multicart_detection() {
+        let mut rom = multicart_rom();
+        assert!(Mbc1::is_multicart(&rom, 64));
+        assert!(!Mbc1::is_multicart(&rom, 32));
+        rom[Mbc1::MULTICART_GAME_BANK_COUNT * ROM_BANK_SIZE + Mbc1::LOGO_START] = 0;
+        assert!(!Mbc1::is_multicart(&rom, 64));
+    }
+
+    #[test]
+    fn multicart_banking() {
+        let mut rom = multicart_rom();
+        let mut mbc = Mbc1::new(64, false, false, true);
+        assert_eq!(banks(&mut mbc, &rom), (0x00, 0x01));
+
+        // The secondary bits select the game in bits 4 and 5, bit 4 of the primary bits is unused.
+        mbc.write_rom(&mut rom, 0x2000, 0x13);
+        assert_eq!(banks(&mut mbc, &rom), (0x00, 0x03));
+        mbc.write_rom(&mut rom, 0x4000, 0x01);
+        assert_eq!(banks(&mut mbc, &rom), (0x00, 0x13));
+
+        // Bank 0 is checked on all 5 primary bits, so 0x10 maps the first bank of the game.
+        mbc.write_rom(&mut rom, 0x2000, 0x10);
+        assert_eq!(banks(&mut mbc, &rom), (0x00, 0x10));
+        mbc.write_rom(&mut rom, 0x2000, 0x00);
+        assert_eq!(banks(&mut mbc, &rom), (0x00, 0x11));
+
+        // In RAM banking mode, the low bank follows the selected game.
+        mbc.write_rom(&mut rom, 0x6000, 0x01);
+        mbc.write_rom(&mut rom, 0x4000, 0x03);
+        assert_eq!(banks(&mut mbc, &rom), (0x30, 0x31));
+        assert_eq!(mbc.mapped_rom_banks(64), (0x30, 0x31));
+    }
+}
@@ -41,6 +41,10 @@ impl MapperOps for Mbc2 {
         }
     }
 
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, self.rom_bank as usize)
+    }
+
     fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
         if let LOW_BANK_START..=LOW_BANK_END = address {
             if address & 0x100 == 0 {
@@ -96,6 +96,9 @@ impl Mbc3 {
     const RTC_DAY_LOW_REG: u8 = 0x0B;
     const RTC_DAY_HIGH_REG: u8 = 0x0C;
 
+    /// The current and latched registers as 32 bits values, followed by a 64 bits timestamp.
+    const RTC_DATA_SIZE: usize = 2 * 5 * 4 + 8;
+
     pub(crate) fn new(has_rtc: bool, has_ram: bool, has_battery: bool) -> Self {
         Self {
             has_rtc,
@@ -110,81 +113,6 @@ impl Mbc3 {
             cycles: 0,
         }
     }
-
-    // See https://bgb.bircd.org/rtcsave.html for the format.
-    pub(crate) fn set_rtc_data<'a>(&mut self, rtc_data: &'a [u8]) -> nom::IResult<&'a [u8], ()> {
-        if !self.has_rtc {
-            return Ok((rtc_data, ()));
-        }
-
-        let (input, current_time) = RtcRegisters::parse(rtc_data)?;
-        let (input, latched_time) = RtcRegisters::parse(input)?;
-        let (input, timestamp) = nom::number::complete::le_u64(input)?;
-        let timestamp_now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        self.current_time = current_time;
-        self.latched_time = latched_time;
-        if timestamp_now > timestamp && !self.current_time.halt {
-            let carry_add = |a: u8, b, prev_carry| {
-                let (sum, carry) = a.overflowing_add(b);
-                let (sum, carry2) = sum.overflowing_add(u8::from(prev_carry));
-                (sum, carry | carry2)
-            };
-
-            let delta = timestamp_now - timestamp;
-            let (seconds, carry) = ((delta % 60) as u8).overflowing_add(self.current_time.seconds);
-            self.current_time.seconds = seconds;
-            let (minutes, carry) = carry_add(
-                ((delta % 3600) / 60) as u8,
-                self.current_time.minutes,
-                carry,
-            );
-            self.current_time.minutes = minutes;
-            let (hours, carry) = carry_add(
-                ((delta % 86400) / 3600) as u8,
-                self.current_time.hours,
-                carry,
-            );
-            self.current_time.hours = hours;
-            self.current_time.days +=
-                u16::min((delta / 86400) as u16, 0b1_1111_1111) + u16::from(carry);
-            if self.current_time.days > 0b1_1111_1111 {
-                self.current_time.days -= 0b1_1111_1111;
-                self.current_time.carry = true;
-            }
-        }
-
-        Ok((input, ()))
-    }
-
-    pub(crate) fn rtc_data(&self) -> Option<Vec<u8>> {
-        if !self.has_rtc {
-            return None;
-        }
-
-        let mut buf: Vec<u8> = vec![];
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        cf::gen(
-            cf::sequence::tuple((
-                self.current_time.serialize(),
-                self.latched_time.serialize(),
-                cf::bytes::le_u64(timestamp),
-            )),
-            &mut buf,
-        )
-        .unwrap();
-        Some(buf)
-    }
-
-    pub(crate) fn has_rtc(&self) -> bool {
-        self.has_rtc
-    }
 }
 
 impl MapperOps for Mbc3 {
@@ -198,6 +126,18 @@ impl MapperOps for Mbc3 {
         }
     }
 
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, self.rom_bank as usize)
+    }
+
+    fn mapped_ram_bank(&self) -> usize {
+        match self.ram_bank_rtc_select {
+            bank @ 0..=Self::MAX_RAM_BANK_SELECT => bank as usize,
+            // The rtc registers are mapped instead.
+            _ => 0,
+        }
+    }
+
     fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
         match address {
             Self::RAM_RTC_ENABLE_START..=Self::RAM_RTC_ENABLE_END => {
@@ -258,7 +198,7 @@ impl MapperOps for Mbc3 {
         }
     }
 
-    fn tick(&mut self) {
+    fn tick(&mut self, _ram: &mut [u8]) {
         if !self.has_rtc || self.current_time.halt {
             self.cycles = 0;
         } else {
@@ -296,4 +236,83 @@ impl MapperOps for Mbc3 {
     fn has_battery(&self) -> bool {
         self.has_battery
     }
+
+    fn rtc_data_size(&self) -> usize {
+        if self.has_rtc {
+            Self::RTC_DATA_SIZE
+        } else {
+            0
+        }
+    }
+
+    // See https://bgb.bircd.org/rtcsave.html for the format.
+    fn set_rtc_data<'a>(&mut self, rtc_data: &'a [u8]) -> nom::IResult<&'a [u8], ()> {
+        if !self.has_rtc {
+            return Ok((rtc_data, ()));
+        }
+
+        let (input, current_time) = RtcRegisters::parse(rtc_data)?;
+        let (input, latched_time) = RtcRegisters::parse(input)?;
+        let (input, timestamp) = nom::number::complete::le_u64(input)?;
+        let timestamp_now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        self.current_time = current_time;
+        self.latched_time = latched_time;
+        if timestamp_now > timestamp && !self.current_time.halt {
+            let carry_add = |a: u8, b, prev_carry| {
+                let (sum, carry) = a.overflowing_add(b);
+                let (sum, carry2) = sum.overflowing_add(u8::from(prev_carry));
+                (sum, carry | carry2)
+            };
+
+            let delta = timestamp_now - timestamp;
+            let (seconds, carry) = ((delta % 60) as u8).overflowing_add(self.current_time.seconds);
+            self.current_time.seconds = seconds;
+            let (minutes, carry) = carry_add(
+                ((delta % 3600) / 60) as u8,
+                self.current_time.minutes,
+                carry,
+            );
+            self.current_time.minutes = minutes;
+            let (hours, carry) = carry_add(
+                ((delta % 86400) / 3600) as u8,
+                self.current_time.hours,
+                carry,
+            );
+            self.current_time.hours = hours;
+            self.current_time.days +=
+                u16::min((delta / 86400) as u16, 0b1_1111_1111) + u16::from(carry);
+            if self.current_time.days > 0b1_1111_1111 {
+                self.current_time.days -= 0b1_1111_1111;
+                self.current_time.carry = true;
+            }
+        }
+
+        Ok((input, ()))
+    }
+
+    fn rtc_data(&self) -> Option<Vec<u8>> {
+        if !self.has_rtc {
+            return None;
+        }
+
+        let mut buf: Vec<u8> = vec![];
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        cf::gen(
+            cf::sequence::tuple((
+                self.current_time.serialize(),
+                self.latched_time.serialize(),
+                cf::bytes::le_u64(timestamp),
+            )),
+            &mut buf,
+        )
+        .unwrap();
+        Some(buf)
+    }
 }
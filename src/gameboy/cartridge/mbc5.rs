@@ -5,16 +5,16 @@ use super::{
     ROM_BANK_SIZE,
 };
 
-// TODO: Rumble with controller.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Mbc5 {
-    _has_rumble: bool,
+    has_rumble: bool,
     has_ram: bool,
     has_battery: bool,
     rom_bank: u16,
     ram_bank: u8,
     ram_bank_mask: u8,
     ram_enabled: bool,
+    rumble: bool,
 }
 
 impl Mbc5 {
@@ -27,15 +27,26 @@ impl Mbc5 {
     const RAM_BANK_START: u16 = 0x4000;
     const RAM_BANK_END: u16 = 0x5FFF;
 
+    const RUMBLE_BIT: u8 = 0b1000;
+
     pub(crate) fn new(has_rumble: bool, has_ram: bool, has_battery: bool) -> Self {
         Self {
-            _has_rumble: has_rumble,
+            has_rumble,
             has_ram,
             has_battery,
             rom_bank: 1,
             ram_bank: 0,
             ram_bank_mask: if has_rumble { 0b0111 } else { 0b1111 },
             ram_enabled: false,
+            rumble: false,
+        }
+    }
+
+    fn ram_address(&self, ram: &[u8], address: u16) -> Option<usize> {
+        if self.has_ram && self.ram_enabled && !ram.is_empty() {
+            Some((address as usize + self.ram_bank as usize * RAM_BANK_SIZE) % ram.len())
+        } else {
+            None
         }
     }
 }
@@ -45,12 +56,22 @@ impl MapperOps for Mbc5 {
         match address {
             LOW_BANK_START..=LOW_BANK_END => rom[address as usize],
             HIGH_BANK_START..=HIGH_BANK_END => {
-                rom[address as usize + (self.rom_bank - 1) as usize * ROM_BANK_SIZE]
+                // Bank 0 can also be mapped here.
+                let offset = self.rom_bank as usize * ROM_BANK_SIZE;
+                rom[((address - HIGH_BANK_START) as usize + offset) % rom.len()]
             }
-            _ => panic!("Tried to read Mbc3 rom out of range"),
+            _ => panic!("Tried to read Mbc5 rom out of range"),
         }
     }
 
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, self.rom_bank as usize)
+    }
+
+    fn mapped_ram_bank(&self) -> usize {
+        self.ram_bank as usize
+    }
+
     fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
         match address {
             Self::RAM_ENABLE_START..=Self::RAM_ENABLE_END => {
@@ -62,22 +83,32 @@ impl MapperOps for Mbc5 {
             Self::ROM_BANK_HIGH_START..=Self::ROM_BANK_HIGH_END => {
                 self.rom_bank = (self.rom_bank & 0x00FF) | ((value as u16 & 1) << 8);
             }
-            Self::RAM_BANK_START..=Self::RAM_BANK_END => self.ram_bank = value & self.ram_bank_mask,
-            _ => panic!("Tried to write Mbc3 rom out of range"),
+            Self::RAM_BANK_START..=Self::RAM_BANK_END => {
+                self.ram_bank = value & self.ram_bank_mask;
+                if self.has_rumble {
+                    self.rumble = value & Self::RUMBLE_BIT != 0;
+                }
+            }
+            _ => {}
         }
     }
 
     fn read_ram(&mut self, ram: &[u8], address: u16) -> u8 {
-        if self.has_ram {
-            ram[address as usize + self.ram_bank as usize * RAM_BANK_SIZE]
-        } else {
-            0xFF
-        }
+        self.ram_address(ram, address)
+            .map_or(0xFF, |address| ram[address])
     }
 
-    fn write_ram(&mut self, _ram: &mut [u8], _address: u16, _value: u8) {}
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if let Some(address) = self.ram_address(ram, address) {
+            ram[address] = value;
+        }
+    }
 
     fn has_battery(&self) -> bool {
         self.has_battery
     }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
 }
@@ -0,0 +1,359 @@
+use serde::{Deserialize, Serialize};
+
+use super::{MapperOps, HIGH_BANK_END, HIGH_BANK_START, LOW_BANK_END, LOW_BANK_START};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum FlashState {
+    Ready,
+    Unlocked1,
+    Unlocked2,
+    Program,
+    EraseReady,
+    EraseUnlocked1,
+    EraseUnlocked2,
+    Id,
+}
+
+/// Rom and flash are mapped in two 8 KiB areas, RAM in two 4 KiB areas.
+/// The flash content is stored after the RAM in the cartridge RAM, to be saved with it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Mbc6 {
+    ram_size: usize,
+    ram_enabled: bool,
+    ram_banks: [u8; 2],
+    rom_banks: [u8; 2],
+    flash_selected: [bool; 2],
+    flash_enabled: bool,
+    flash_write_enabled: bool,
+    flash_state: FlashState,
+}
+
+impl Mbc6 {
+    pub(crate) const FLASH_SIZE: usize = 0x100000;
+
+    const RAM_ENABLE_START: u16 = 0x0000;
+    const RAM_ENABLE_END: u16 = 0x03FF;
+    const RAM_BANK_A_START: u16 = 0x0400;
+    const RAM_BANK_A_END: u16 = 0x07FF;
+    const RAM_BANK_B_START: u16 = 0x0800;
+    const RAM_BANK_B_END: u16 = 0x0BFF;
+    const FLASH_ENABLE_START: u16 = 0x0C00;
+    const FLASH_ENABLE_END: u16 = 0x0FFF;
+    const FLASH_WRITE_ENABLE: u16 = 0x1000;
+    const ROM_BANK_A_START: u16 = 0x2000;
+    const ROM_BANK_A_END: u16 = 0x27FF;
+    const FLASH_SELECT_A_START: u16 = 0x2800;
+    const FLASH_SELECT_A_END: u16 = 0x2FFF;
+    const ROM_BANK_B_START: u16 = 0x3000;
+    const ROM_BANK_B_END: u16 = 0x37FF;
+    const FLASH_SELECT_B_START: u16 = 0x3800;
+    const FLASH_SELECT_B_END: u16 = 0x3FFF;
+
+    const ROM_AREA_SIZE: usize = 0x2000;
+    const RAM_AREA_SIZE: usize = 0x1000;
+    const FLASH_SELECT_VALUE: u8 = 0x08;
+
+    const FLASH_SECTOR_SIZE: usize = 0x20000;
+    const FLASH_COMMAND_ADDRESS_MASK: usize = 0x7FFF;
+    const FLASH_UNLOCK_ADDRESS_1: usize = 0x5555;
+    const FLASH_UNLOCK_ADDRESS_2: usize = 0x2AAA;
+    const FLASH_UNLOCK_VALUE_1: u8 = 0xAA;
+    const FLASH_UNLOCK_VALUE_2: u8 = 0x55;
+    const FLASH_COMMAND_PROGRAM: u8 = 0xA0;
+    const FLASH_COMMAND_ERASE: u8 = 0x80;
+    const FLASH_COMMAND_ID: u8 = 0x90;
+    const FLASH_COMMAND_ERASE_SECTOR: u8 = 0x30;
+    const FLASH_COMMAND_ERASE_CHIP: u8 = 0x10;
+    const FLASH_COMMAND_RESET: u8 = 0xF0;
+    const FLASH_MANUFACTURER_ID: u8 = 0xC2;
+    const FLASH_DEVICE_ID: u8 = 0x81;
+
+    pub(crate) const fn new(ram_size: usize) -> Self {
+        Self {
+            ram_size,
+            ram_enabled: false,
+            ram_banks: [0; 2],
+            rom_banks: [0; 2],
+            flash_selected: [false; 2],
+            flash_enabled: false,
+            flash_write_enabled: false,
+            flash_state: FlashState::Ready,
+        }
+    }
+
+    /// Area index and offset in it of an address in the 0x4000-0x7FFF range.
+    const fn area(address: u16) -> (usize, usize) {
+        let area = (address as usize >> 13) & 1;
+        (area, address as usize & (Self::ROM_AREA_SIZE - 1))
+    }
+
+    fn flash_address(&self, area: usize, offset: usize) -> usize {
+        (self.rom_banks[area] as usize * Self::ROM_AREA_SIZE + offset) % Self::FLASH_SIZE
+    }
+
+    fn write_flash(&mut self, flash: &mut [u8], address: usize, value: u8) {
+        let command_address = address & Self::FLASH_COMMAND_ADDRESS_MASK;
+        if value == Self::FLASH_COMMAND_RESET {
+            self.flash_state = FlashState::Ready;
+            return;
+        }
+
+        self.flash_state = match self.flash_state {
+            FlashState::Ready | FlashState::Id
+                if command_address == Self::FLASH_UNLOCK_ADDRESS_1
+                    && value == Self::FLASH_UNLOCK_VALUE_1 =>
+            {
+                FlashState::Unlocked1
+            }
+            FlashState::Unlocked1
+                if command_address == Self::FLASH_UNLOCK_ADDRESS_2
+                    && value == Self::FLASH_UNLOCK_VALUE_2 =>
+            {
+                FlashState::Unlocked2
+            }
+            FlashState::Unlocked2 if command_address == Self::FLASH_UNLOCK_ADDRESS_1 => match value
+            {
+                Self::FLASH_COMMAND_PROGRAM => FlashState::Program,
+                Self::FLASH_COMMAND_ERASE => FlashState::EraseReady,
+                Self::FLASH_COMMAND_ID => FlashState::Id,
+                _ => FlashState::Ready,
+            },
+            FlashState::Program => {
+                // Programming can only clear bits, erasing sets them back.
+                flash[address] &= value;
+                FlashState::Ready
+            }
+            FlashState::EraseReady
+                if command_address == Self::FLASH_UNLOCK_ADDRESS_1
+                    && value == Self::FLASH_UNLOCK_VALUE_1 =>
+            {
+                FlashState::EraseUnlocked1
+            }
+            FlashState::EraseUnlocked1
+                if command_address == Self::FLASH_UNLOCK_ADDRESS_2
+                    && value == Self::FLASH_UNLOCK_VALUE_2 =>
+            {
+                FlashState::EraseUnlocked2
+            }
+            FlashState::EraseUnlocked2 => {
+                match value {
+                    Self::FLASH_COMMAND_ERASE_SECTOR => {
+                        let start = address - address % Self::FLASH_SECTOR_SIZE;
+                        flash[start..start + Self::FLASH_SECTOR_SIZE].fill(0xFF);
+                    }
+                    Self::FLASH_COMMAND_ERASE_CHIP => flash.fill(0xFF),
+                    _ => {}
+                }
+                FlashState::Ready
+            }
+            _ => FlashState::Ready,
+        };
+    }
+}
+
+impl MapperOps for Mbc6 {
+    fn read_rom(&mut self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            LOW_BANK_START..=LOW_BANK_END => rom[address as usize],
+            HIGH_BANK_START..=HIGH_BANK_END => {
+                let (area, offset) = Self::area(address);
+                let bank = self.rom_banks[area] as usize;
+                rom[(bank * Self::ROM_AREA_SIZE + offset) % rom.len()]
+            }
+            _ => panic!("Tried to read Mbc6 rom out of range"),
+        }
+    }
+
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        // Banks are switched in 8 KiB areas, only the first one is reported.
+        (0, self.rom_banks[0] as usize / 2)
+    }
+
+    fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
+        match address {
+            Self::RAM_ENABLE_START..=Self::RAM_ENABLE_END => {
+                self.ram_enabled = value & 0x0F == 0x0A
+            }
+            Self::RAM_BANK_A_START..=Self::RAM_BANK_A_END => self.ram_banks[0] = value & 0b111,
+            Self::RAM_BANK_B_START..=Self::RAM_BANK_B_END => self.ram_banks[1] = value & 0b111,
+            Self::FLASH_ENABLE_START..=Self::FLASH_ENABLE_END => {
+                self.flash_enabled = value & 1 != 0
+            }
+            Self::FLASH_WRITE_ENABLE => self.flash_write_enabled = value & 1 != 0,
+            Self::ROM_BANK_A_START..=Self::ROM_BANK_A_END => self.rom_banks[0] = value & 0x7F,
+            Self::FLASH_SELECT_A_START..=Self::FLASH_SELECT_A_END => {
+                self.flash_selected[0] = value == Self::FLASH_SELECT_VALUE
+            }
+            Self::ROM_BANK_B_START..=Self::ROM_BANK_B_END => self.rom_banks[1] = value & 0x7F,
+            Self::FLASH_SELECT_B_START..=Self::FLASH_SELECT_B_END => {
+                self.flash_selected[1] = value == Self::FLASH_SELECT_VALUE
+            }
+            _ => {}
+        }
+    }
+
+    /// Rom reads, with the flash stored after the RAM mapped in the areas where it is selected.
+    fn read_rom_with_ram(&mut self, rom: &[u8], ram: &[u8], address: u16) -> u8 {
+        let (area, offset) = Self::area(address);
+        match address {
+            HIGH_BANK_START..=HIGH_BANK_END if self.flash_selected[area] => {
+                if !self.flash_enabled {
+                    0xFF
+                } else if self.flash_state == FlashState::Id {
+                    match offset {
+                        0 => Self::FLASH_MANUFACTURER_ID,
+                        1 => Self::FLASH_DEVICE_ID,
+                        _ => 0xFF,
+                    }
+                } else {
+                    ram[self.ram_size + self.flash_address(area, offset)]
+                }
+            }
+            _ => self.read_rom(rom, address),
+        }
+    }
+
+    fn write_rom_with_ram(&mut self, rom: &mut [u8], ram: &mut [u8], address: u16, value: u8) {
+        let (area, offset) = Self::area(address);
+        match address {
+            HIGH_BANK_START..=HIGH_BANK_END if self.flash_selected[area] => {
+                if self.flash_enabled && self.flash_write_enabled {
+                    let address = self.flash_address(area, offset);
+                    self.write_flash(&mut ram[self.ram_size..], address, value);
+                }
+            }
+            _ => self.write_rom(rom, address, value),
+        }
+    }
+
+    fn read_ram(&mut self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled || self.ram_size == 0 {
+            return 0xFF;
+        }
+        let area = address as usize / Self::RAM_AREA_SIZE;
+        let offset = address as usize % Self::RAM_AREA_SIZE;
+        ram[(self.ram_banks[area] as usize * Self::RAM_AREA_SIZE + offset) % self.ram_size]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if !self.ram_enabled || self.ram_size == 0 {
+            return;
+        }
+        let area = address as usize / Self::RAM_AREA_SIZE;
+        let offset = address as usize % Self::RAM_AREA_SIZE;
+        ram[(self.ram_banks[area] as usize * Self::RAM_AREA_SIZE + offset) % self.ram_size] = value;
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn sram_size(&self, _ram_size: usize) -> usize {
+        self.ram_size
+    }
+
+    fn init_ram(&self, ram: &mut [u8]) {
+        // Flash memory starts erased.
+        ram[self.ram_size..].fill(0xFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAM_SIZE: usize = 0x8000;
+    const FLASH_ADDRESS: usize = 0x4100;
+    /// Flash area A, mapped to flash 0x4000 with bank 2.
+    const AREA_A: u16 = 0x4000;
+    /// Flash area B, mapped to flash 0x2000 with bank 1.
+    const AREA_B: u16 = 0x6000;
+
+    struct Cartridge {
+        mbc: Mbc6,
+        rom: Vec<u8>,
+        ram: Vec<u8>,
+    }
+
+    impl Cartridge {
+        /// Maps the flash in both areas so that both unlock addresses can be written.
+        fn new() -> Self {
+            let mut cartridge = Self {
+                mbc: Mbc6::new(RAM_SIZE),
+                rom: vec![0; 0x20000],
+                ram: vec![0; RAM_SIZE + Mbc6::FLASH_SIZE],
+            };
+            cartridge.mbc.init_ram(&mut cartridge.ram);
+            for (address, value) in [
+                (Mbc6::FLASH_ENABLE_START, 0x01),
+                (Mbc6::FLASH_WRITE_ENABLE, 0x01),
+                (Mbc6::ROM_BANK_A_START, 0x02),
+                (Mbc6::FLASH_SELECT_A_START, Mbc6::FLASH_SELECT_VALUE),
+                (Mbc6::ROM_BANK_B_START, 0x01),
+                (Mbc6::FLASH_SELECT_B_START, Mbc6::FLASH_SELECT_VALUE),
+            ] {
+                cartridge.write(address, value);
+            }
+            cartridge
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.mbc
+                .write_rom_with_ram(&mut self.rom, &mut self.ram, address, value);
+        }
+
+        fn read(&mut self, address: u16) -> u8 {
+            self.mbc.read_rom_with_ram(&self.rom, &self.ram, address)
+        }
+
+        fn command(&mut self, command: u8) {
+            self.write(AREA_A + 0x1555, Mbc6::FLASH_UNLOCK_VALUE_1);
+            self.write(AREA_B + 0x0AAA, Mbc6::FLASH_UNLOCK_VALUE_2);
+            self.write(AREA_A + 0x1555, command);
+        }
+
+        fn program(&mut self, address: u16, value: u8) {
+            self.command(Mbc6::FLASH_COMMAND_PROGRAM);
+            self.write(address, value);
+        }
+
+        fn flash(&self, address: usize) -> u8 {
+            self.ram[RAM_SIZE + address]
+        }
+    }
+
+    #[test]
+    fn program() {
+        let mut cartridge = Cartridge::new();
+        let address = AREA_A + (FLASH_ADDRESS % Mbc6::ROM_AREA_SIZE) as u16;
+        cartridge.program(address, 0x5A);
+        assert_eq!(cartridge.flash(FLASH_ADDRESS), 0x5A);
+        assert_eq!(cartridge.read(address), 0x5A);
+
+        // Programming only clears bits, and needs the unlock sequence each time.
+        cartridge.program(address, 0x0F);
+        assert_eq!(cartridge.flash(FLASH_ADDRESS), 0x0A);
+        cartridge.write(address, 0x00);
+        assert_eq!(cartridge.flash(FLASH_ADDRESS), 0x0A);
+
+        cartridge.write(Mbc6::FLASH_WRITE_ENABLE, 0x00);
+        cartridge.program(address, 0x00);
+        assert_eq!(cartridge.flash(FLASH_ADDRESS), 0x0A);
+    }
+
+    #[test]
+    fn sector_erase() {
+        let mut cartridge = Cartridge::new();
+        let address = AREA_A + (FLASH_ADDRESS % Mbc6::ROM_AREA_SIZE) as u16;
+        cartridge.program(address, 0x00);
+        let next_sector = Mbc6::FLASH_SECTOR_SIZE + FLASH_ADDRESS;
+        cartridge.ram[RAM_SIZE + next_sector] = 0x00;
+
+        cartridge.command(Mbc6::FLASH_COMMAND_ERASE);
+        cartridge.write(AREA_A + 0x1555, Mbc6::FLASH_UNLOCK_VALUE_1);
+        cartridge.write(AREA_B + 0x0AAA, Mbc6::FLASH_UNLOCK_VALUE_2);
+        cartridge.write(address, Mbc6::FLASH_COMMAND_ERASE_SECTOR);
+        assert_eq!(cartridge.flash(FLASH_ADDRESS), 0xFF);
+        assert_eq!(cartridge.flash(next_sector), 0x00);
+    }
+}
@@ -0,0 +1,413 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    MapperOps, HIGH_BANK_END, HIGH_BANK_START, LOW_BANK_END, LOW_BANK_START, ROM_BANK_SIZE,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+enum EepromWrite {
+    Word(u8),
+    All,
+}
+
+/// 93LC56 serial EEPROM, organized as 128 16 bits words stored in the cartridge RAM.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Eeprom {
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+    write_enabled: bool,
+    command: u16,
+    command_bits: u8,
+    pending_write: Option<EepromWrite>,
+    write_data: u16,
+    write_bits: u8,
+    read_data: u16,
+    read_bits: u8,
+}
+
+impl Eeprom {
+    const CHIP_SELECT_BIT: u8 = 7;
+    const CLOCK_BIT: u8 = 6;
+    const DATA_IN_BIT: u8 = 1;
+    const DATA_OUT_BIT: u8 = 0;
+
+    /// Start bit, 2 bits opcode and 8 bits address.
+    const COMMAND_BITS: u8 = 11;
+    const WORD_BITS: u8 = 16;
+    const ADDRESS_MASK: u8 = 0x7F;
+
+    const OPCODE_EXTENDED: u16 = 0b00;
+    const OPCODE_WRITE: u16 = 0b01;
+    const OPCODE_READ: u16 = 0b10;
+    const OPCODE_ERASE: u16 = 0b11;
+
+    const EXTENDED_DISABLE_WRITE: u8 = 0b00;
+    const EXTENDED_WRITE_ALL: u8 = 0b01;
+    const EXTENDED_ERASE_ALL: u8 = 0b10;
+    const EXTENDED_ENABLE_WRITE: u8 = 0b11;
+
+    fn read(&self) -> u8 {
+        (self.chip_select as u8) << Self::CHIP_SELECT_BIT
+            | (self.clock as u8) << Self::CLOCK_BIT
+            | (self.data_in as u8) << Self::DATA_IN_BIT
+            | (self.data_out as u8) << Self::DATA_OUT_BIT
+    }
+
+    fn write(&mut self, ram: &mut [u8], value: u8) {
+        let chip_select = value & (1 << Self::CHIP_SELECT_BIT) != 0;
+        let clock = value & (1 << Self::CLOCK_BIT) != 0;
+        self.data_in = value & (1 << Self::DATA_IN_BIT) != 0;
+
+        if !chip_select {
+            self.reset_command();
+            self.data_out = true;
+        } else if clock && !self.clock {
+            self.clock_in(ram);
+        }
+        self.chip_select = chip_select;
+        self.clock = clock;
+    }
+
+    fn reset_command(&mut self) {
+        self.command = 0;
+        self.command_bits = 0;
+        self.pending_write = None;
+        self.write_bits = 0;
+        self.read_bits = 0;
+    }
+
+    fn clock_in(&mut self, ram: &mut [u8]) {
+        if self.read_bits > 0 {
+            self.data_out = self.read_data & 0x8000 != 0;
+            self.read_data <<= 1;
+            self.read_bits -= 1;
+        } else if let Some(write) = self.pending_write {
+            self.write_data = (self.write_data << 1) | self.data_in as u16;
+            self.write_bits += 1;
+            if self.write_bits == Self::WORD_BITS {
+                if self.write_enabled {
+                    match write {
+                        EepromWrite::Word(address) => {
+                            Self::write_word(ram, address, self.write_data)
+                        }
+                        EepromWrite::All => {
+                            for address in 0..=Self::ADDRESS_MASK {
+                                Self::write_word(ram, address, self.write_data);
+                            }
+                        }
+                    }
+                }
+                self.reset_command();
+                self.data_out = true;
+            }
+        } else if self.command_bits > 0 || self.data_in {
+            // Leading zeros are ignored until the start bit.
+            self.command = (self.command << 1) | self.data_in as u16;
+            self.command_bits += 1;
+            if self.command_bits == Self::COMMAND_BITS {
+                self.run_command(ram);
+            }
+        }
+    }
+
+    fn run_command(&mut self, ram: &mut [u8]) {
+        let command = self.command;
+        let address = command as u8;
+        self.reset_command();
+        match (command >> 8) & 0b11 {
+            Self::OPCODE_READ => {
+                self.read_data = Self::read_word(ram, address);
+                self.read_bits = Self::WORD_BITS;
+                // A dummy zero bit is sent before the data.
+                self.data_out = false;
+            }
+            Self::OPCODE_WRITE => self.pending_write = Some(EepromWrite::Word(address)),
+            Self::OPCODE_ERASE => {
+                if self.write_enabled {
+                    Self::write_word(ram, address, 0xFFFF);
+                }
+                self.data_out = true;
+            }
+            Self::OPCODE_EXTENDED => match address >> 6 {
+                Self::EXTENDED_DISABLE_WRITE => self.write_enabled = false,
+                Self::EXTENDED_WRITE_ALL => self.pending_write = Some(EepromWrite::All),
+                Self::EXTENDED_ERASE_ALL => {
+                    if self.write_enabled {
+                        ram.fill(0xFF);
+                    }
+                    self.data_out = true;
+                }
+                Self::EXTENDED_ENABLE_WRITE => self.write_enabled = true,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn read_word(ram: &[u8], address: u8) -> u16 {
+        let address = (address & Self::ADDRESS_MASK) as usize * 2;
+        u16::from_le_bytes([ram[address], ram[address + 1]])
+    }
+
+    fn write_word(ram: &mut [u8], address: u8, value: u16) {
+        let address = (address & Self::ADDRESS_MASK) as usize * 2;
+        ram[address..address + 2].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Mbc7 {
+    rom_bank: u8,
+    ram_enabled_1: bool,
+    ram_enabled_2: bool,
+    tilt: (f32, f32),
+    latched_tilt: (u16, u16),
+    latch_erased: bool,
+    eeprom: Eeprom,
+}
+
+impl Mbc7 {
+    pub(crate) const RAM_SIZE: usize = 0x100;
+
+    const RAM_ENABLE_1_START: u16 = 0x0000;
+    const RAM_ENABLE_1_END: u16 = 0x1FFF;
+    const ROM_BANK_START: u16 = 0x2000;
+    const ROM_BANK_END: u16 = 0x3FFF;
+    const RAM_ENABLE_2_START: u16 = 0x4000;
+    const RAM_ENABLE_2_END: u16 = 0x5FFF;
+
+    const REGISTERS_END: u16 = 0x0FFF;
+    const REG_LATCH_ERASE: u8 = 0x0;
+    const REG_LATCH: u8 = 0x1;
+    const REG_X_LOW: u8 = 0x2;
+    const REG_X_HIGH: u8 = 0x3;
+    const REG_Y_LOW: u8 = 0x4;
+    const REG_Y_HIGH: u8 = 0x5;
+    const REG_UNKNOWN_LOW: u8 = 0x6;
+    const REG_EEPROM: u8 = 0x8;
+
+    const LATCH_ERASE_VALUE: u8 = 0x55;
+    const LATCH_VALUE: u8 = 0xAA;
+    const ERASED_TILT: u16 = 0x8000;
+    const TILT_CENTER: f32 = 0x81D0 as f32;
+    const TILT_PER_G: f32 = 0x70 as f32;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_enabled_1: false,
+            ram_enabled_2: false,
+            tilt: (0.0, 0.0),
+            latched_tilt: (Self::ERASED_TILT, Self::ERASED_TILT),
+            latch_erased: false,
+            eeprom: Eeprom {
+                data_out: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub(crate) fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled_1 && self.ram_enabled_2
+    }
+
+    fn tilt_value(tilt: f32) -> u16 {
+        (Self::TILT_CENTER + tilt * Self::TILT_PER_G).clamp(0.0, u16::MAX as f32) as u16
+    }
+}
+
+impl MapperOps for Mbc7 {
+    fn read_rom(&mut self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            LOW_BANK_START..=LOW_BANK_END => rom[address as usize],
+            HIGH_BANK_START..=HIGH_BANK_END => {
+                let offset = self.rom_bank as usize * ROM_BANK_SIZE;
+                rom[((address - HIGH_BANK_START) as usize + offset) % rom.len()]
+            }
+            _ => panic!("Tried to read Mbc7 rom out of range"),
+        }
+    }
+
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, self.rom_bank as usize)
+    }
+
+    fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
+        match address {
+            Self::RAM_ENABLE_1_START..=Self::RAM_ENABLE_1_END => {
+                self.ram_enabled_1 = value & 0x0F == 0x0A
+            }
+            Self::ROM_BANK_START..=Self::ROM_BANK_END => self.rom_bank = value & 0b111_1111,
+            Self::RAM_ENABLE_2_START..=Self::RAM_ENABLE_2_END => self.ram_enabled_2 = value == 0x40,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&mut self, _ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled() || address > Self::REGISTERS_END {
+            return 0xFF;
+        }
+
+        let (x, y) = self.latched_tilt;
+        match (address >> 4) as u8 & 0xF {
+            Self::REG_X_LOW => x as u8,
+            Self::REG_X_HIGH => (x >> 8) as u8,
+            Self::REG_Y_LOW => y as u8,
+            Self::REG_Y_HIGH => (y >> 8) as u8,
+            Self::REG_UNKNOWN_LOW => 0x00,
+            Self::REG_EEPROM => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if !self.ram_enabled() || address > Self::REGISTERS_END {
+            return;
+        }
+
+        match (address >> 4) as u8 & 0xF {
+            Self::REG_LATCH_ERASE if value == Self::LATCH_ERASE_VALUE => {
+                self.latched_tilt = (Self::ERASED_TILT, Self::ERASED_TILT);
+                self.latch_erased = true;
+            }
+            Self::REG_LATCH if value == Self::LATCH_VALUE && self.latch_erased => {
+                let (x, y) = self.tilt;
+                self.latched_tilt = (Self::tilt_value(x), Self::tilt_value(y));
+                self.latch_erased = false;
+            }
+            Self::REG_EEPROM => self.eeprom.write(ram, value),
+            _ => {}
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn sram_size(&self, _ram_size: usize) -> usize {
+        // The eeprom is only reachable through its serial interface.
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EEPROM: u16 = 0x0080;
+    const CHIP_SELECT: u8 = 1 << Eeprom::CHIP_SELECT_BIT;
+    const CLOCK: u8 = 1 << Eeprom::CLOCK_BIT;
+
+    struct Cartridge {
+        mbc: Mbc7,
+        ram: Vec<u8>,
+    }
+
+    impl Cartridge {
+        fn new() -> Self {
+            let mut mbc = Mbc7::new();
+            mbc.write_rom(&mut [], 0x0000, 0x0A);
+            mbc.write_rom(&mut [], 0x4000, 0x40);
+            Self {
+                mbc,
+                ram: vec![0; Mbc7::RAM_SIZE],
+            }
+        }
+
+        fn data_out(&mut self) -> bool {
+            self.mbc.read_ram(&self.ram, EEPROM) & (1 << Eeprom::DATA_OUT_BIT) != 0
+        }
+
+        /// Sends a bit on the rising edge of the clock, returns the data out bit afterwards.
+        fn clock_bit(&mut self, bit: bool) -> bool {
+            let data_in = (bit as u8) << Eeprom::DATA_IN_BIT;
+            self.mbc
+                .write_ram(&mut self.ram, EEPROM, CHIP_SELECT | data_in);
+            self.mbc
+                .write_ram(&mut self.ram, EEPROM, CHIP_SELECT | CLOCK | data_in);
+            self.data_out()
+        }
+
+        fn clock_bits(&mut self, value: u16, count: u8) {
+            for bit in (0..count).rev() {
+                self.clock_bit(value & (1 << bit) != 0);
+            }
+        }
+
+        /// Sends the start bit, the opcode and the address, after a leading zero.
+        fn command(&mut self, opcode: u16, address: u8) {
+            self.mbc.write_ram(&mut self.ram, EEPROM, 0x00);
+            self.clock_bit(false);
+            self.clock_bits(0b100 | opcode, 3);
+            self.clock_bits(address as u16, 8);
+        }
+
+        fn deselect(&mut self) {
+            self.mbc.write_ram(&mut self.ram, EEPROM, 0x00);
+        }
+
+        fn write(&mut self, address: u8, value: u16) {
+            self.command(Eeprom::OPCODE_WRITE, address);
+            self.clock_bits(value, Eeprom::WORD_BITS);
+            self.deselect();
+        }
+
+        fn extended(&mut self, command: u8) {
+            self.command(Eeprom::OPCODE_EXTENDED, command << 6);
+            self.deselect();
+        }
+
+        fn read(&mut self, address: u8) -> u16 {
+            self.command(Eeprom::OPCODE_READ, address);
+            assert!(!self.data_out(), "Missing dummy zero bit before read data");
+            let value = (0..Eeprom::WORD_BITS)
+                .fold(0, |value, _| (value << 1) | self.clock_bit(false) as u16);
+            self.deselect();
+            value
+        }
+
+        fn word(&self, address: u8) -> u16 {
+            Eeprom::read_word(&self.ram, address)
+        }
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut cartridge = Cartridge::new();
+        cartridge.write(0x12, 0xBEEF);
+        assert_eq!(cartridge.word(0x12), 0x0000, "Written while write disabled");
+
+        cartridge.extended(Eeprom::EXTENDED_ENABLE_WRITE);
+        cartridge.write(0x12, 0xBEEF);
+        assert_eq!(cartridge.ram[0x24..0x26], [0xEF, 0xBE]);
+        assert_eq!(cartridge.read(0x12), 0xBEEF);
+        assert_eq!(cartridge.read(0x13), 0x0000);
+        assert!(cartridge.data_out());
+
+        cartridge.extended(Eeprom::EXTENDED_DISABLE_WRITE);
+        cartridge.write(0x12, 0x1234);
+        assert_eq!(cartridge.read(0x12), 0xBEEF);
+    }
+
+    #[test]
+    fn write_all_and_erase_all() {
+        let mut cartridge = Cartridge::new();
+        cartridge.extended(Eeprom::EXTENDED_ERASE_ALL);
+        assert!(cartridge.ram.iter().all(|&byte| byte == 0x00));
+
+        cartridge.extended(Eeprom::EXTENDED_ENABLE_WRITE);
+        cartridge.command(Eeprom::OPCODE_EXTENDED, Eeprom::EXTENDED_WRITE_ALL << 6);
+        cartridge.clock_bits(0x1234, Eeprom::WORD_BITS);
+        cartridge.deselect();
+        assert!((0..=Eeprom::ADDRESS_MASK).all(|address| cartridge.word(address) == 0x1234));
+
+        cartridge.extended(Eeprom::EXTENDED_ERASE_ALL);
+        assert!(cartridge.ram.iter().all(|&byte| byte == 0xFF));
+    }
+}
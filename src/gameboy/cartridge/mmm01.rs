@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    MapperOps, HIGH_BANK_END, HIGH_BANK_START, LOW_BANK_END, LOW_BANK_START, RAM_BANK_SIZE,
+    ROM_BANK_SIZE,
+};
+
+/// Multicart mapper, booting on a menu in the last 32 KiB of the rom which then selects and locks
+/// the game banks, behaving mostly like an Mbc1 afterward.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Mmm01 {
+    has_ram: bool,
+    has_battery: bool,
+    ram_enabled: bool,
+    locked: bool,
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    rom_bank_mask: u8,
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    ram_bank_mask: u8,
+    mbc1_mode: bool,
+    mbc1_mode_disabled: bool,
+    multiplex: bool,
+}
+
+impl Mmm01 {
+    const RAM_ENABLE_START: u16 = 0x0000;
+    const RAM_ENABLE_END: u16 = 0x1FFF;
+    const ROM_BANK_START: u16 = 0x2000;
+    const ROM_BANK_END: u16 = 0x3FFF;
+    const RAM_BANK_START: u16 = 0x4000;
+    const RAM_BANK_END: u16 = 0x5FFF;
+    const MODE_START: u16 = 0x6000;
+    const MODE_END: u16 = 0x7FFF;
+
+    const MENU_SIZE: usize = 2 * ROM_BANK_SIZE;
+    const CARTRIDGE_TYPE: usize = 0x147;
+
+    pub(crate) const fn new(has_ram: bool, has_battery: bool) -> Self {
+        Self {
+            has_ram,
+            has_battery,
+            ram_enabled: false,
+            locked: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            mbc1_mode: false,
+            mbc1_mode_disabled: false,
+            multiplex: false,
+        }
+    }
+
+    /// The menu, with the header describing the whole cartridge, when the rom is a Mmm01 dump.
+    pub(crate) fn menu(rom: &[u8]) -> Option<&[u8]> {
+        if rom.len() <= Self::MENU_SIZE || rom.len() % Self::MENU_SIZE != 0 {
+            return None;
+        }
+        let menu = &rom[rom.len() - Self::MENU_SIZE..];
+        matches!(menu[Self::CARTRIDGE_TYPE], 0x0B..=0x0D).then_some(menu)
+    }
+
+    /// Rom banks mapped in the low and high areas, the menu is mapped until the banks are locked.
+    fn rom_banks(&self, rom_bank_count: usize) -> (usize, usize) {
+        if !self.locked {
+            return (rom_bank_count - 2, rom_bank_count - 1);
+        }
+
+        let middle = if self.multiplex {
+            if self.mbc1_mode {
+                0
+            } else {
+                self.ram_bank_low
+            }
+        } else {
+            self.rom_bank_mid
+        } as usize;
+        let high = (self.rom_bank_high as usize) << 7;
+        let fixed_mask = self.rom_bank_mask << 1;
+        let low_bank = (self.rom_bank_low & fixed_mask) as usize | middle << 5 | high;
+        let middle = if self.multiplex {
+            self.ram_bank_low
+        } else {
+            self.rom_bank_mid
+        } as usize;
+        let mut high_bank = self.rom_bank_low as usize | middle << 5 | high;
+        if high_bank == low_bank {
+            high_bank += 1;
+        }
+        (low_bank % rom_bank_count, high_bank % rom_bank_count)
+    }
+
+    fn ram_bank(&self) -> u8 {
+        if self.multiplex {
+            self.rom_bank_mid | self.ram_bank_high << 2
+        } else {
+            self.ram_bank_low | self.ram_bank_high << 2
+        }
+    }
+
+    fn ram_address(&self, ram: &[u8], address: u16) -> Option<usize> {
+        if !self.has_ram || !self.ram_enabled || ram.is_empty() {
+            return None;
+        }
+        Some((address as usize + self.ram_bank() as usize * RAM_BANK_SIZE) % ram.len())
+    }
+}
+
+impl MapperOps for Mmm01 {
+    fn read_rom(&mut self, rom: &[u8], address: u16) -> u8 {
+        let (low_bank, high_bank) = self.rom_banks(rom.len() / ROM_BANK_SIZE);
+        match address {
+            LOW_BANK_START..=LOW_BANK_END => rom[address as usize + low_bank * ROM_BANK_SIZE],
+            HIGH_BANK_START..=HIGH_BANK_END => {
+                rom[(address - HIGH_BANK_START) as usize + high_bank * ROM_BANK_SIZE]
+            }
+            _ => panic!("Tried to read Mmm01 rom out of range"),
+        }
+    }
+
+    fn mapped_rom_banks(&self, rom_bank_count: usize) -> (usize, usize) {
+        self.rom_banks(rom_bank_count)
+    }
+
+    fn mapped_ram_bank(&self) -> usize {
+        self.ram_bank() as usize
+    }
+
+    fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
+        // The upper bank bits and masks can only be set by the menu, before locking.
+        match address {
+            Self::RAM_ENABLE_START..=Self::RAM_ENABLE_END => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+                if !self.locked {
+                    self.ram_bank_mask = (value >> 4) & 0b11;
+                    self.locked = value & 0x40 != 0;
+                }
+            }
+            Self::ROM_BANK_START..=Self::ROM_BANK_END => {
+                if !self.locked {
+                    self.rom_bank_mid = (value >> 5) & 0b11;
+                }
+                let fixed_mask = self.rom_bank_mask << 1;
+                self.rom_bank_low =
+                    (self.rom_bank_low & fixed_mask) | (value & !fixed_mask & 0b1_1111);
+            }
+            Self::RAM_BANK_START..=Self::RAM_BANK_END => {
+                self.ram_bank_low =
+                    (self.ram_bank_low & self.ram_bank_mask) | (value & !self.ram_bank_mask & 0b11);
+                if !self.locked {
+                    self.ram_bank_high = (value >> 2) & 0b11;
+                    self.rom_bank_high = (value >> 4) & 0b11;
+                    self.mbc1_mode_disabled = value & 0x40 != 0;
+                }
+            }
+            Self::MODE_START..=Self::MODE_END => {
+                if !self.mbc1_mode_disabled {
+                    self.mbc1_mode = value & 1 != 0;
+                }
+                if !self.locked {
+                    self.rom_bank_mask = (value >> 2) & 0b1111;
+                    self.multiplex = value & 0x40 != 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&mut self, ram: &[u8], address: u16) -> u8 {
+        self.ram_address(ram, address)
+            .map_or(0xFF, |address| ram[address])
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if let Some(address) = self.ram_address(ram, address) {
+            ram[address] = value;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM_BANK_COUNT: usize = 16;
+
+    /// A 256 KiB rom whose banks start with their own number.
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0; ROM_BANK_COUNT * ROM_BANK_SIZE];
+        for (bank, data) in rom.chunks_exact_mut(ROM_BANK_SIZE).enumerate() {
+            data[0] = bank as u8;
+        }
+        rom
+    }
+
+    fn banks(mmm01: &mut Mmm01, rom: &[u8]) -> (u8, u8) {
+        let banks = (mmm01.read_rom(rom, 0x0000), mmm01.read_rom(rom, 0x4000));
+        let (low, high) = mmm01.mapped_rom_banks(ROM_BANK_COUNT);
+        assert_eq!((low as u8, high as u8), banks);
+        banks
+    }
+
+    #[test]
+    fn menu_then_locked_game() {
+        let mut rom = rom();
+        let mut mmm01 = Mmm01::new(false, false);
+        assert_eq!(banks(&mut mmm01, &rom), (14, 15));
+
+        // The menu selects a 64 KiB game at bank 4, only the 2 lower bank bits staying writable.
+        mmm01.write_rom(&mut rom, 0x2000, 0x04);
+        mmm01.write_rom(&mut rom, 0x6000, 0b1110 << 2);
+        assert_eq!(banks(&mut mmm01, &rom), (14, 15));
+        mmm01.write_rom(&mut rom, 0x0000, 0x40);
+        assert_eq!(banks(&mut mmm01, &rom), (4, 5));
+
+        mmm01.write_rom(&mut rom, 0x2000, 0x02);
+        assert_eq!(banks(&mut mmm01, &rom), (4, 6));
+        mmm01.write_rom(&mut rom, 0x2000, 0x1F);
+        assert_eq!(banks(&mut mmm01, &rom), (4, 7));
+
+        // The mask and the lock cannot be changed by the game.
+        mmm01.write_rom(&mut rom, 0x6000, 0x00);
+        mmm01.write_rom(&mut rom, 0x0000, 0x00);
+        mmm01.write_rom(&mut rom, 0x2000, 0x09);
+        assert_eq!(banks(&mut mmm01, &rom), (4, 5));
+    }
+}
@@ -1,53 +1,142 @@
+mod huc1;
+mod huc3;
 mod mbc1;
 mod mbc2;
 mod mbc3;
 mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
+mod pocket_camera;
 mod rom_only;
+mod tama5;
+
+use std::fmt;
 
 use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
 
-use self::{mbc1::Mbc1, mbc2::Mbc2, mbc3::Mbc3, mbc5::Mbc5, rom_only::RomOnly};
+use self::{
+    huc1::Huc1, huc3::Huc3, mbc1::Mbc1, mbc2::Mbc2, mbc3::Mbc3, mbc5::Mbc5, mbc6::Mbc6, mbc7::Mbc7,
+    mmm01::Mmm01, pocket_camera::PocketCamera, rom_only::RomOnly, tama5::Tama5,
+};
 use crate::error::Error;
 
-#[derive(Serialize, Deserialize, Debug)]
-pub(crate) enum Destination {
+pub use pocket_camera::{CameraSource, StaticImage, TestPattern, CAMERA_HEIGHT, CAMERA_WIDTH};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
     Japanese,
     NonJapanese,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbFlag {
+    Dmg,
+    CgbEnhanced,
+    CgbOnly,
+}
+
+impl CgbFlag {
+    pub const fn supports_cgb(&self) -> bool {
+        matches!(self, Self::CgbEnhanced | Self::CgbOnly)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Header {
-    pub(crate) title: String,
-    pub(crate) rom_size: u32,
-    pub(crate) rom_bank_count: u32,
-    pub(crate) ram_size: u32,
-    pub(crate) ram_bank_count: u32,
-    pub(crate) destination: Destination,
+    pub title: String,
+    /// Only present on some later cartridges, in place of the end of the title.
+    pub manufacturer_code: Option<String>,
+    pub cgb_flag: CgbFlag,
+    pub sgb_flag: bool,
+    pub cartridge_type: u8,
+    pub rom_size: u32,
+    pub rom_bank_count: u32,
+    pub ram_size: u32,
+    pub ram_bank_count: u32,
+    pub destination: Destination,
+    pub old_licensee_code: u8,
+    /// Used instead of the old licensee code when it is set to 0x33.
+    pub new_licensee_code: Option<String>,
+    pub mask_rom_version: u8,
+    pub header_checksum: u8,
+    pub computed_header_checksum: u8,
+    pub global_checksum: u16,
+    pub computed_global_checksum: u16,
 }
 
 impl Header {
-    fn parse(rom_bytes: &[u8]) -> Result<(Self, Mapper), Error> {
-        if rom_bytes.len() < 0x014F {
+    const TITLE_START: usize = 0x134;
+    const MANUFACTURER_CODE_START: usize = 0x13F;
+    const CGB_FLAG: usize = 0x143;
+    const NEW_LICENSEE_CODE_START: usize = 0x144;
+    const SGB_FLAG: usize = 0x146;
+    const CARTRIDGE_TYPE: usize = 0x147;
+    const ROM_SIZE: usize = 0x148;
+    const RAM_SIZE: usize = 0x149;
+    const DESTINATION: usize = 0x14A;
+    const OLD_LICENSEE_CODE: usize = 0x14B;
+    const MASK_ROM_VERSION: usize = 0x14C;
+    const HEADER_CHECKSUM: usize = 0x14D;
+    const GLOBAL_CHECKSUM_START: usize = 0x14E;
+    const END: usize = 0x150;
+
+    const USE_NEW_LICENSEE_CODE: u8 = 0x33;
+
+    fn parse(rom: &[u8]) -> Result<(Self, Mapper), Error> {
+        // Mmm01 dumps store the menu, with the header describing the whole cartridge, at the end.
+        let rom_bytes = Mmm01::menu(rom).unwrap_or(rom);
+        if rom_bytes.len() < Self::END {
             return Err(Error::InvalidRomHeader("Header is too short"));
         }
 
+        let cgb_flag = match rom_bytes[Self::CGB_FLAG] {
+            0xC0 => CgbFlag::CgbOnly,
+            flag if flag & 0x80 != 0 => CgbFlag::CgbEnhanced,
+            _ => CgbFlag::Dmg,
+        };
+
+        // The manufacturer code can only be told apart from the title by its format.
+        let manufacturer_code = &rom_bytes[Self::MANUFACTURER_CODE_START..Self::CGB_FLAG];
+        let manufacturer_code = (cgb_flag.supports_cgb()
+            && manufacturer_code
+                .iter()
+                .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit()))
+        .then(|| String::from_utf8_lossy(manufacturer_code).to_string());
+
+        // The last title byte is used as the CGB flag on CGB compatible cartridges.
+        let title_end = if manufacturer_code.is_some() {
+            Self::MANUFACTURER_CODE_START
+        } else if cgb_flag.supports_cgb() {
+            Self::CGB_FLAG
+        } else {
+            Self::CGB_FLAG + 1
+        };
         let title = String::from_utf8_lossy(
-            rom_bytes[0x0134..=0x143]
+            rom_bytes[Self::TITLE_START..title_end]
                 .splitn(2, |byte| *byte == 0)
                 .next()
                 .unwrap(),
         )
         .to_string();
 
-        let rom_bank_count = match rom_bytes[0x148] {
+        let old_licensee_code = rom_bytes[Self::OLD_LICENSEE_CODE];
+        let new_licensee_code = (old_licensee_code == Self::USE_NEW_LICENSEE_CODE).then(|| {
+            String::from_utf8_lossy(
+                &rom_bytes[Self::NEW_LICENSEE_CODE_START..Self::NEW_LICENSEE_CODE_START + 2],
+            )
+            .to_string()
+        });
+
+        let rom_bank_count = match rom_bytes[Self::ROM_SIZE] {
             rom_bank_byte @ 0x00..=0x08 => 2 << rom_bank_byte,
             _ => return Err(Error::InvalidRomHeader("Invalid rom bank count")),
         };
 
         let rom_size = 0x4000 * rom_bank_count;
 
-        let (ram_bank_count, ram_size) = match rom_bytes[0x149] {
+        let (ram_bank_count, ram_size) = match rom_bytes[Self::RAM_SIZE] {
             0x00 => (0, 0),
             0x02 => (1, 0x2000),
             0x03 => (4, 0x2000 * 4),
@@ -56,15 +145,27 @@ impl Header {
             _ => return Err(Error::InvalidRomHeader("Invalid ram bank count")),
         };
 
-        let mapper = match rom_bytes[0x147] {
+        let cartridge_type = rom_bytes[Self::CARTRIDGE_TYPE];
+        let multicart = Mbc1::is_multicart(rom_bytes, rom_bank_count as u16);
+        let mapper = match cartridge_type {
             0x00 => Mapper::RomOnly(RomOnly),
-            0x01 => Mapper::Mbc1(Mbc1::new(rom_bank_count as u16, false, false)),
-            0x02 => Mapper::Mbc1(Mbc1::new(rom_bank_count as u16, true, false)),
-            0x03 => Mapper::Mbc1(Mbc1::new(rom_bank_count as u16, true, true)),
+            0x01 => Mapper::Mbc1(Mbc1::new(rom_bank_count as u16, false, false, multicart)),
+            0x02 => Mapper::Mbc1(Mbc1::new(rom_bank_count as u16, true, false, multicart)),
+            0x03 => Mapper::Mbc1(Mbc1::new(rom_bank_count as u16, true, true, multicart)),
 
             0x05 => Mapper::Mbc2(Mbc2::new(false)),
             0x06 => Mapper::Mbc2(Mbc2::new(true)),
 
+            // The menu is mapped from the last two banks.
+            0x0B..=0x0D if rom.len() < 2 * ROM_BANK_SIZE => {
+                return Err(Error::InvalidRomHeader(
+                    "Mmm01 rom is smaller than two banks",
+                ))
+            }
+            0x0B => Mapper::Mmm01(Mmm01::new(false, false)),
+            0x0C => Mapper::Mmm01(Mmm01::new(true, false)),
+            0x0D => Mapper::Mmm01(Mmm01::new(true, true)),
+
             0x0F => Mapper::Mbc3(Mbc3::new(true, false, true)),
             0x10 => Mapper::Mbc3(Mbc3::new(true, true, true)),
             0x11 => Mapper::Mbc3(Mbc3::new(false, false, false)),
@@ -78,39 +179,237 @@ impl Header {
             0x1D => Mapper::Mbc5(Mbc5::new(true, true, false)),
             0x1E => Mapper::Mbc5(Mbc5::new(true, true, true)),
 
+            0x20 => Mapper::Mbc6(Mbc6::new(ram_size as usize)),
+
+            0x22 => Mapper::Mbc7(Mbc7::new()),
+
+            0xFC => Mapper::PocketCamera(PocketCamera::new()),
+            0xFD => Mapper::Tama5(Tama5::new()),
+            0xFE => Mapper::Huc3(Huc3::new()),
+            0xFF => Mapper::Huc1(Huc1::new()),
+
             id => return Err(Error::UnsupportedMapper(id)),
         };
 
-        let destination = match rom_bytes[0x14A] {
+        let destination = match rom_bytes[Self::DESTINATION] {
             0x00 => Destination::Japanese,
             0x01 => Destination::NonJapanese,
             _ => return Err(Error::InvalidRomHeader("Invalid destination")),
         };
 
+        let computed_header_checksum = rom_bytes[Self::TITLE_START..Self::HEADER_CHECKSUM]
+            .iter()
+            .fold(0u8, |checksum, &byte| {
+                checksum.wrapping_sub(byte).wrapping_sub(1)
+            });
+        let global_checksum = u16::from_be_bytes([
+            rom_bytes[Self::GLOBAL_CHECKSUM_START],
+            rom_bytes[Self::GLOBAL_CHECKSUM_START + 1],
+        ]);
+        // The checksum covers the whole rom, skipping its own bytes wherever the header is.
+        let header_offset = rom.len() - rom_bytes.len();
+        let checksum_range = header_offset + Self::GLOBAL_CHECKSUM_START..header_offset + Self::END;
+        let computed_global_checksum = rom
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !checksum_range.contains(i))
+            .fold(0u16, |checksum, (_, &byte)| {
+                checksum.wrapping_add(byte as u16)
+            });
+
         Ok((
             Self {
                 title,
+                manufacturer_code,
+                cgb_flag,
+                sgb_flag: rom_bytes[Self::SGB_FLAG] == 0x03,
+                cartridge_type,
                 rom_size,
                 rom_bank_count,
                 ram_size,
                 ram_bank_count,
                 destination,
+                old_licensee_code,
+                new_licensee_code,
+                mask_rom_version: rom_bytes[Self::MASK_ROM_VERSION],
+                header_checksum: rom_bytes[Self::HEADER_CHECKSUM],
+                computed_header_checksum,
+                global_checksum,
+                computed_global_checksum,
             },
             mapper,
         ))
     }
+
+    /// SGB functions are only enabled when the old licensee code is also set to 0x33.
+    pub const fn supports_sgb(&self) -> bool {
+        self.sgb_flag && self.old_licensee_code == Self::USE_NEW_LICENSEE_CODE
+    }
+
+    /// Checked by the bootrom, which locks up on a mismatch.
+    pub const fn header_checksum_valid(&self) -> bool {
+        self.header_checksum == self.computed_header_checksum
+    }
+
+    pub fn check_header_checksum(&self) -> Result<(), Error> {
+        if self.header_checksum_valid() {
+            Ok(())
+        } else {
+            Err(Error::InvalidRomHeader("Header checksum mismatch"))
+        }
+    }
+
+    /// Not checked by the hardware, some games ship with a wrong value.
+    pub const fn global_checksum_valid(&self) -> bool {
+        self.global_checksum == self.computed_global_checksum
+    }
+
+    pub const fn cartridge_type_name(&self) -> &'static str {
+        match self.cartridge_type {
+            0x00 => "ROM ONLY",
+            0x01 => "MBC1",
+            0x02 => "MBC1+RAM",
+            0x03 => "MBC1+RAM+BATTERY",
+            0x05 => "MBC2",
+            0x06 => "MBC2+BATTERY",
+            0x08 => "ROM+RAM",
+            0x09 => "ROM+RAM+BATTERY",
+            0x0B => "MMM01",
+            0x0C => "MMM01+RAM",
+            0x0D => "MMM01+RAM+BATTERY",
+            0x0F => "MBC3+TIMER+BATTERY",
+            0x10 => "MBC3+TIMER+RAM+BATTERY",
+            0x11 => "MBC3",
+            0x12 => "MBC3+RAM",
+            0x13 => "MBC3+RAM+BATTERY",
+            0x19 => "MBC5",
+            0x1A => "MBC5+RAM",
+            0x1B => "MBC5+RAM+BATTERY",
+            0x1C => "MBC5+RUMBLE",
+            0x1D => "MBC5+RUMBLE+RAM",
+            0x1E => "MBC5+RUMBLE+RAM+BATTERY",
+            0x20 => "MBC6",
+            0x22 => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+            0xFC => "POCKET CAMERA",
+            0xFD => "BANDAI TAMA5",
+            0xFE => "HuC3",
+            0xFF => "HuC1+RAM+BATTERY",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = |valid| if valid { "valid" } else { "INVALID" };
+        writeln!(f, "Title:             {}", self.title)?;
+        if let Some(manufacturer_code) = &self.manufacturer_code {
+            writeln!(f, "Manufacturer code: {manufacturer_code}")?;
+        }
+        let cgb_flag = match self.cgb_flag {
+            CgbFlag::Dmg => "DMG only",
+            CgbFlag::CgbEnhanced => "CGB enhanced",
+            CgbFlag::CgbOnly => "CGB only",
+        };
+        writeln!(f, "CGB flag:          {cgb_flag}")?;
+        writeln!(
+            f,
+            "SGB support:       {}",
+            if self.supports_sgb() { "yes" } else { "no" }
+        )?;
+        match &self.new_licensee_code {
+            Some(code) => writeln!(f, "Licensee code:     {code} (new)")?,
+            None => writeln!(
+                f,
+                "Licensee code:     0x{:02X} (old)",
+                self.old_licensee_code
+            )?,
+        }
+        writeln!(
+            f,
+            "Cartridge type:    0x{:02X} ({})",
+            self.cartridge_type,
+            self.cartridge_type_name()
+        )?;
+        writeln!(
+            f,
+            "ROM size:          {} KiB ({} banks)",
+            self.rom_size / 1024,
+            self.rom_bank_count
+        )?;
+        writeln!(
+            f,
+            "RAM size:          {} KiB ({} banks)",
+            self.ram_size / 1024,
+            self.ram_bank_count
+        )?;
+        let destination = match self.destination {
+            Destination::Japanese => "Japanese",
+            Destination::NonJapanese => "Non-Japanese",
+        };
+        writeln!(f, "Destination:       {destination}")?;
+        writeln!(f, "Mask ROM version:  {}", self.mask_rom_version)?;
+        writeln!(
+            f,
+            "Header checksum:   0x{:02X} ({}, computed 0x{:02X})",
+            self.header_checksum,
+            status(self.header_checksum_valid()),
+            self.computed_header_checksum
+        )?;
+        write!(
+            f,
+            "Global checksum:   0x{:04X} ({}, computed 0x{:04X})",
+            self.global_checksum,
+            status(self.global_checksum_valid()),
+            self.computed_global_checksum
+        )
+    }
 }
 
 #[enum_dispatch(Mapper)]
 pub(crate) trait MapperOps {
     fn read_rom(&mut self, rom: &[u8], address: u16) -> u8;
     fn write_rom(&mut self, rom: &mut [u8], address: u16, value: u8);
+    /// Rom area accesses, for mappers also mapping part of the cartridge ram there.
+    fn read_rom_with_ram(&mut self, rom: &[u8], _ram: &[u8], address: u16) -> u8 {
+        self.read_rom(rom, address)
+    }
+    fn write_rom_with_ram(&mut self, rom: &mut [u8], _ram: &mut [u8], address: u16, value: u8) {
+        self.write_rom(rom, address, value);
+    }
     fn read_ram(&mut self, ram: &[u8], address: u16) -> u8;
     fn write_ram(&mut self, rom: &mut [u8], address: u16, value: u8);
-    fn tick(&mut self) {}
+    fn tick(&mut self, _ram: &mut [u8]) {}
     fn has_battery(&self) -> bool {
         false
     }
+    fn rumble(&self) -> bool {
+        false
+    }
+    /// The rom banks mapped at 0x0000-0x3FFF and 0x4000-0x7FFF, before wrapping on the rom size.
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, 1)
+    }
+    /// The ram bank mapped at 0xA000-0xBFFF, before wrapping on the ram size.
+    fn mapped_ram_bank(&self) -> usize {
+        0
+    }
+    /// The size of the start of the cartridge ram mapped as plain SRAM at 0xA000-0xBFFF.
+    fn sram_size(&self, ram_size: usize) -> usize {
+        ram_size
+    }
+    /// Sets the initial content of the cartridge ram when no save is loaded.
+    fn init_ram(&self, _ram: &mut [u8]) {}
+    /// The size of the RTC data saved after the cartridge ram, 0 without RTC.
+    fn rtc_data_size(&self) -> usize {
+        0
+    }
+    fn set_rtc_data<'a>(&mut self, rtc_data: &'a [u8]) -> nom::IResult<&'a [u8], ()> {
+        Ok((rtc_data, ()))
+    }
+    fn rtc_data(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 #[derive(Debug, Default)]
@@ -127,9 +426,21 @@ pub enum Mapper {
     Mbc2(Mbc2),
     Mbc3(Mbc3),
     Mbc5(Mbc5),
+    Mbc6(Mbc6),
+    Mbc7(Mbc7),
+    Mmm01(Mmm01),
+    PocketCamera(PocketCamera),
+    Tama5(Tama5),
+    Huc1(Huc1),
+    Huc3(Huc3),
 }
 
-const BOOTROM_END: u16 = 0x0100;
+const BOOTROM_END: u16 = 0x00FF;
+const CGB_BOOTROM_HIGH_START: u16 = 0x0200;
+const CGB_BOOTROM_HIGH_END: u16 = 0x08FF;
+
+pub(crate) const DMG_BOOTROM_SIZE: usize = 0x100;
+pub(crate) const CGB_BOOTROM_SIZE: usize = 0x900;
 
 const LOW_BANK_START: u16 = 0x0000;
 const LOW_BANK_END: u16 = 0x3FFF;
@@ -158,23 +469,25 @@ impl Cartridge {
         save: Option<Vec<u8>>,
     ) -> Result<Self, Error> {
         let (header, mut mapper) = Header::parse(&rom)?;
-        // Mbc2 has 512 half-bytes of internal RAM that are not reported in the header.
-        let ram_size = if let Mapper::Mbc2(_) = mapper {
-            Mbc2::RAM_SIZE
-        } else {
-            header.ram_size as usize
+        // Mbc2 has 512 half-bytes of internal RAM, Mbc6 a flash, Mbc7 an EEPROM and Tama5 32 bytes
+        // of RAM, not reported in the header.
+        let ram_size = match mapper {
+            Mapper::Mbc2(_) => Mbc2::RAM_SIZE,
+            Mapper::Mbc6(_) => header.ram_size as usize + Mbc6::FLASH_SIZE,
+            Mapper::Mbc7(_) => Mbc7::RAM_SIZE,
+            Mapper::Tama5(_) => Tama5::RAM_SIZE,
+            _ => header.ram_size as usize,
         };
         let ram = if let Some(mut save) = save {
             if !mapper.has_battery() {
                 return Err(Error::SaveNotSupported);
             }
-            if let Mapper::Mbc3(mapper) = &mut mapper {
-                if save.len() == ram_size + 48 && mapper.has_rtc() {
-                    if mapper.set_rtc_data(&save[ram_size..]).is_ok() {
-                        save.truncate(ram_size);
-                    } else {
-                        return Err(Error::InvalidRtcData);
-                    }
+            let rtc_data_size = mapper.rtc_data_size();
+            if rtc_data_size != 0 && save.len() == ram_size + rtc_data_size {
+                if mapper.set_rtc_data(&save[ram_size..]).is_ok() {
+                    save.truncate(ram_size);
+                } else {
+                    return Err(Error::InvalidRtcData);
                 }
             }
             if save.len() != ram_size {
@@ -183,11 +496,13 @@ impl Cartridge {
 
             save
         } else {
-            vec![0; ram_size]
+            let mut ram = vec![0; ram_size];
+            mapper.init_ram(&mut ram);
+            ram
         };
         let bootrom_enabled = bootrom.is_some();
         if let Some(bootrom) = &bootrom {
-            if bootrom.len() != 0x100 {
+            if bootrom.len() != DMG_BOOTROM_SIZE && bootrom.len() != CGB_BOOTROM_SIZE {
                 return Err(Error::InvalidBootRom);
             }
         }
@@ -203,28 +518,60 @@ impl Cartridge {
     }
 
     pub(crate) fn tick(&mut self) {
-        self.mapper.tick();
+        self.mapper.tick(&mut self.ram);
     }
 
     pub(crate) fn disable_bootrom(&mut self) {
         self.bootrom_enabled = false;
     }
 
-    pub(crate) fn read_rom(&mut self, address: u16) -> u8 {
+    fn bootrom_mapped(&self, address: u16) -> bool {
         match &self.bootrom {
-            Some(bootrom) if self.bootrom_enabled && address <= BOOTROM_END => {
-                bootrom[address as usize]
+            Some(bootrom) if self.bootrom_enabled => {
+                address <= BOOTROM_END
+                    || (bootrom.len() == CGB_BOOTROM_SIZE
+                        && (CGB_BOOTROM_HIGH_START..=CGB_BOOTROM_HIGH_END).contains(&address))
             }
-            _ => self.mapper.read_rom(&self.rom, address),
+            _ => false,
+        }
+    }
+
+    /// The rom bank mapped at an address of the rom area.
+    pub(crate) fn rom_bank(&self, address: u16) -> usize {
+        let rom_bank_count = (self.rom.len() / ROM_BANK_SIZE).max(1);
+        let (low_bank, high_bank) = self.mapper.mapped_rom_banks(rom_bank_count);
+        if address < HIGH_BANK_START {
+            low_bank % rom_bank_count
+        } else {
+            high_bank % rom_bank_count
+        }
+    }
+
+    /// The cartridge ram bank mapped at 0xA000-0xBFFF.
+    pub(crate) fn ram_bank(&self) -> usize {
+        let ram_bank_count = (self.ram.len() / RAM_BANK_SIZE).max(1);
+        self.mapper.mapped_ram_bank() % ram_bank_count
+    }
+
+    /// The part of the cartridge ram mapped as plain SRAM, without flash or internal memories.
+    pub(crate) fn sram(&self) -> &[u8] {
+        &self.ram[..self.mapper.sram_size(self.ram.len())]
+    }
+
+    pub(crate) fn read_rom(&mut self, address: u16) -> u8 {
+        match &self.bootrom {
+            Some(bootrom) if self.bootrom_mapped(address) => bootrom[address as usize],
+            _ => self.mapper.read_rom_with_ram(&self.rom, &self.ram, address),
         }
     }
 
     pub(crate) fn write_rom(&mut self, address: u16, value: u8) {
+        let bootrom_mapped = self.bootrom_mapped(address);
         match &mut self.bootrom {
-            Some(bootrom) if self.bootrom_enabled && address <= BOOTROM_END => {
-                bootrom[address as usize] = value
-            }
-            _ => self.mapper.write_rom(&mut self.rom, address, value),
+            Some(bootrom) if bootrom_mapped => bootrom[address as usize] = value,
+            _ => self
+                .mapper
+                .write_rom_with_ram(&mut self.rom, &mut self.ram, address, value),
         }
     }
 
@@ -242,12 +589,9 @@ impl Cartridge {
         }
 
         let ram = (!self.ram.is_empty()).then_some(&self.ram[..]);
-        let rtc = if let Mapper::Mbc3(mapper) = &self.mapper {
-            mapper.rtc_data()
-        } else {
-            None
-        };
-
-        SaveData { ram, rtc }
+        SaveData {
+            ram,
+            rtc: self.mapper.rtc_data(),
+        }
     }
 }
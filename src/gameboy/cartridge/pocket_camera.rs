@@ -0,0 +1,320 @@
+use std::{fmt, fs::File, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+use super::{
+    MapperOps, HIGH_BANK_END, HIGH_BANK_START, LOW_BANK_END, LOW_BANK_START, RAM_BANK_SIZE,
+    ROM_BANK_SIZE,
+};
+
+pub const CAMERA_WIDTH: usize = 128;
+pub const CAMERA_HEIGHT: usize = 112;
+
+/// Provides the images seen by the Pocket Camera sensor.
+pub trait CameraSource: fmt::Debug + Send {
+    /// Fills a grayscale image of [`CAMERA_WIDTH`] by [`CAMERA_HEIGHT`] pixels, from 0 for black
+    /// to 255 for white.
+    fn capture(&mut self, image: &mut [u8]);
+}
+
+/// Scrolling bars with a gradient, for testing without an actual image.
+#[derive(Debug, Default)]
+pub struct TestPattern {
+    frame: usize,
+}
+
+impl TestPattern {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CameraSource for TestPattern {
+    fn capture(&mut self, image: &mut [u8]) {
+        for (i, pixel) in image.iter_mut().enumerate() {
+            let (x, y) = (i % CAMERA_WIDTH, i / CAMERA_WIDTH);
+            let bar = ((x + self.frame) / 16) % 2 == 0;
+            let gradient = (y * 255 / (CAMERA_HEIGHT - 1)) as u8;
+            *pixel = if bar { gradient } else { 255 - gradient };
+        }
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
+
+/// A still image, scaled to the sensor size.
+#[derive(Debug)]
+pub struct StaticImage {
+    image: Vec<u8>,
+}
+
+impl StaticImage {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        let channels = info.color_type.samples();
+        let (width, height) = (info.width as usize, info.height as usize);
+
+        let luma = |pixel: &[u8]| match pixel {
+            [r, g, b, ..] => ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000) as u8,
+            [gray, ..] => *gray,
+            [] => 0,
+        };
+        let image = (0..CAMERA_WIDTH * CAMERA_HEIGHT)
+            .map(|i| {
+                let x = (i % CAMERA_WIDTH) * width / CAMERA_WIDTH;
+                let y = (i / CAMERA_WIDTH) * height / CAMERA_HEIGHT;
+                let start = (y * width + x) * channels;
+                luma(&buf[start..start + channels])
+            })
+            .collect();
+        Ok(Self { image })
+    }
+}
+
+impl CameraSource for StaticImage {
+    fn capture(&mut self, image: &mut [u8]) {
+        image.copy_from_slice(&self.image);
+    }
+}
+
+fn default_source() -> Box<dyn CameraSource> {
+    Box::new(TestPattern::new())
+}
+
+/// Game Boy Camera cartridge, with its M64282FP sensor registers mapped in place of RAM bank 0x10.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PocketCamera {
+    rom_bank: u8,
+    ram_bank: u8,
+    ram_enabled: bool,
+    registers_selected: bool,
+    #[serde(with = "BigArray")]
+    registers: [u8; Self::REGISTER_COUNT],
+    /// Remaining M-cycles before the end of the current capture.
+    capture_cycles: u32,
+    #[serde(skip, default = "default_source")]
+    source: Box<dyn CameraSource>,
+}
+
+impl PocketCamera {
+    const RAM_ENABLE_START: u16 = 0x0000;
+    const RAM_ENABLE_END: u16 = 0x1FFF;
+    const ROM_BANK_START: u16 = 0x2000;
+    const ROM_BANK_END: u16 = 0x3FFF;
+    const RAM_BANK_START: u16 = 0x4000;
+    const RAM_BANK_END: u16 = 0x5FFF;
+
+    const REGISTERS_BANK_BIT: u8 = 0x10;
+    const REGISTER_COUNT: usize = 0x36;
+    const REGISTER_MASK: u16 = 0x7F;
+    const REG_CAPTURE: usize = 0x00;
+    const REG_EDGE_GAIN: usize = 0x01;
+    const REG_EXPOSURE_HIGH: usize = 0x02;
+    const REG_EXPOSURE_LOW: usize = 0x03;
+    const REG_EDGE_RATIO_INVERT: usize = 0x04;
+    const REG_DITHER_MATRIX: usize = 0x06;
+
+    const CAPTURE_BIT: u8 = 0b1;
+    const N_BIT: u8 = 0b1000_0000;
+    const INVERT_BIT: u8 = 0b1000;
+
+    const CAPTURE_BASE_CYCLES: u32 = 32446;
+    const CAPTURE_NO_N_CYCLES: u32 = 512;
+    const CAPTURE_EXPOSURE_CYCLES: u32 = 16;
+
+    const IMAGE_ADDRESS: usize = 0x100;
+    const IMAGE_SIZE: usize = CAMERA_WIDTH * CAMERA_HEIGHT / 4;
+    /// Exposure at which the source image is used as is.
+    const NEUTRAL_EXPOSURE: f32 = 0x0800 as f32;
+    const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+    pub(crate) fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            registers_selected: false,
+            registers: [0; Self::REGISTER_COUNT],
+            capture_cycles: 0,
+            source: default_source(),
+        }
+    }
+
+    pub(crate) fn set_source(&mut self, source: Box<dyn CameraSource>) {
+        self.source = source;
+    }
+
+    fn exposure(&self) -> u16 {
+        u16::from_be_bytes([
+            self.registers[Self::REG_EXPOSURE_HIGH],
+            self.registers[Self::REG_EXPOSURE_LOW],
+        ])
+    }
+
+    fn start_capture(&mut self) {
+        let n_cycles = if self.registers[Self::REG_EDGE_GAIN] & Self::N_BIT != 0 {
+            0
+        } else {
+            Self::CAPTURE_NO_N_CYCLES
+        };
+        self.capture_cycles = Self::CAPTURE_BASE_CYCLES
+            + n_cycles
+            + Self::CAPTURE_EXPOSURE_CYCLES * self.exposure() as u32;
+    }
+
+    /// Processes the sensor image like the camera chip and writes it as tiles in RAM bank 0.
+    fn capture(&mut self, ram: &mut [u8]) {
+        let mut image = vec![0; CAMERA_WIDTH * CAMERA_HEIGHT];
+        self.source.capture(&mut image);
+
+        let exposure = self.exposure() as f32 / Self::NEUTRAL_EXPOSURE;
+        let invert = self.registers[Self::REG_EDGE_RATIO_INVERT] & Self::INVERT_BIT != 0;
+        let sensor: Vec<f32> = image
+            .iter()
+            .map(|&pixel| {
+                let value = (pixel as f32 * exposure).min(255.0);
+                if invert {
+                    255.0 - value
+                } else {
+                    value
+                }
+            })
+            .collect();
+
+        let edge_mode = (self.registers[Self::REG_EDGE_GAIN] >> 5) & 0b11;
+        let edge_ratio = Self::EDGE_RATIOS
+            [((self.registers[Self::REG_EDGE_RATIO_INVERT] >> 4) & 0b111) as usize];
+        let at = |x: usize, y: usize| sensor[y * CAMERA_WIDTH + x];
+
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let (left, right) = (
+                    at(x.saturating_sub(1), y),
+                    at((x + 1).min(CAMERA_WIDTH - 1), y),
+                );
+                let (up, down) = (
+                    at(x, y.saturating_sub(1)),
+                    at(x, (y + 1).min(CAMERA_HEIGHT - 1)),
+                );
+                let value = at(x, y);
+                let neighbors = match edge_mode {
+                    0b00 => None,
+                    0b01 => Some((left + right) / 2.0),
+                    0b10 => Some((up + down) / 2.0),
+                    _ => Some((left + right + up + down) / 4.0),
+                };
+                let value =
+                    neighbors.map_or(value, |neighbors| value + (value - neighbors) * edge_ratio);
+
+                let matrix = Self::REG_DITHER_MATRIX + ((y % 4) * 4 + x % 4) * 3;
+                let thresholds = &self.registers[matrix..matrix + 3];
+                let color = if value < thresholds[0] as f32 {
+                    3
+                } else if value < thresholds[1] as f32 {
+                    2
+                } else if value < thresholds[2] as f32 {
+                    1
+                } else {
+                    0
+                };
+
+                let tile = (y / 8) * (CAMERA_WIDTH / 8) + x / 8;
+                let address = Self::IMAGE_ADDRESS + tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                ram[address] = (ram[address] & !(1 << bit)) | ((color & 1) << bit);
+                ram[address + 1] = (ram[address + 1] & !(1 << bit)) | ((color >> 1) << bit);
+            }
+        }
+    }
+
+    fn ram_address(&self, ram: &[u8], address: u16) -> Option<usize> {
+        (!ram.is_empty())
+            .then(|| (address as usize + self.ram_bank as usize * RAM_BANK_SIZE) % ram.len())
+    }
+}
+
+impl MapperOps for PocketCamera {
+    fn read_rom(&mut self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            LOW_BANK_START..=LOW_BANK_END => rom[address as usize],
+            HIGH_BANK_START..=HIGH_BANK_END => {
+                let offset = self.rom_bank as usize * ROM_BANK_SIZE;
+                rom[((address - HIGH_BANK_START) as usize + offset) % rom.len()]
+            }
+            _ => panic!("Tried to read PocketCamera rom out of range"),
+        }
+    }
+
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, self.rom_bank as usize)
+    }
+
+    fn mapped_ram_bank(&self) -> usize {
+        self.ram_bank as usize
+    }
+
+    fn write_rom(&mut self, _rom: &mut [u8], address: u16, value: u8) {
+        match address {
+            Self::RAM_ENABLE_START..=Self::RAM_ENABLE_END => {
+                self.ram_enabled = value & 0x0F == 0x0A
+            }
+            Self::ROM_BANK_START..=Self::ROM_BANK_END => self.rom_bank = value & 0b11_1111,
+            Self::RAM_BANK_START..=Self::RAM_BANK_END => {
+                self.registers_selected = value & Self::REGISTERS_BANK_BIT != 0;
+                self.ram_bank = value & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&mut self, ram: &[u8], address: u16) -> u8 {
+        if self.registers_selected {
+            // Only the capture register can be read back, with its busy flag.
+            match (address & Self::REGISTER_MASK) as usize {
+                Self::REG_CAPTURE => {
+                    (self.registers[Self::REG_CAPTURE] & !Self::CAPTURE_BIT)
+                        | (self.capture_cycles > 0) as u8
+                }
+                _ => 0x00,
+            }
+        } else {
+            // Unlike writes, reads do not need RAM to be enabled.
+            self.ram_address(ram, address)
+                .map_or(0xFF, |address| ram[address])
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if self.registers_selected {
+            let register = (address & Self::REGISTER_MASK) as usize;
+            if register < Self::REGISTER_COUNT {
+                self.registers[register] = value;
+                if register == Self::REG_CAPTURE && value & Self::CAPTURE_BIT != 0 {
+                    self.start_capture();
+                }
+            }
+        } else if self.ram_enabled {
+            if let Some(address) = self.ram_address(ram, address) {
+                ram[address] = value;
+            }
+        }
+    }
+
+    /// Counts down the current capture, as the image is written to RAM once it ends.
+    fn tick(&mut self, ram: &mut [u8]) {
+        if self.capture_cycles > 0 {
+            self.capture_cycles -= 1;
+            if self.capture_cycles == 0 && ram.len() >= Self::IMAGE_ADDRESS + Self::IMAGE_SIZE {
+                self.capture(ram);
+            }
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+}
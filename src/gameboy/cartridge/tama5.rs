@@ -0,0 +1,325 @@
+use std::{
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use cookie_factory as cf;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    MapperOps, HIGH_BANK_END, HIGH_BANK_START, LOW_BANK_END, LOW_BANK_START, ROM_BANK_SIZE,
+};
+use crate::gameboy::Gameboy;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+struct RtcTime {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    week_day: u8,
+    /// Starts at 1, like the month.
+    day: u8,
+    month: u8,
+    year: u8,
+}
+
+impl RtcTime {
+    const REG_SECONDS: u8 = 0x0;
+    const REG_MINUTES: u8 = 0x2;
+    const REG_HOURS: u8 = 0x4;
+    const REG_WEEK_DAY: u8 = 0x6;
+    const REG_DAY: u8 = 0x7;
+    const REG_MONTH: u8 = 0x9;
+    const REG_YEAR: u8 = 0xB;
+
+    fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (input, seconds) = nom::number::complete::u8(input)?;
+        let (input, minutes) = nom::number::complete::u8(input)?;
+        let (input, hours) = nom::number::complete::u8(input)?;
+        let (input, week_day) = nom::number::complete::u8(input)?;
+        let (input, day) = nom::number::complete::u8(input)?;
+        let (input, month) = nom::number::complete::u8(input)?;
+        let (input, year) = nom::number::complete::u8(input)?;
+        Ok((
+            input,
+            Self {
+                seconds,
+                minutes,
+                hours,
+                week_day,
+                day,
+                month,
+                year,
+            },
+        ))
+    }
+
+    /// Whether the fields hold values their registers can, two BCD digits with tens up to 0xF for
+    /// the counters written by the game. Anything above would overflow while counting.
+    fn is_valid(&self) -> bool {
+        const MAX_BCD: u8 = 0xF * 10 + 9;
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day,
+            self.month,
+            self.year,
+        ]
+        .iter()
+        .all(|&value| value <= MAX_BCD)
+            && self.week_day < 7
+    }
+
+    fn serialize<W>(&self) -> impl cf::SerializeFn<W>
+    where
+        W: io::Write,
+    {
+        cf::sequence::tuple((
+            cf::bytes::le_u8(self.seconds),
+            cf::bytes::le_u8(self.minutes),
+            cf::bytes::le_u8(self.hours),
+            cf::bytes::le_u8(self.week_day),
+            cf::bytes::le_u8(self.day),
+            cf::bytes::le_u8(self.month),
+            cf::bytes::le_u8(self.year),
+        ))
+    }
+
+    fn days_in_month(&self) -> u8 {
+        match self.month {
+            2 if self.year % 4 == 0 => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    fn advance_days(&mut self, days: u64) {
+        for _ in 0..days {
+            self.week_day = (self.week_day + 1) % 7;
+            self.day += 1;
+            if self.day > self.days_in_month() {
+                self.day = 1;
+                self.month += 1;
+                if self.month > 12 {
+                    self.month = 1;
+                    self.year = (self.year + 1) % 100;
+                }
+            }
+        }
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        let seconds = self.seconds as u64 + seconds;
+        let minutes = self.minutes as u64 + seconds / 60;
+        let hours = self.hours as u64 + minutes / 60;
+        self.seconds = (seconds % 60) as u8;
+        self.minutes = (minutes % 60) as u8;
+        self.hours = (hours % 24) as u8;
+        self.advance_days(hours / 24);
+    }
+
+    /// Registers are BCD nibbles, units first.
+    fn read(&self, register: u8) -> u8 {
+        let bcd = |value: u8, tens: bool| if tens { value / 10 } else { value % 10 };
+        match register {
+            Self::REG_SECONDS..=0x1 => bcd(self.seconds, register & 1 != 0),
+            Self::REG_MINUTES..=0x3 => bcd(self.minutes, register & 1 != 0),
+            Self::REG_HOURS..=0x5 => bcd(self.hours, register & 1 != 0),
+            Self::REG_WEEK_DAY => self.week_day,
+            Self::REG_DAY..=0x8 => bcd(self.day, register == 0x8),
+            Self::REG_MONTH..=0xA => bcd(self.month, register == 0xA),
+            Self::REG_YEAR..=0xC => bcd(self.year, register == 0xC),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        let value = value & 0xF;
+        let set_bcd = |current: &mut u8, tens: bool| {
+            *current = if tens {
+                value * 10 + *current % 10
+            } else {
+                *current / 10 * 10 + value
+            }
+        };
+        match register {
+            Self::REG_SECONDS..=0x1 => set_bcd(&mut self.seconds, register & 1 != 0),
+            Self::REG_MINUTES..=0x3 => set_bcd(&mut self.minutes, register & 1 != 0),
+            Self::REG_HOURS..=0x5 => set_bcd(&mut self.hours, register & 1 != 0),
+            Self::REG_WEEK_DAY => self.week_day = value % 7,
+            Self::REG_DAY..=0x8 => set_bcd(&mut self.day, register == 0x8),
+            Self::REG_MONTH..=0xA => set_bcd(&mut self.month, register == 0xA),
+            Self::REG_YEAR..=0xC => set_bcd(&mut self.year, register == 0xC),
+            _ => {}
+        }
+    }
+}
+
+/// Bandai mapper, only accessed through a register select and a 4 bits register value address.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Tama5 {
+    selected_register: u8,
+    rom_bank: u8,
+    data_in: u8,
+    address_high: u8,
+    data_out: u8,
+    time: RtcTime,
+    cycles: usize,
+}
+
+impl Tama5 {
+    pub(crate) const RAM_SIZE: usize = 0x20;
+    const RTC_DATA_SIZE: usize = 15;
+
+    const REG_ROM_BANK_LOW: u8 = 0x0;
+    const REG_ROM_BANK_HIGH: u8 = 0x1;
+    const REG_DATA_IN_LOW: u8 = 0x4;
+    const REG_DATA_IN_HIGH: u8 = 0x5;
+    const REG_ADDRESS_HIGH: u8 = 0x6;
+    const REG_ADDRESS_LOW: u8 = 0x7;
+    const REG_READY: u8 = 0xA;
+    const REG_DATA_OUT_LOW: u8 = 0xC;
+    const REG_DATA_OUT_HIGH: u8 = 0xD;
+
+    const COMMAND_RAM_WRITE: u8 = 0x0;
+    const COMMAND_RAM_READ: u8 = 0x1;
+    const COMMAND_RTC_WRITE: u8 = 0x2;
+    const COMMAND_RTC_READ: u8 = 0x3;
+
+    const READY: u8 = 0xF1;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            selected_register: 0,
+            rom_bank: 1,
+            data_in: 0,
+            address_high: 0,
+            data_out: 0,
+            time: RtcTime {
+                day: 1,
+                month: 1,
+                ..Default::default()
+            },
+            cycles: 0,
+        }
+    }
+
+    /// Runs the command selected in the address high register once the address is complete.
+    fn run_command(&mut self, ram: &mut [u8], address_low: u8) {
+        let address = ((self.address_high & 1) << 4 | address_low) as usize;
+        match self.address_high >> 1 {
+            Self::COMMAND_RAM_WRITE => {
+                if let Some(byte) = ram.get_mut(address) {
+                    *byte = self.data_in;
+                }
+            }
+            Self::COMMAND_RAM_READ => self.data_out = ram.get(address).copied().unwrap_or(0xFF),
+            Self::COMMAND_RTC_WRITE => self.time.write(address_low, self.data_in),
+            Self::COMMAND_RTC_READ => self.data_out = self.time.read(address_low),
+            _ => {}
+        }
+    }
+}
+
+impl MapperOps for Tama5 {
+    fn read_rom(&mut self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            LOW_BANK_START..=LOW_BANK_END => rom[address as usize],
+            HIGH_BANK_START..=HIGH_BANK_END => {
+                let offset = self.rom_bank as usize * ROM_BANK_SIZE;
+                rom[((address - HIGH_BANK_START) as usize + offset) % rom.len()]
+            }
+            _ => panic!("Tried to read Tama5 rom out of range"),
+        }
+    }
+
+    fn mapped_rom_banks(&self, _rom_bank_count: usize) -> (usize, usize) {
+        (0, self.rom_bank as usize)
+    }
+
+    fn write_rom(&mut self, _rom: &mut [u8], _address: u16, _value: u8) {}
+
+    fn read_ram(&mut self, _ram: &[u8], address: u16) -> u8 {
+        if address & 1 != 0 {
+            return 0xFF;
+        }
+
+        match self.selected_register {
+            Self::REG_READY => Self::READY,
+            Self::REG_DATA_OUT_LOW => 0xF0 | self.data_out & 0x0F,
+            Self::REG_DATA_OUT_HIGH => 0xF0 | self.data_out >> 4,
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if address & 1 != 0 {
+            self.selected_register = value & 0x0F;
+            return;
+        }
+
+        let value = value & 0x0F;
+        match self.selected_register {
+            Self::REG_ROM_BANK_LOW => self.rom_bank = (self.rom_bank & 0xF0) | value,
+            Self::REG_ROM_BANK_HIGH => self.rom_bank = (self.rom_bank & 0x0F) | (value & 1) << 4,
+            Self::REG_DATA_IN_LOW => self.data_in = (self.data_in & 0xF0) | value,
+            Self::REG_DATA_IN_HIGH => self.data_in = (self.data_in & 0x0F) | value << 4,
+            Self::REG_ADDRESS_HIGH => self.address_high = value,
+            Self::REG_ADDRESS_LOW => self.run_command(ram, value),
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, _ram: &mut [u8]) {
+        self.cycles += 4;
+        if self.cycles == Gameboy::CYCLES_PER_SECOND as usize {
+            self.cycles = 0;
+            self.time.advance(1);
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn sram_size(&self, _ram_size: usize) -> usize {
+        // The ram is only reachable through the mapper registers.
+        0
+    }
+
+    fn rtc_data_size(&self) -> usize {
+        Self::RTC_DATA_SIZE
+    }
+
+    fn set_rtc_data<'a>(&mut self, rtc_data: &'a [u8]) -> nom::IResult<&'a [u8], ()> {
+        let (input, time) = nom::combinator::verify(RtcTime::parse, RtcTime::is_valid)(rtc_data)?;
+        let (input, timestamp) = nom::number::complete::le_u64(input)?;
+        let timestamp_now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        self.time = time;
+        if timestamp_now > timestamp {
+            self.time.advance(timestamp_now - timestamp);
+        }
+
+        Ok((input, ()))
+    }
+
+    fn rtc_data(&self) -> Option<Vec<u8>> {
+        let mut buf: Vec<u8> = vec![];
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        cf::gen(
+            cf::sequence::tuple((self.time.serialize(), cf::bytes::le_u64(timestamp))),
+            &mut buf,
+        )
+        .unwrap();
+        Some(buf)
+    }
+}
@@ -0,0 +1,136 @@
+use super::Cpu;
+use crate::gameboy::symbols::Symbols;
+
+/// A decoded instruction, with its operands resolved to labels when possible.
+pub(crate) struct Instruction {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) text: String,
+}
+
+impl Cpu {
+    /// Mnemonics in the same layout as the opcode table. Operands are written as `u8` and `u16`
+    /// for immediates, `i8` for signed offsets, `a8` for high page addresses and `rel` for
+    /// relative jump targets.
+    #[rustfmt::skip]
+    const MNEMONIC_TABLE: [&'static str; 256] = [
+        "nop",        "ld bc,u16", "ld (bc),a",  "inc bc",    "inc b",       "dec b",     "ld b,u8",    "rlca",      "ld (u16),sp", "add hl,bc", "ld a,(bc)",  "dec bc",    "inc c",      "dec c",    "ld c,u8",    "rrca",
+        "stop u8",    "ld de,u16", "ld (de),a",  "inc de",    "inc d",       "dec d",     "ld d,u8",    "rla",       "jr rel",      "add hl,de", "ld a,(de)",  "dec de",    "inc e",      "dec e",    "ld e,u8",    "rra",
+        "jr nz,rel",  "ld hl,u16", "ld (hl+),a", "inc hl",    "inc h",       "dec h",     "ld h,u8",    "daa",       "jr z,rel",    "add hl,hl", "ld a,(hl+)", "dec hl",    "inc l",      "dec l",    "ld l,u8",    "cpl",
+        "jr nc,rel",  "ld sp,u16", "ld (hl-),a", "inc sp",    "inc (hl)",    "dec (hl)",  "ld (hl),u8", "scf",       "jr c,rel",    "add hl,sp", "ld a,(hl-)", "dec sp",    "inc a",      "dec a",    "ld a,u8",    "ccf",
+        "ld b,b",     "ld b,c",    "ld b,d",     "ld b,e",    "ld b,h",      "ld b,l",    "ld b,(hl)",  "ld b,a",    "ld c,b",      "ld c,c",    "ld c,d",     "ld c,e",    "ld c,h",     "ld c,l",   "ld c,(hl)",  "ld c,a",
+        "ld d,b",     "ld d,c",    "ld d,d",     "ld d,e",    "ld d,h",      "ld d,l",    "ld d,(hl)",  "ld d,a",    "ld e,b",      "ld e,c",    "ld e,d",     "ld e,e",    "ld e,h",     "ld e,l",   "ld e,(hl)",  "ld e,a",
+        "ld h,b",     "ld h,c",    "ld h,d",     "ld h,e",    "ld h,h",      "ld h,l",    "ld h,(hl)",  "ld h,a",    "ld l,b",      "ld l,c",    "ld l,d",     "ld l,e",    "ld l,h",     "ld l,l",   "ld l,(hl)",  "ld l,a",
+        "ld (hl),b",  "ld (hl),c", "ld (hl),d",  "ld (hl),e", "ld (hl),h",   "ld (hl),l", "halt",       "ld (hl),a", "ld a,b",      "ld a,c",    "ld a,d",     "ld a,e",    "ld a,h",     "ld a,l",   "ld a,(hl)",  "ld a,a",
+        "add a,b",    "add a,c",   "add a,d",    "add a,e",   "add a,h",     "add a,l",   "add a,(hl)", "add a,a",   "adc a,b",     "adc a,c",   "adc a,d",    "adc a,e",   "adc a,h",    "adc a,l",  "adc a,(hl)", "adc a,a",
+        "sub a,b",    "sub a,c",   "sub a,d",    "sub a,e",   "sub a,h",     "sub a,l",   "sub a,(hl)", "sub a,a",   "sbc a,b",     "sbc a,c",   "sbc a,d",    "sbc a,e",   "sbc a,h",    "sbc a,l",  "sbc a,(hl)", "sbc a,a",
+        "and a,b",    "and a,c",   "and a,d",    "and a,e",   "and a,h",     "and a,l",   "and a,(hl)", "and a,a",   "xor a,b",     "xor a,c",   "xor a,d",    "xor a,e",   "xor a,h",    "xor a,l",  "xor a,(hl)", "xor a,a",
+        "or a,b",     "or a,c",    "or a,d",     "or a,e",    "or a,h",      "or a,l",    "or a,(hl)",  "or a,a",    "cp a,b",      "cp a,c",    "cp a,d",     "cp a,e",    "cp a,h",     "cp a,l",   "cp a,(hl)",  "cp a,a",
+        "ret nz",     "pop bc",    "jp nz,u16",  "jp u16",    "call nz,u16", "push bc",   "add a,u8",   "rst 0x00",  "ret z",       "ret",       "jp z,u16",   "prefix cb", "call z,u16", "call u16", "adc a,u8",   "rst 0x08",
+        "ret nc",     "pop de",    "jp nc,u16",  "ill",       "call nc,u16", "push de",   "sub a,u8",   "rst 0x10",  "ret c",       "reti",      "jp c,u16",   "ill",       "call c,u16", "ill",      "sbc a,u8",   "rst 0x18",
+        "ldh (a8),a", "pop hl",    "ldh (c),a",  "ill",       "ill",         "push hl",   "and a,u8",   "rst 0x20",  "add sp,i8",   "jp hl",     "ld (u16),a", "ill",       "ill",        "ill",      "xor a,u8",   "rst 0x28",
+        "ldh a,(a8)", "pop af",    "ldh a,(c)",  "di",        "ill",         "push af",   "or a,u8",    "rst 0x30",  "ld hl,sp+i8", "ld sp,hl",  "ld a,(u16)", "ei",        "ill",        "ill",      "cp a,u8",    "rst 0x38",
+    ];
+
+    const CB_MNEMONICS: [&'static str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+    const REGISTER_NAMES: [&'static str; 8] = ["b", "c", "d", "e", "h", "l", "(hl)", "a"];
+
+    /// Disassembles the instruction at an address, reading memory without ticking the cpu.
+    pub(crate) fn disassemble(&mut self, address: u16, symbols: &Symbols) -> Instruction {
+        let opcode = self.mmu.read_byte_no_conflict(address);
+        let mnemonic = Self::MNEMONIC_TABLE[opcode as usize];
+        let mut read_operand =
+            |offset: u16| self.mmu.read_byte_no_conflict(address.wrapping_add(offset));
+
+        if opcode == 0xCB {
+            let cb_opcode = read_operand(1);
+            let register = Self::REGISTER_NAMES[(cb_opcode & 0b111) as usize];
+            let bit = (cb_opcode >> 3) & 0b111;
+            let text = match cb_opcode {
+                0x00..=0x3F => format!("{} {register}", Self::CB_MNEMONICS[bit as usize]),
+                0x40..=0x7F => format!("bit {bit},{register}"),
+                0x80..=0xBF => format!("res {bit},{register}"),
+                0xC0..=0xFF => format!("set {bit},{register}"),
+            };
+            return Instruction {
+                bytes: vec![opcode, cb_opcode],
+                text,
+            };
+        }
+
+        let (bytes, text) = if mnemonic.contains("u16") {
+            let (low, high) = (read_operand(1), read_operand(2));
+            let target = u16::from_le_bytes([low, high]);
+            let operand = self.label_or_address(target, symbols);
+            (vec![opcode, low, high], mnemonic.replace("u16", &operand))
+        } else if mnemonic.contains("u8") {
+            let value = read_operand(1);
+            (
+                vec![opcode, value],
+                mnemonic.replace("u8", &format!("0x{value:02X}")),
+            )
+        } else if mnemonic.contains("a8") {
+            let value = read_operand(1);
+            let operand = self.label_or_address(0xFF00 | value as u16, symbols);
+            (vec![opcode, value], mnemonic.replace("a8", &operand))
+        } else if mnemonic.contains("+i8") {
+            let value = read_operand(1);
+            let operand = format!("{:+}", value as i8);
+            (vec![opcode, value], mnemonic.replace("+i8", &operand))
+        } else if mnemonic.contains("i8") {
+            let value = read_operand(1);
+            let operand = format!("{}", value as i8);
+            (vec![opcode, value], mnemonic.replace("i8", &operand))
+        } else if mnemonic.contains("rel") {
+            let value = read_operand(1);
+            let target = address
+                .wrapping_add(2)
+                .wrapping_add_signed(value as i8 as i16);
+            let operand = self.label_or_address(target, symbols);
+            (vec![opcode, value], mnemonic.replace("rel", &operand))
+        } else {
+            (vec![opcode], mnemonic.to_owned())
+        };
+        Instruction { bytes, text }
+    }
+
+    fn label_or_address(&self, address: u16, symbols: &Symbols) -> String {
+        symbols
+            .label(self.mmu.bank(address), address)
+            .map_or_else(|| format!("0x{address:04X}"), str::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY_POINT: u16 = 0x100;
+
+    /// Checks that the disassembled length of every instruction matches the bytes the cpu reads
+    /// when running it, except for jumps whose next pc is not the next instruction.
+    #[test]
+    fn mnemonics_match_opcodes() {
+        let symbols = Symbols::new();
+        for opcode in 0..=u8::MAX {
+            let mnemonic = Cpu::MNEMONIC_TABLE[opcode as usize];
+            if ["jp", "jr", "call", "ret", "rst"]
+                .iter()
+                .any(|jump| mnemonic.starts_with(jump))
+            {
+                continue;
+            }
+
+            let mut rom = vec![0; 0x8000];
+            rom[ENTRY_POINT as usize] = opcode;
+            let mut cpu = Cpu::new(rom, None, None, false).unwrap();
+            assert_eq!(cpu.registers.pc, ENTRY_POINT);
+            let length = cpu.disassemble(ENTRY_POINT, &symbols).bytes.len();
+            cpu.next_instruction();
+            assert_eq!(
+                cpu.registers.pc - ENTRY_POINT,
+                length as u16,
+                "Wrong length for {opcode:#04X} ({mnemonic})"
+            );
+        }
+    }
+}
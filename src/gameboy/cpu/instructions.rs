@@ -34,7 +34,11 @@ impl Cpu {
     fn nop(&mut self, _opcode: u8) {}
 
     fn stop(&mut self, _opcode: u8) {
-        self.execution_state = ExecutionState::Stop;
+        // Stop is two bytes long, the second one being ignored, including when switching speed.
+        self.fetch_byte_pc();
+        if !self.mmu.switch_speed() {
+            self.execution_state = ExecutionState::Stop;
+        }
     }
 
     fn halt(&mut self, _opcode: u8) {
@@ -684,3 +688,49 @@ impl Cpu {
         self.set_r(index, value);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY_POINT: u16 = 0x100;
+    const CGB_FLAG: usize = 0x143;
+    const CGB_SPEED_SWITCH: u16 = 0xFF4D;
+
+    fn stop_cpu(cgb: bool) -> Cpu {
+        let mut rom = vec![0; 0x8000];
+        rom[ENTRY_POINT as usize..ENTRY_POINT as usize + 2].copy_from_slice(&[0x10, 0xFF]);
+        if cgb {
+            rom[CGB_FLAG] = 0x80;
+        }
+        Cpu::new(rom, None, None, false).unwrap()
+    }
+
+    #[test]
+    fn stop_skips_its_operand() {
+        let mut cpu = stop_cpu(false);
+        cpu.next_instruction();
+        assert_eq!(cpu.registers.pc, ENTRY_POINT + 2);
+        assert_eq!(cpu.cycles, 8);
+        assert!(matches!(cpu.execution_state, ExecutionState::Stop));
+    }
+
+    #[test]
+    fn stop_switches_speed() {
+        let mut cpu = stop_cpu(true);
+        cpu.mmu.write_byte(CGB_SPEED_SWITCH, 0x01);
+        cpu.next_instruction();
+        assert_eq!(cpu.registers.pc, ENTRY_POINT + 2);
+        assert_eq!(cpu.cycles, 8);
+        assert!(matches!(cpu.execution_state, ExecutionState::Continue));
+        assert!(cpu.mmu.double_speed);
+
+        // The operand is still skipped once running at double speed.
+        cpu.registers.pc = ENTRY_POINT;
+        cpu.mmu.write_byte(CGB_SPEED_SWITCH, 0x01);
+        cpu.next_instruction();
+        assert_eq!(cpu.registers.pc, ENTRY_POINT + 2);
+        assert_eq!(cpu.cycles, 12);
+        assert!(!cpu.mmu.double_speed);
+    }
+}
@@ -1,3 +1,4 @@
+mod disassembler;
 mod instructions;
 mod registers;
 
@@ -5,8 +6,12 @@ use std::primitive::u16;
 
 use serde::{Deserialize, Serialize};
 
-use self::registers::{RegisterIndex, Registers};
-use super::mmu::{MemoryOps, Mmu};
+use self::registers::RegisterIndex;
+pub(crate) use self::registers::Registers;
+use super::{
+    interrupts::Interrupt,
+    mmu::{MemoryOps, Mmu},
+};
 use crate::error::Error;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -24,6 +29,9 @@ pub(crate) struct Cpu {
     pub(crate) mmu: Mmu,
     pub(crate) cycles: u64,
     pub(crate) execution_state: ExecutionState,
+    /// The interrupt serviced by the last call to `next_instruction`, for the debugger.
+    #[serde(skip)]
+    pub(crate) serviced_interrupt: Option<Interrupt>,
 }
 
 impl MemoryOps for Cpu {
@@ -39,28 +47,48 @@ impl MemoryOps for Cpu {
 }
 
 impl Cpu {
-    pub(crate) const MAX_TICKS_PER_INSTR: usize = 6 * 4;
-
     pub(crate) fn new(
         rom: Vec<u8>,
         bootrom: Option<Vec<u8>>,
         save: Option<Vec<u8>>,
+        sgb: bool,
     ) -> Result<Self, Error> {
-        let registers = if bootrom.is_some() {
+        let mmu = Mmu::new(rom, bootrom, save, sgb)?;
+        let registers = if mmu.cartridge.bootrom_enabled {
             Registers::new()
         } else {
-            Registers::new_post_bootrom()
+            Registers::new_post_bootrom(mmu.cgb_mode, mmu.sgb.is_some())
         };
         Ok(Self {
             registers,
             enable_ime: false,
-            mmu: Mmu::new(rom, bootrom, save)?,
+            mmu,
             cycles: 0,
             execution_state: ExecutionState::Continue,
+            serviced_interrupt: None,
         })
     }
 
+    /// Whether the next call to `next_instruction` fetches the instruction at pc, rather than
+    /// waiting or servicing an interrupt.
+    pub(crate) fn fetches_instruction(&self) -> bool {
+        let interrupt_pending = !self.mmu.interrupts().is_empty();
+        let running = match self.execution_state {
+            ExecutionState::Continue => true,
+            ExecutionState::Halt | ExecutionState::Stop => interrupt_pending,
+            ExecutionState::IllegalInstruction => false,
+        };
+        running && !self.mmu.hdma_copying() && !(self.registers.ime && interrupt_pending)
+    }
+
     pub(crate) fn next_instruction(&mut self) -> u64 {
+        self.serviced_interrupt = None;
+        // The cpu is stalled while a VRAM DMA block is being copied.
+        if self.mmu.hdma_copying() {
+            self.tick();
+            return self.cycles;
+        }
+
         // TODO Ensure proper behaviour for those.
         match self.execution_state {
             ExecutionState::Continue => {}
@@ -92,6 +120,7 @@ impl Cpu {
                 self.registers.pc = interrupt.address();
                 self.mmu.reset_interrupt(interrupt);
                 self.registers.ime = false;
+                self.serviced_interrupt = Some(interrupt);
 
                 return self.cycles;
             }
@@ -110,12 +139,12 @@ impl Cpu {
     }
 
     fn tick(&mut self) {
-        self.cycles += 4;
+        self.cycles += self.mmu.cycles_per_tick();
         self.mmu.tick();
     }
 
     fn tick_stopped(&mut self) {
-        self.cycles += 4;
+        self.cycles += self.mmu.cycles_per_tick();
         self.mmu.tick_stopped();
     }
 
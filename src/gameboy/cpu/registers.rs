@@ -1,6 +1,8 @@
 use flagset::{flags, FlagSet};
 use serde::{Deserialize, Serialize};
 
+use crate::gameboy::CgbMode;
+
 #[derive(Debug, Copy, Clone)]
 pub(crate) struct RegisterIndex(u8);
 
@@ -162,7 +164,39 @@ impl Registers {
         Default::default()
     }
 
-    pub(crate) fn new_post_bootrom() -> Self {
+    pub(crate) fn new_post_bootrom(cgb_mode: CgbMode, sgb: bool) -> Self {
+        if cgb_mode == CgbMode::Enabled {
+            return Self {
+                b: 0x00,
+                c: 0x00,
+                d: 0xFF,
+                e: 0x56,
+                h: 0x00,
+                l: 0x0D,
+                flags: Flags(Flag::Z.into()),
+                a: 0x11,
+                sp: 0xFFFE,
+                pc: 0x100,
+                ime: true,
+            };
+        }
+
+        if sgb {
+            return Self {
+                b: 0x00,
+                c: 0x14,
+                d: 0x00,
+                e: 0x00,
+                h: 0xC0,
+                l: 0x60,
+                flags: Flags(FlagSet::default()),
+                a: 0x01,
+                sp: 0xFFFE,
+                pc: 0x100,
+                ime: true,
+            };
+        }
+
         let flags = Flags(Flag::C | Flag::H | Flag::Z);
         Self {
             b: 0x00,
@@ -0,0 +1,319 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{alpha1, digit1, hex_digit1, multispace0},
+    combinator::{all_consuming, map, map_res, not, opt, value},
+    multi::fold_many0,
+    sequence::{delimited, pair, preceded, terminated},
+    IResult,
+};
+use serde::{Deserialize, Serialize};
+
+use super::cpu::{Cpu, Registers};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    Af,
+    Bc,
+    De,
+    Hl,
+    Sp,
+    Pc,
+}
+
+impl Register {
+    pub(crate) fn get(self, registers: &Registers) -> u16 {
+        let [a, f] = registers.af().to_be_bytes();
+        match self {
+            Register::A => a as u16,
+            Register::F => f as u16,
+            Register::B => registers.b as u16,
+            Register::C => registers.c as u16,
+            Register::D => registers.d as u16,
+            Register::E => registers.e as u16,
+            Register::H => registers.h as u16,
+            Register::L => registers.l as u16,
+            Register::Af => registers.af(),
+            Register::Bc => u16::from_be_bytes([registers.b, registers.c]),
+            Register::De => u16::from_be_bytes([registers.d, registers.e]),
+            Register::Hl => registers.hl(),
+            Register::Sp => registers.sp,
+            Register::Pc => registers.pc,
+        }
+    }
+
+    pub(crate) fn set(self, registers: &mut Registers, value: u16) -> Result<(), &'static str> {
+        let [high, low] = value.to_be_bytes();
+        let byte = || u8::try_from(value).map_err(|_| "Value too large for an 8 bits register");
+        match self {
+            Register::A => registers.a = byte()?,
+            // The lower bits of the flags register always stay cleared.
+            Register::F => registers.set_af(u16::from_be_bytes([registers.a, byte()?])),
+            Register::B => registers.b = byte()?,
+            Register::C => registers.c = byte()?,
+            Register::D => registers.d = byte()?,
+            Register::E => registers.e = byte()?,
+            Register::H => registers.h = byte()?,
+            Register::L => registers.l = byte()?,
+            Register::Af => registers.set_af(value),
+            Register::Bc => (registers.b, registers.c) = (high, low),
+            Register::De => (registers.d, registers.e) = (high, low),
+            Register::Hl => registers.set_hl(value),
+            Register::Sp => registers.sp = value,
+            Register::Pc => registers.pc = value,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    BitAnd,
+    BitOr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    fn apply(self, lhs: u16, rhs: u16) -> u16 {
+        match self {
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+            BinaryOp::BitAnd => lhs & rhs,
+            BinaryOp::BitOr => lhs | rhs,
+            BinaryOp::Eq => (lhs == rhs) as u16,
+            BinaryOp::Ne => (lhs != rhs) as u16,
+            BinaryOp::Lt => (lhs < rhs) as u16,
+            BinaryOp::Le => (lhs <= rhs) as u16,
+            BinaryOp::Gt => (lhs > rhs) as u16,
+            BinaryOp::Ge => (lhs >= rhs) as u16,
+            BinaryOp::And => (lhs != 0 && rhs != 0) as u16,
+            BinaryOp::Or => (lhs != 0 || rhs != 0) as u16,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Number(u16),
+    Register(Register),
+    /// A byte read from memory, written `[address]`.
+    Memory(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, cpu: &mut Cpu) -> u16 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Register(register) => register.get(&cpu.registers),
+            Node::Memory(address) => {
+                let address = address.evaluate(cpu);
+                cpu.mmu.read_byte_no_conflict(address) as u16
+            }
+            Node::Not(node) => (node.evaluate(cpu) == 0) as u16,
+            Node::Binary(op, lhs, rhs) => op.apply(lhs.evaluate(cpu), rhs.evaluate(cpu)),
+        }
+    }
+}
+
+/// An expression over registers and memory, such as `a == 3 && [0xC000] > 10`.
+/// Values are 16 bits, comparisons and logical operators give 1 when true and 0 otherwise.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Expression {
+    text: String,
+    root: Node,
+}
+
+impl Expression {
+    pub(crate) fn parse(text: &str) -> Result<Self, &'static str> {
+        let (_, root) = all_consuming(logical_or)(text).map_err(|_| "Invalid expression")?;
+        Ok(Self {
+            text: text.trim().to_owned(),
+            root,
+        })
+    }
+
+    pub(crate) fn evaluate(&self, cpu: &mut Cpu) -> u16 {
+        self.root.evaluate(cpu)
+    }
+
+    pub(crate) fn is_true(&self, cpu: &mut Cpu) -> bool {
+        self.evaluate(cpu) != 0
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn spaced<'a, O>(
+    parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    delimited(multispace0, parser, multispace0)
+}
+
+fn binary(lhs: Node, (op, rhs): (BinaryOp, Node)) -> Node {
+    Node::Binary(op, Box::new(lhs), Box::new(rhs))
+}
+
+fn number(input: &str) -> IResult<&str, Node> {
+    map(
+        alt((
+            map_res(preceded(tag("0x"), hex_digit1), |value| {
+                u16::from_str_radix(value, 16)
+            }),
+            map_res(digit1, str::parse),
+        )),
+        Node::Number,
+    )(input)
+}
+
+fn register(input: &str) -> IResult<&str, Node> {
+    map(
+        map_res(alpha1, |name| Register::from_str(name, true)),
+        Node::Register,
+    )(input)
+}
+
+fn primary(input: &str) -> IResult<&str, Node> {
+    spaced(alt((
+        number,
+        register,
+        map(delimited(tag("["), logical_or, tag("]")), |address| {
+            Node::Memory(Box::new(address))
+        }),
+        delimited(tag("("), logical_or, tag(")")),
+        map(preceded(tag("!"), primary), |node| {
+            Node::Not(Box::new(node))
+        }),
+    )))(input)
+}
+
+fn arithmetic(input: &str) -> IResult<&str, Node> {
+    let (input, first) = primary(input)?;
+    let operator = alt((
+        value(BinaryOp::Add, tag("+")),
+        value(BinaryOp::Sub, tag("-")),
+        value(BinaryOp::BitAnd, terminated(tag("&"), not(tag("&")))),
+        value(BinaryOp::BitOr, terminated(tag("|"), not(tag("|")))),
+    ));
+    fold_many0(pair(operator, primary), move || first.clone(), binary)(input)
+}
+
+fn comparison(input: &str) -> IResult<&str, Node> {
+    let (input, lhs) = arithmetic(input)?;
+    let operator = alt((
+        value(BinaryOp::Eq, tag("==")),
+        value(BinaryOp::Ne, tag("!=")),
+        value(BinaryOp::Le, tag("<=")),
+        value(BinaryOp::Ge, tag(">=")),
+        value(BinaryOp::Lt, tag("<")),
+        value(BinaryOp::Gt, tag(">")),
+    ));
+    let (input, rhs) = opt(pair(operator, arithmetic))(input)?;
+    let node = match rhs {
+        Some(rhs) => binary(lhs, rhs),
+        None => lhs,
+    };
+    Ok((input, node))
+}
+
+fn logical_and(input: &str) -> IResult<&str, Node> {
+    let (input, first) = comparison(input)?;
+    let operator = value(BinaryOp::And, tag("&&"));
+    fold_many0(pair(operator, comparison), move || first.clone(), binary)(input)
+}
+
+fn logical_or(input: &str) -> IResult<&str, Node> {
+    let (input, first) = logical_and(input)?;
+    let operator = value(BinaryOp::Or, tag("||"));
+    fold_many0(pair(operator, logical_and), move || first.clone(), binary)(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::mmu::MemoryOps;
+
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new(vec![0; 0x8000], None, None, false).unwrap();
+        Register::A.set(&mut cpu.registers, 0x12).unwrap();
+        Register::Hl.set(&mut cpu.registers, 0xC000).unwrap();
+        cpu.mmu.write_byte(0xC000, 0x42);
+        cpu.mmu.write_byte(0xC001, 0x07);
+        cpu
+    }
+
+    fn evaluate(text: &str) -> u16 {
+        Expression::parse(text)
+            .unwrap_or_else(|err| panic!("{err}: {text}"))
+            .evaluate(&mut cpu())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("1 + 2 & 1"), 1);
+        assert_eq!(evaluate("2 + 3 == 5"), 1);
+        assert_eq!(evaluate("1 || 0 && 0"), 1);
+        assert_eq!(evaluate("(1 || 0) && 0"), 0);
+        assert_eq!(evaluate("a == 0x12 && l == 0 || 0"), 1);
+        assert_eq!(evaluate("10 - 2 - 3"), 5);
+    }
+
+    #[test]
+    fn bitwise_and_logical_operators() {
+        assert_eq!(evaluate("6 & 3"), 2);
+        assert_eq!(evaluate("6 && 3"), 1);
+        assert_eq!(evaluate("6 | 1"), 7);
+        assert_eq!(evaluate("6 || 0"), 1);
+        assert_eq!(evaluate("0x0F & 0xF0 == 0"), 1);
+        assert_eq!(evaluate("a & 0x10 && a & 0x02"), 1);
+    }
+
+    #[test]
+    fn not_and_not_equal() {
+        assert_eq!(evaluate("!0"), 1);
+        assert_eq!(evaluate("!a"), 0);
+        assert_eq!(evaluate("3 != 4"), 1);
+        assert_eq!(evaluate("!3 != 0"), 0);
+        assert_eq!(evaluate("!(a != 0x12)"), 1);
+    }
+
+    #[test]
+    fn memory() {
+        assert_eq!(evaluate("[0xC000]"), 0x42);
+        assert_eq!(evaluate("[hl + 1] == 7"), 1);
+        assert_eq!(evaluate("[0xC000 + [0xC001] - 7]"), 0x42);
+        assert_eq!(evaluate("[ hl ] & 0x0F"), 0x02);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for text in ["", "1 +", "[0xC000", "(1", "a &&& b", "1 == 2 == 3", "zz"] {
+            assert!(Expression::parse(text).is_err(), "{text} should not parse");
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    General,
+    HBlankWaiting,
+    HBlankBlock,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Hdma {
+    source: u16,
+    destination: u16,
+    remaining_blocks: u8,
+    block_offset: u8,
+    state: State,
+}
+
+impl Hdma {
+    const SOURCE_HIGH_ADDRESS: u16 = 0xFF51;
+    const SOURCE_LOW_ADDRESS: u16 = 0xFF52;
+    const DESTINATION_HIGH_ADDRESS: u16 = 0xFF53;
+    const DESTINATION_LOW_ADDRESS: u16 = 0xFF54;
+    const CONTROL_ADDRESS: u16 = 0xFF55;
+
+    const BLOCK_SIZE: u8 = 0x10;
+    const DESTINATION_MASK: u16 = 0x1FFF;
+
+    pub(crate) const fn new() -> Self {
+        Self {
+            source: 0,
+            destination: 0,
+            remaining_blocks: 0x7F,
+            block_offset: 0,
+            state: State::Idle,
+        }
+    }
+
+    /// Whether a block is being copied, in which case the cpu is stalled.
+    pub(crate) fn copying(&self) -> bool {
+        matches!(self.state, State::General | State::HBlankBlock)
+    }
+
+    pub(crate) fn start_hblank_block(&mut self) {
+        if self.state == State::HBlankWaiting {
+            self.state = State::HBlankBlock;
+        }
+    }
+
+    /// Returns the source address and the destination VRAM offset of the next byte to copy.
+    pub(crate) fn next_transfer(&mut self) -> Option<(u16, u16)> {
+        if !self.copying() {
+            return None;
+        }
+
+        let transfer = (self.source, self.destination);
+        self.source = self.source.wrapping_add(1);
+        self.destination = (self.destination + 1) & Self::DESTINATION_MASK;
+        self.block_offset += 1;
+
+        if self.block_offset == Self::BLOCK_SIZE {
+            self.block_offset = 0;
+            if self.remaining_blocks == 0 {
+                self.remaining_blocks = 0x7F;
+                self.state = State::Idle;
+            } else {
+                self.remaining_blocks -= 1;
+                if self.state == State::HBlankBlock {
+                    self.state = State::HBlankWaiting;
+                }
+            }
+        }
+
+        Some(transfer)
+    }
+
+    pub(crate) fn read(&self, address: u16) -> u8 {
+        match address {
+            Self::SOURCE_HIGH_ADDRESS..=Self::DESTINATION_LOW_ADDRESS => 0xFF,
+            Self::CONTROL_ADDRESS => {
+                let inactive = self.state == State::Idle;
+                (inactive as u8) << 7 | self.remaining_blocks
+            }
+            _ => panic!("Tried to read hdma register out of range"),
+        }
+    }
+
+    pub(crate) fn write(&mut self, address: u16, value: u8) {
+        match address {
+            Self::SOURCE_HIGH_ADDRESS => {
+                self.source = (self.source & 0x00FF) | (value as u16) << 8;
+            }
+            Self::SOURCE_LOW_ADDRESS => {
+                self.source = (self.source & 0xFF00) | (value & 0xF0) as u16;
+            }
+            Self::DESTINATION_HIGH_ADDRESS => {
+                self.destination = (self.destination & 0x00FF) | ((value & 0x1F) as u16) << 8;
+            }
+            Self::DESTINATION_LOW_ADDRESS => {
+                self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16;
+            }
+            Self::CONTROL_ADDRESS => {
+                let hblank = value & 0b1000_0000 != 0;
+                match self.state {
+                    // Writing with bit 7 cleared during an HBlank transfer cancels it.
+                    State::HBlankWaiting | State::HBlankBlock if !hblank => {
+                        self.state = State::Idle;
+                    }
+                    _ => {
+                        self.remaining_blocks = value & 0x7F;
+                        self.block_offset = 0;
+                        self.state = if hblank {
+                            State::HBlankWaiting
+                        } else {
+                            State::General
+                        };
+                    }
+                }
+            }
+            _ => panic!("Tried to write hdma register out of range"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sets up a copy from 0xC000 to VRAM offset 0x0010.
+    fn hdma(control: u8) -> Hdma {
+        let mut hdma = Hdma::new();
+        hdma.write(Hdma::SOURCE_HIGH_ADDRESS, 0xC0);
+        hdma.write(Hdma::SOURCE_LOW_ADDRESS, 0x00);
+        hdma.write(Hdma::DESTINATION_HIGH_ADDRESS, 0x80);
+        hdma.write(Hdma::DESTINATION_LOW_ADDRESS, 0x10);
+        hdma.write(Hdma::CONTROL_ADDRESS, control);
+        hdma
+    }
+
+    fn copy_block(hdma: &mut Hdma) {
+        for _ in 0..Hdma::BLOCK_SIZE {
+            assert!(hdma.next_transfer().is_some());
+        }
+    }
+
+    #[test]
+    fn general_dma() {
+        let mut hdma = hdma(0x01);
+        assert!(hdma.copying());
+        assert_eq!(hdma.read(Hdma::CONTROL_ADDRESS), 0x01);
+        assert_eq!(hdma.next_transfer(), Some((0xC000, 0x0010)));
+        for _ in 1..Hdma::BLOCK_SIZE {
+            hdma.next_transfer();
+        }
+
+        // Blocks are copied without waiting for an HBlank.
+        assert!(hdma.copying());
+        assert_eq!(hdma.read(Hdma::CONTROL_ADDRESS), 0x00);
+        assert_eq!(hdma.next_transfer(), Some((0xC010, 0x0020)));
+        for _ in 1..Hdma::BLOCK_SIZE {
+            hdma.next_transfer();
+        }
+        assert!(!hdma.copying());
+        assert_eq!(hdma.next_transfer(), None);
+        assert_eq!(hdma.read(Hdma::CONTROL_ADDRESS), 0xFF);
+    }
+
+    #[test]
+    fn hblank_dma() {
+        let mut hdma = hdma(0x81);
+        assert!(!hdma.copying());
+        assert_eq!(hdma.next_transfer(), None);
+        assert_eq!(hdma.read(Hdma::CONTROL_ADDRESS), 0x01);
+
+        // One block is copied per HBlank.
+        hdma.start_hblank_block();
+        copy_block(&mut hdma);
+        assert!(!hdma.copying());
+        assert_eq!(hdma.next_transfer(), None);
+        assert_eq!(hdma.read(Hdma::CONTROL_ADDRESS), 0x00);
+
+        hdma.start_hblank_block();
+        copy_block(&mut hdma);
+        assert!(!hdma.copying());
+        assert_eq!(hdma.read(Hdma::CONTROL_ADDRESS), 0xFF);
+        hdma.start_hblank_block();
+        assert!(!hdma.copying());
+    }
+
+    #[test]
+    fn cancel_hblank_dma() {
+        let mut hdma = hdma(0x82);
+        hdma.start_hblank_block();
+        copy_block(&mut hdma);
+        assert_eq!(hdma.read(Hdma::CONTROL_ADDRESS), 0x01);
+
+        // The remaining length is kept, with bit 7 set as the transfer is inactive.
+        hdma.write(Hdma::CONTROL_ADDRESS, 0x00);
+        assert_eq!(hdma.read(Hdma::CONTROL_ADDRESS), 0x81);
+        hdma.start_hblank_block();
+        assert!(!hdma.copying());
+        assert_eq!(hdma.next_transfer(), None);
+    }
+}
@@ -1,8 +1,9 @@
 use flagset::FlagSet;
 use serde::{Deserialize, Serialize};
 
-use super::interrupts::Interrupt;
+use super::{interrupts::Interrupt, serial::Serial};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Button {
     Down,
     Up,
@@ -40,36 +41,98 @@ enum InputLine {
     None = 0b0000_0000,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub(crate) struct Buttons {
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct Joypad {
     directions: u8,
     buttons: u8,
+}
+
+/// Receives Super Game Boy command packets, sent one bit at a time through the joypad register.
+#[derive(Serialize, Deserialize, Debug)]
+struct PacketReceiver {
+    data: [u8; Self::PACKET_SIZE],
+    /// Index of the next bit, none while waiting for a reset pulse.
+    bit: Option<usize>,
+}
+
+impl PacketReceiver {
+    const PACKET_SIZE: usize = 16;
+
+    const fn new() -> Self {
+        Self {
+            data: [0; Self::PACKET_SIZE],
+            bit: None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.data = [0; Self::PACKET_SIZE];
+        self.bit = Some(0);
+    }
+
+    /// Returns the packet once its stop bit is received.
+    fn receive_bit(&mut self, value: bool) -> Option<[u8; Self::PACKET_SIZE]> {
+        let bit = self.bit?;
+        if bit == Self::PACKET_SIZE * 8 {
+            self.bit = None;
+            return Some(self.data);
+        }
+        self.data[bit / 8] |= (value as u8) << (bit % 8);
+        self.bit = Some(bit + 1);
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Buttons {
+    joypads: [Joypad; Self::MAX_PLAYERS],
     current_line: InputLine,
     interrupt_raised: bool,
+    /// Only present in Super Game Boy mode.
+    packet_receiver: Option<PacketReceiver>,
+    received_packet: Option<[u8; PacketReceiver::PACKET_SIZE]>,
+    player_count: u8,
+    current_player: u8,
 }
 
 impl Buttons {
-    const fn new() -> Self {
+    const MAX_PLAYERS: usize = 4;
+
+    const fn new(sgb: bool) -> Self {
         Self {
-            directions: 0x0F,
-            buttons: 0x0F,
+            joypads: [Joypad {
+                directions: 0x0F,
+                buttons: 0x0F,
+            }; Self::MAX_PLAYERS],
             current_line: InputLine::None,
             interrupt_raised: false,
+            packet_receiver: if sgb {
+                Some(PacketReceiver::new())
+            } else {
+                None
+            },
+            received_packet: None,
+            player_count: 1,
+            current_player: 0,
         }
     }
 
     const fn read(&self) -> u8 {
+        let joypad = &self.joypads[self.current_player as usize];
         let nibble_l = match self.current_line {
-            InputLine::Directions => self.directions & 0xF,
-            InputLine::Buttons => self.buttons & 0xF,
+            InputLine::Directions => joypad.directions & 0xF,
+            InputLine::Buttons => joypad.buttons & 0xF,
+            // With no line selected, the Super Game Boy reports the current joypad instead.
+            InputLine::Both if self.player_count > 1 => 0xF - self.current_player,
             InputLine::Both => 0xF,
-            InputLine::None => self.buttons & self.directions & 0xF,
+            InputLine::None => joypad.buttons & joypad.directions & 0xF,
         };
 
         0b1100_0000 | self.current_line as u8 | nibble_l
     }
 
     fn write(&mut self, value: u8) {
+        let previous_line = self.current_line;
         self.current_line = match (value >> 4) & 0b11 {
             0b01 => InputLine::Buttons,
             0b10 => InputLine::Directions,
@@ -78,19 +141,52 @@ impl Buttons {
             _ => unreachable!(),
         };
 
+        if let Some(receiver) = &mut self.packet_receiver {
+            // Bits are sent by pulling one of the lines low, then releasing both.
+            match (previous_line, self.current_line) {
+                (_, InputLine::None) => receiver.reset(),
+                (InputLine::Both, InputLine::Directions) => {
+                    self.received_packet = receiver.receive_bit(false);
+                }
+                (InputLine::Both, InputLine::Buttons) => {
+                    self.received_packet = receiver.receive_bit(true);
+                }
+                // The joypad ID is incremented when P15 goes back high.
+                (InputLine::Buttons | InputLine::None, InputLine::Both)
+                    if self.player_count > 1 =>
+                {
+                    self.current_player = (self.current_player + 1) % self.player_count;
+                }
+                _ => {}
+            }
+        }
+
         if self.read() & 0xF != 0xF {
             self.interrupt_raised = true;
         }
     }
 
-    pub(crate) fn set_button(&mut self, button: Button, set: bool) {
-        if set && self.read() & 0x0F == 0x0F {
+    pub(crate) fn take_sgb_packet(&mut self) -> Option<[u8; PacketReceiver::PACKET_SIZE]> {
+        self.received_packet.take()
+    }
+
+    pub(crate) fn set_player_count(&mut self, player_count: u8) {
+        self.player_count = player_count;
+        self.current_player = 0;
+    }
+
+    pub(crate) fn set_button(&mut self, player: usize, button: Button, set: bool) {
+        if player >= Self::MAX_PLAYERS {
+            return;
+        }
+        if player == self.current_player as usize && set && self.read() & 0x0F == 0x0F {
             self.interrupt_raised = true;
         }
 
+        let joypad = &mut self.joypads[player];
         let line = match button.line() {
-            InputLine::Directions => &mut self.directions,
-            InputLine::Buttons => &mut self.buttons,
+            InputLine::Directions => &mut joypad.directions,
+            InputLine::Buttons => &mut joypad.buttons,
             InputLine::Both | InputLine::None => unreachable!(),
         };
 
@@ -136,6 +232,7 @@ struct Timer {
     apu_inc_div: bool,
     input_clock: InputClock,
     state: TimerState,
+    double_speed: bool,
 }
 
 pub(crate) struct TimerTick {
@@ -158,6 +255,16 @@ impl Timer {
             input_clock: InputClock::CpuDiv1024,
             apu_inc_div: false,
             state: TimerState::Normal,
+            double_speed: false,
+        }
+    }
+
+    // The APU frame sequencer is clocked from a higher DIV bit in double speed mode.
+    const fn apu_div_bit(&self) -> u16 {
+        if self.double_speed {
+            1 << 13
+        } else {
+            1 << 12
         }
     }
 
@@ -165,9 +272,10 @@ impl Timer {
         let old_divider = self.divider;
         self.divider = self.divider.wrapping_add(4);
 
-        // DIV bit 4 high to low
-        let apu_inc_div =
-            (old_divider & (1 << 12)) != 0 && (self.divider & (1 << 12)) == 0 || self.apu_inc_div;
+        // DIV bit 4 (bit 5 in double speed) high to low
+        let apu_div_bit = self.apu_div_bit();
+        let apu_inc_div = (old_divider & apu_div_bit) != 0 && (self.divider & apu_div_bit) == 0
+            || self.apu_inc_div;
         self.apu_inc_div = false;
 
         if !self.enabled {
@@ -225,7 +333,7 @@ impl Timer {
                 if self.divider & self.input_clock.bit() != 0 {
                     self.increase_counter();
                 }
-                if self.divider & (1 << 12) != 0 {
+                if self.divider & self.apu_div_bit() != 0 {
                     self.apu_inc_div = true;
                 }
                 self.divider = 0;
@@ -270,6 +378,7 @@ mod map {
 pub(crate) struct Io {
     pub(super) buttons: Buttons,
     timer: Timer,
+    pub(super) serial: Serial,
 }
 
 pub(crate) struct IoTick {
@@ -278,10 +387,11 @@ pub(crate) struct IoTick {
 }
 
 impl Io {
-    pub(crate) const fn new() -> Self {
+    pub(crate) fn new(cgb: bool, sgb: bool) -> Self {
         Self {
-            buttons: Buttons::new(),
+            buttons: Buttons::new(sgb),
             timer: Timer::new(),
+            serial: Serial::new(cgb),
         }
     }
 
@@ -291,6 +401,7 @@ impl Io {
             interrupts |= Interrupt::Joypad;
         }
 
+        let old_divider = self.timer.divider;
         let TimerTick {
             timer_overflow,
             apu_inc_div,
@@ -299,12 +410,24 @@ impl Io {
             interrupts |= Interrupt::Timer;
         }
 
+        if self.serial.tick(old_divider, self.timer.divider) {
+            interrupts |= Interrupt::Serial;
+        }
+
         IoTick {
             interrupts,
             apu_inc_div,
         }
     }
 
+    pub(crate) fn set_double_speed(&mut self, double_speed: bool) {
+        self.timer.double_speed = double_speed;
+    }
+
+    pub(crate) fn reset_divider(&mut self) {
+        self.timer.write(Timer::DIVIDER_ADDRESS, 0);
+    }
+
     pub(crate) fn tick_stopped(&mut self) -> FlagSet<Interrupt> {
         if self.buttons.interrupt_raised {
             Interrupt::Joypad.into()
@@ -317,8 +440,7 @@ impl Io {
         use map::*;
         match address {
             BUTTONS => self.buttons.read(),
-            SERIAL_TRANSFER => 0xFF,
-            SERIAL_CONTROL => 0xFF,
+            SERIAL_TRANSFER | SERIAL_CONTROL => self.serial.read(address),
             UNUSED => 0xFF,
             TIMER_START..=TIMER_END => self.timer.read(address),
             invalid_address => {
@@ -331,8 +453,7 @@ impl Io {
         use map::*;
         match address {
             BUTTONS => self.buttons.write(value),
-            SERIAL_TRANSFER => {}
-            SERIAL_CONTROL => {}
+            SERIAL_TRANSFER | SERIAL_CONTROL => self.serial.write(address, value),
             UNUSED => {}
             TIMER_START..=TIMER_END => self.timer.write(address, value),
             invalid_address => {
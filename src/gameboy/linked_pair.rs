@@ -0,0 +1,85 @@
+use super::{Gameboy, LinkCable};
+
+/// Two Game Boys connected through a link cable, stepped in lockstep.
+pub struct LinkedPair {
+    gameboys: [Gameboy; 2],
+    cycles: [u64; 2],
+}
+
+impl LinkedPair {
+    pub fn new(mut first: Gameboy, mut second: Gameboy) -> Self {
+        let (first_end, second_end) = LinkCable::pair();
+        first.set_serial_link(Box::new(first_end));
+        second.set_serial_link(Box::new(second_end));
+        Self {
+            gameboys: [first, second],
+            cycles: [0, 0],
+        }
+    }
+
+    /// Runs an instruction on the Game Boy lagging behind, so that both never drift apart by more
+    /// than a single instruction. Returns the number of cycles the pair as a whole advanced.
+    pub fn run_instruction(&mut self) -> u64 {
+        let elapsed_before = self.elapsed_cycles();
+        let index = if self.cycles[0] <= self.cycles[1] {
+            0
+        } else {
+            1
+        };
+        self.cycles[index] += self.gameboys[index].run_instruction();
+        self.elapsed_cycles() - elapsed_before
+    }
+
+    /// Runs until the first Game Boy finishes a frame, see [`Gameboy::run_frame`].
+    pub fn run_frame(&mut self) -> u64 {
+        let elapsed_before = self.elapsed_cycles();
+        let start = self.cycles[0];
+        self.gameboys[0].begin_frame();
+        while !self.gameboys[0].frame_finished(self.cycles[0] - start) {
+            self.run_instruction();
+        }
+        self.elapsed_cycles() - elapsed_before
+    }
+
+    /// Runs both Game Boys for at least the given number of cycles.
+    pub fn run_cycles(&mut self, cycles: u64) -> u64 {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.run_instruction();
+        }
+        elapsed
+    }
+
+    pub fn elapsed_cycles(&self) -> u64 {
+        u64::min(self.cycles[0], self.cycles[1])
+    }
+
+    pub const fn first(&self) -> &Gameboy {
+        &self.gameboys[0]
+    }
+
+    pub fn first_mut(&mut self) -> &mut Gameboy {
+        &mut self.gameboys[0]
+    }
+
+    pub const fn second(&self) -> &Gameboy {
+        &self.gameboys[1]
+    }
+
+    pub fn second_mut(&mut self) -> &mut Gameboy {
+        &mut self.gameboys[1]
+    }
+
+    pub fn gameboys(&self) -> &[Gameboy; 2] {
+        &self.gameboys
+    }
+
+    pub fn gameboys_mut(&mut self) -> &mut [Gameboy; 2] {
+        &mut self.gameboys
+    }
+
+    pub fn into_inner(self) -> (Gameboy, Gameboy) {
+        let [first, second] = self.gameboys;
+        (first, second)
+    }
+}
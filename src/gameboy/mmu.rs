@@ -3,7 +3,15 @@ use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 
 use super::{
-    apu::Apu, cartridge::Cartridge, interrupts::Interrupt, io::Io, ppu::DmaRequest, ppu::Ppu,
+    apu::Apu,
+    cartridge::{Cartridge, CGB_BOOTROM_SIZE},
+    hdma::Hdma,
+    interrupts::Interrupt,
+    io::Io,
+    ppu::{DmaRequest, Ppu, PpuTick},
+    sgb::Sgb,
+    watchpoint::Watchpoints,
+    CgbMode,
 };
 use crate::error::Error;
 
@@ -30,10 +38,23 @@ mod map {
     pub(crate) const APU_REGISTERS_START: u16 = 0xFF10;
     pub(crate) const APU_REGISTERS_END: u16 = 0xFF3F;
     pub(crate) const PPU_REGISTERS_START: u16 = 0xFF40;
-    pub(crate) const PPU_REGISTERS_END: u16 = 0xFF4F;
+    pub(crate) const PPU_REGISTERS_END: u16 = 0xFF4B;
+    pub(crate) const CGB_MODE_SELECT: u16 = 0xFF4C;
+    pub(crate) const CGB_SPEED_SWITCH: u16 = 0xFF4D;
+    pub(crate) const UNUSED_3: u16 = 0xFF4E;
+    pub(crate) const CGB_VRAM_BANK_SELECT: u16 = 0xFF4F;
     pub(crate) const DISABLE_BOOTROM: u16 = 0xFF50;
-    pub(crate) const CGB_REGISTERS_START: u16 = 0xFF51;
-    pub(crate) const CGB_REGISTERS_END: u16 = 0xFF7F;
+    pub(crate) const CGB_HDMA_START: u16 = 0xFF51;
+    pub(crate) const CGB_HDMA_END: u16 = 0xFF55;
+    pub(crate) const UNUSED_4_START: u16 = 0xFF56;
+    pub(crate) const UNUSED_4_END: u16 = 0xFF67;
+    pub(crate) const CGB_PPU_REGISTERS_START: u16 = 0xFF68;
+    pub(crate) const CGB_PPU_REGISTERS_END: u16 = 0xFF6C;
+    pub(crate) const UNUSED_5_START: u16 = 0xFF6D;
+    pub(crate) const UNUSED_5_END: u16 = 0xFF6F;
+    pub(crate) const CGB_WRAM_BANK_SELECT: u16 = 0xFF70;
+    pub(crate) const UNUSED_6_START: u16 = 0xFF71;
+    pub(crate) const UNUSED_6_END: u16 = 0xFF7F;
     pub(crate) const HRAM_START: u16 = 0xFF80;
     pub(crate) const HRAM_END: u16 = 0xFFFE;
     pub(crate) const INTERRUPT_ENABLE: u16 = 0xFFFF;
@@ -70,56 +91,127 @@ pub(crate) trait MemoryOps {
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct Mmu {
     #[serde(with = "BigArray")]
-    wram: [u8; 8192],
+    wram: [u8; Self::WRAM_BANK_SIZE * Self::WRAM_BANK_COUNT],
+    wram_bank: u8,
     #[serde(with = "BigArray")]
     hram: [u8; 127],
     pub(crate) apu: Apu,
     pub(crate) ppu: Ppu,
     pub(crate) io: Io,
     pub(crate) cartridge: Cartridge,
+    pub(crate) sgb: Option<Sgb>,
     dma: Dma,
+    hdma: Hdma,
     pub(crate) interrupt_flags: FlagSet<Interrupt>,
     pub(crate) interrupt_enable: FlagSet<Interrupt>,
     ie_value: u8,
+    pub(crate) cgb_mode: CgbMode,
+    pub(crate) double_speed: bool,
+    speed_switch_armed: bool,
+    odd_tick: bool,
+    /// Set when the PPU enters VBlank.
+    pub(crate) frame_ready: bool,
+    /// Debugger state, kept when loading a savestate.
+    #[serde(skip)]
+    pub(crate) watchpoints: Watchpoints,
 }
 
 impl Mmu {
+    const WRAM_BANK_SIZE: usize = 0x1000;
+    const WRAM_BANK_COUNT: usize = 8;
+    const VRAM_BANK_SIZE: usize = 0x2000;
+    const EXT_RAM_BANK_SIZE: usize = 0x2000;
+
     pub(crate) fn new(
         rom: Vec<u8>,
         bootrom: Option<Vec<u8>>,
         save: Option<Vec<u8>>,
+        sgb: bool,
     ) -> Result<Self, Error> {
-        let ppu = if bootrom.is_some() {
-            Ppu::new()
+        let cartridge = Cartridge::new(rom, bootrom, save)?;
+        // Without a bootrom, the model is picked from the cartridge header.
+        let cgb_mode = match &cartridge.bootrom {
+            Some(bootrom) if bootrom.len() == CGB_BOOTROM_SIZE => CgbMode::Enabled,
+            Some(_) => CgbMode::Disabled,
+            None if cartridge.header.cgb_flag.supports_cgb() => CgbMode::Enabled,
+            None => CgbMode::Disabled,
+        };
+        let sgb = (sgb && cgb_mode == CgbMode::Disabled && cartridge.header.supports_sgb())
+            .then(Sgb::new);
+        let ppu = if cartridge.bootrom_enabled {
+            Ppu::new(cgb_mode)
         } else {
-            Ppu::new_post_bootrom()
+            Ppu::new_post_bootrom(cgb_mode)
         };
         Ok(Self {
-            wram: [0; 8192],
+            wram: [0; Self::WRAM_BANK_SIZE * Self::WRAM_BANK_COUNT],
+            wram_bank: 1,
             hram: [0; 127],
             apu: Apu::new(),
             ppu,
-            io: Io::new(),
-            cartridge: Cartridge::new(rom, bootrom, save)?,
+            io: Io::new(cgb_mode == CgbMode::Enabled, sgb.is_some()),
+            cartridge,
+            sgb,
             dma: Dma::None,
+            hdma: Hdma::new(),
             interrupt_flags: FlagSet::default(),
             interrupt_enable: FlagSet::default(),
             ie_value: 0,
+            cgb_mode,
+            double_speed: false,
+            speed_switch_armed: false,
+            odd_tick: false,
+            frame_ready: false,
+            watchpoints: Watchpoints::default(),
         })
     }
 
     pub(crate) fn tick(&mut self) {
-        self.cartridge.tick();
+        // In double speed mode, only the cpu, timer, serial port and OAM DMA run twice as fast.
+        self.odd_tick = !self.odd_tick;
+        let real_time_tick = !self.double_speed || self.odd_tick;
+
+        if real_time_tick {
+            self.cartridge.tick();
+        }
         let io_tick = self.io.tick();
-        let (ppu_interrupts, dma_request) = self.ppu.tick();
-        self.interrupt_flags |= io_tick.interrupts | ppu_interrupts;
+        let ppu_tick = if real_time_tick {
+            self.ppu.tick()
+        } else {
+            PpuTick {
+                interrupts: FlagSet::default(),
+                dma_request: DmaRequest::None,
+                hblank_started: false,
+            }
+        };
+        self.interrupt_flags |= io_tick.interrupts | ppu_tick.interrupts;
+        if ppu_tick.interrupts.contains(Interrupt::VBlank) {
+            self.frame_ready = true;
+            if let Some(sgb) = &mut self.sgb {
+                sgb.end_frame(self.ppu.shades());
+            }
+        }
+
+        if ppu_tick.hblank_started {
+            self.hdma.start_hblank_block();
+        }
+        // VRAM DMA copies 2 bytes per cycle in normal speed, it takes as long in double speed.
+        let hdma_bytes = if self.double_speed { 1 } else { 2 };
+        for _ in 0..hdma_bytes {
+            if let Some((source, destination)) = self.hdma.next_transfer() {
+                let value = self.read_byte_no_conflict(source);
+                self.ppu.write_vram(destination, value);
+            }
+        }
 
         if io_tick.apu_inc_div {
             self.apu.inc_div();
         }
-        self.apu.tick();
+        if real_time_tick {
+            self.apu.tick();
+        }
 
-        if let DmaRequest::Start(high_byte) = dma_request {
+        if let DmaRequest::Start(high_byte) = ppu_tick.dma_request {
             let base_address = (high_byte as u16) << 8;
             let stored_value = self.read_byte_no_conflict(base_address);
             self.dma = Dma::InProgress {
@@ -148,6 +240,31 @@ impl Mmu {
         }
     }
 
+    pub(crate) fn hdma_copying(&self) -> bool {
+        self.hdma.copying()
+    }
+
+    pub(crate) const fn cycles_per_tick(&self) -> u64 {
+        if self.double_speed {
+            2
+        } else {
+            4
+        }
+    }
+
+    /// Performs a pending speed switch, returns false if no switch was requested.
+    pub(crate) fn switch_speed(&mut self) -> bool {
+        if !self.speed_switch_armed {
+            return false;
+        }
+        // TODO: Emulate the cpu pause happening during the switch.
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        self.io.set_double_speed(self.double_speed);
+        self.io.reset_divider();
+        true
+    }
+
     pub(crate) fn tick_stopped(&mut self) {
         self.interrupt_flags |= self.io.tick_stopped();
     }
@@ -175,14 +292,95 @@ impl Mmu {
         self.interrupt_flags &= !interrupt;
     }
 
-    fn read_byte_no_conflict(&mut self, address: u16) -> u8 {
+    const fn wram_address(&self, address: u16) -> usize {
+        let address = address as usize;
+        if address < Self::WRAM_BANK_SIZE {
+            address
+        } else {
+            address - Self::WRAM_BANK_SIZE + self.wram_bank as usize * Self::WRAM_BANK_SIZE
+        }
+    }
+
+    /// The bank mapped at an address, 0 for areas without banks, as numbered in symbol files.
+    pub(crate) fn bank(&self, address: u16) -> usize {
+        use map::*;
+        match address {
+            ROM_START..=ROM_END => self.cartridge.rom_bank(address),
+            EXT_RAM_START..=EXT_RAM_END => self.cartridge.ram_bank(),
+            // WRAMX is always numbered from 1, even without CGB banking.
+            0xD000..=WRAM_END if self.cgb_mode == CgbMode::Enabled => self.wram_bank as usize,
+            0xD000..=WRAM_END => 1,
+            VRAM_START..=VRAM_END if self.cgb_mode == CgbMode::Enabled => self.ppu.vram_bank(),
+            _ => 0,
+        }
+    }
+
+    /// Finds a byte pattern in VRAM, cartridge SRAM, WRAM and HRAM, looking in all their banks.
+    /// Matches are returned as bank and address.
+    pub(crate) fn search(&self, pattern: &[u8]) -> Vec<(usize, u16)> {
+        use map::*;
+        let cgb = self.cgb_mode == CgbMode::Enabled;
+        let (vram, wram) = if cgb {
+            (self.ppu.vram(), &self.wram[..])
+        } else {
+            (
+                &self.ppu.vram()[..Self::VRAM_BANK_SIZE],
+                &self.wram[..2 * Self::WRAM_BANK_SIZE],
+            )
+        };
+        let find = |area: &[u8]| -> Vec<usize> {
+            if pattern.is_empty() {
+                return vec![];
+            }
+            area.windows(pattern.len())
+                .enumerate()
+                .filter(|(_, window)| *window == pattern)
+                .map(|(offset, _)| offset)
+                .collect()
+        };
+
+        let vram_matches = find(vram).into_iter().map(|offset| {
+            let (bank, offset) = (offset / Self::VRAM_BANK_SIZE, offset % Self::VRAM_BANK_SIZE);
+            (bank, VRAM_START + offset as u16)
+        });
+        let ram_matches = find(self.cartridge.sram()).into_iter().map(|offset| {
+            let (bank, offset) = (
+                offset / Self::EXT_RAM_BANK_SIZE,
+                offset % Self::EXT_RAM_BANK_SIZE,
+            );
+            (bank, EXT_RAM_START + offset as u16)
+        });
+        // The first WRAM bank is always mapped at 0xC000, the others are switched at 0xD000.
+        let wram_matches = find(wram).into_iter().map(|offset| {
+            let (bank, offset) = (offset / Self::WRAM_BANK_SIZE, offset % Self::WRAM_BANK_SIZE);
+            let start = if bank == 0 {
+                WRAM_START
+            } else {
+                WRAM_START + Self::WRAM_BANK_SIZE as u16
+            };
+            (bank, start + offset as u16)
+        });
+        let hram_matches = find(&self.hram)
+            .into_iter()
+            .map(|offset| (0, HRAM_START + offset as u16));
+
+        vram_matches
+            .chain(ram_matches)
+            .chain(wram_matches)
+            .chain(hram_matches)
+            .collect()
+    }
+
+    pub(crate) fn read_byte_no_conflict(&mut self, address: u16) -> u8 {
         use map::*;
         match address {
             ROM_START..=ROM_END => self.cartridge.read_rom(address - ROM_START),
             VRAM_START..=VRAM_END => self.ppu.read_vram(address - VRAM_START),
             EXT_RAM_START..=EXT_RAM_END => self.cartridge.read_ram(address - EXT_RAM_START),
-            WRAM_START..=WRAM_END => self.wram[(address - WRAM_START) as usize],
-            ECHO_WRAM_START..=ECHO_WRAM_END => self.wram[(address - ECHO_WRAM_START) as usize],
+            WRAM_START..=WRAM_END => self.wram[self.wram_address(address - WRAM_START)],
+            ECHO_WRAM_START..=ECHO_WRAM_END => {
+                self.wram[self.wram_address(address - ECHO_WRAM_START)]
+            }
             OAM_START..=OAM_END => self.ppu.read_oam(address - OAM_START),
             UNUSED_START..=UNUSED_END => 0xFF,
             IO_START..=IO_END => self.io.read(address),
@@ -190,36 +388,103 @@ impl Mmu {
             INTERRUPT_FLAGS => self.interrupt_flags.bits() | 0b1110_0000,
             APU_REGISTERS_START..=APU_REGISTERS_END => self.apu.read(address),
             PPU_REGISTERS_START..=PPU_REGISTERS_END => self.ppu.read_registers(address),
+            CGB_MODE_SELECT => 0xFF,
+            CGB_SPEED_SWITCH => match self.cgb_mode {
+                CgbMode::Enabled => {
+                    (self.double_speed as u8) << 7 | 0b0111_1110 | self.speed_switch_armed as u8
+                }
+                CgbMode::Disabled | CgbMode::Compatibility => 0xFF,
+            },
+            UNUSED_3 => 0xFF,
+            CGB_VRAM_BANK_SELECT => self.ppu.read_registers(address),
             DISABLE_BOOTROM => 0xFF,
-            CGB_REGISTERS_START..=CGB_REGISTERS_END => 0xFF,
+            CGB_HDMA_START..=CGB_HDMA_END => match self.cgb_mode {
+                CgbMode::Enabled => self.hdma.read(address),
+                CgbMode::Disabled | CgbMode::Compatibility => 0xFF,
+            },
+            UNUSED_4_START..=UNUSED_4_END => 0xFF,
+            CGB_PPU_REGISTERS_START..=CGB_PPU_REGISTERS_END => self.ppu.read_registers(address),
+            UNUSED_5_START..=UNUSED_5_END => 0xFF,
+            CGB_WRAM_BANK_SELECT => match self.cgb_mode {
+                CgbMode::Enabled => 0b1111_1000 | self.wram_bank,
+                CgbMode::Disabled | CgbMode::Compatibility => 0xFF,
+            },
+            UNUSED_6_START..=UNUSED_6_END => 0xFF,
             HRAM_START..=HRAM_END => self.hram[(address - HRAM_START) as usize],
             INTERRUPT_ENABLE => self.interrupt_enable.bits() | (self.ie_value & 0b1110_0000),
         }
     }
 
-    fn write_byte_no_conflict(&mut self, address: u16, value: u8) {
+    pub(crate) fn write_byte_no_conflict(&mut self, address: u16, value: u8) {
         use map::*;
         match address {
             ROM_START..=ROM_END => self.cartridge.write_rom(address - ROM_START, value),
             VRAM_START..=VRAM_END => self.ppu.write_vram(address - VRAM_START, value),
             EXT_RAM_START..=EXT_RAM_END => self.cartridge.write_ram(address - EXT_RAM_START, value),
-            WRAM_START..=WRAM_END => self.wram[(address - WRAM_START) as usize] = value,
+            WRAM_START..=WRAM_END => self.wram[self.wram_address(address - WRAM_START)] = value,
             ECHO_WRAM_START..=ECHO_WRAM_END => {
-                self.wram[(address - ECHO_WRAM_START) as usize] = value
+                self.wram[self.wram_address(address - ECHO_WRAM_START)] = value
             }
             OAM_START..=OAM_END => self.ppu.write_oam(address - OAM_START, value),
             UNUSED_START..=UNUSED_END => {}
-            IO_START..=IO_END => self.io.write(address, value),
+            IO_START..=IO_END => {
+                self.io.write(address, value);
+                if let Some(sgb) = &mut self.sgb {
+                    if let Some(packet) = self.io.buttons.take_sgb_packet() {
+                        sgb.receive_packet(packet, &mut self.io.buttons);
+                    }
+                }
+            }
             UNUSED_2_START..=UNUSED_2_END => {}
             INTERRUPT_FLAGS => self.interrupt_flags = FlagSet::new_truncated(value),
             APU_REGISTERS_START..=APU_REGISTERS_END => self.apu.write(address, value),
             PPU_REGISTERS_START..=PPU_REGISTERS_END => self.ppu.write_registers(address, value),
+            CGB_MODE_SELECT => {
+                // Only writable by the CGB bootrom, used to enter DMG compatibility mode.
+                if self.cgb_mode != CgbMode::Disabled && self.cartridge.bootrom_enabled {
+                    self.cgb_mode = if value & 0b100 != 0 {
+                        CgbMode::Compatibility
+                    } else {
+                        CgbMode::Enabled
+                    };
+                    self.ppu.set_cgb_mode(self.cgb_mode);
+                    if self.cgb_mode != CgbMode::Enabled {
+                        self.wram_bank = 1;
+                    }
+                }
+            }
+            CGB_SPEED_SWITCH => {
+                if self.cgb_mode == CgbMode::Enabled {
+                    self.speed_switch_armed = value & 0b1 != 0;
+                }
+            }
+            UNUSED_3 => {}
+            CGB_VRAM_BANK_SELECT => self.ppu.write_registers(address, value),
             DISABLE_BOOTROM => {
                 if value != 0 {
                     self.cartridge.disable_bootrom();
                 }
             }
-            CGB_REGISTERS_START..=CGB_REGISTERS_END => {}
+            CGB_HDMA_START..=CGB_HDMA_END => {
+                if self.cgb_mode == CgbMode::Enabled {
+                    self.hdma.write(address, value);
+                    // With the lcd off, HBlank transfers copy a first block once started.
+                    if address == CGB_HDMA_END && !self.ppu.lcd_enabled() {
+                        self.hdma.start_hblank_block();
+                    }
+                }
+            }
+            UNUSED_4_START..=UNUSED_4_END => {}
+            CGB_PPU_REGISTERS_START..=CGB_PPU_REGISTERS_END => {
+                self.ppu.write_registers(address, value)
+            }
+            UNUSED_5_START..=UNUSED_5_END => {}
+            CGB_WRAM_BANK_SELECT => {
+                if self.cgb_mode == CgbMode::Enabled {
+                    self.wram_bank = u8::max(value & 0b111, 1);
+                }
+            }
+            UNUSED_6_START..=UNUSED_6_END => {}
             HRAM_START..=HRAM_END => self.hram[(address - HRAM_START) as usize] = value,
             INTERRUPT_ENABLE => {
                 self.interrupt_enable = FlagSet::new_truncated(value);
@@ -238,7 +503,11 @@ impl MemoryOps for Mmu {
         //        return 0xFF;
         //    }
         //}
-        self.read_byte_no_conflict(address)
+        let value = self.read_byte_no_conflict(address);
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.check_read(address, value);
+        }
+        value
     }
 
     fn write_byte(&mut self, address: u16, value: u8) {
@@ -248,6 +517,60 @@ impl MemoryOps for Mmu {
         //        return;
         //    }
         //}
+        if self.watchpoints.is_empty() {
+            self.write_byte_no_conflict(address, value);
+            return;
+        }
+        let old_value = self
+            .watchpoints
+            .watches_change(address)
+            .then(|| self.read_byte_no_conflict(address));
         self.write_byte_no_conflict(address, value);
+        self.watchpoints.check_write(address, old_value, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CGB_FLAG: usize = 0x143;
+    const LCD_CONTROL: u16 = 0xFF40;
+
+    fn cgb_mmu() -> Mmu {
+        let mut rom = vec![0; 0x8000];
+        rom[CGB_FLAG] = 0x80;
+        Mmu::new(rom, None, None, false).unwrap()
+    }
+
+    #[test]
+    fn hblank_dma_with_lcd_off() {
+        let mut mmu = cgb_mmu();
+        mmu.write_byte(LCD_CONTROL, 0x00);
+        for i in 0..0x20 {
+            mmu.write_byte(map::WRAM_START + i, i as u8 + 1);
+        }
+        for (address, value) in [
+            (0xFF51, 0xC0),
+            (0xFF52, 0x00),
+            (0xFF53, 0x80),
+            (0xFF54, 0x00),
+        ] {
+            mmu.write_byte(address, value);
+        }
+        assert!(!mmu.hdma_copying());
+
+        // The first block is copied right away, the next one waits for an HBlank.
+        mmu.write_byte(map::CGB_HDMA_END, 0x81);
+        while mmu.hdma_copying() {
+            mmu.tick();
+        }
+        assert_eq!(mmu.read_byte(map::CGB_HDMA_END), 0x00);
+        assert_eq!(mmu.read_byte(map::VRAM_START), 0x01);
+        assert_eq!(mmu.read_byte(map::VRAM_START + 0x0F), 0x10);
+        assert_eq!(mmu.read_byte(map::VRAM_START + 0x10), 0x00);
+
+        mmu.write_byte(0xFF51, 0xC0);
+        assert!(!mmu.hdma_copying());
     }
 }
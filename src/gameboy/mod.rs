@@ -1,28 +1,130 @@
 mod apu;
 mod cartridge;
 mod cpu;
+mod expression;
+mod hdma;
 mod interrupts;
 mod io;
+mod linked_pair;
 mod mmu;
 mod ppu;
+mod serial;
+mod sgb;
+mod symbols;
+mod watchpoint;
 
-use std::io::Write;
+use std::{io::Write, mem, path::Path};
 
 use clap::Parser;
 use serde::{Deserialize, Serialize};
 
-use crate::{error::Error, gameboy::mmu::MemoryOps};
-use cpu::Cpu;
+use crate::error::Error;
+use cpu::{Cpu, ExecutionState};
 
+pub use cartridge::{
+    CameraSource, CgbFlag, Destination, Header, StaticImage, TestPattern, CAMERA_HEIGHT,
+    CAMERA_WIDTH,
+};
 pub use io::Button;
+pub use linked_pair::LinkedPair;
+pub use ppu::Color;
+pub use serial::{
+    CaptureLink, LinkCable, LoopbackLink, NullLink, PrintedImage, Printer, Prints, SerialLink,
+    TcpLink,
+};
+pub use symbols::Symbols;
 
-use self::cartridge::{MapperOps, SaveData};
+use self::{
+    cartridge::{MapperOps, SaveData},
+    expression::{Expression, Register},
+    watchpoint::{WatchKind, Watchpoint},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CgbMode {
+    /// Running on DMG hardware.
+    Disabled,
+    /// Running on CGB hardware with CGB features enabled.
+    Enabled,
+    /// Running a DMG cartridge on CGB hardware.
+    Compatibility,
+}
+
+/// A snapshot of the cpu registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuRegisters {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Breakpoint {
+    /// The breakpoint applies to all banks when not set.
+    bank: Option<usize>,
+    address: u16,
+    condition: Option<Expression>,
+    /// Number of hits before actually breaking.
+    ignore_count: usize,
+    hit_count: usize,
+}
+
+/// Where execution started from the debugger must stop.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+enum RunTarget {
+    /// Break after this many more instructions.
+    Steps(usize),
+    /// Break on an address, with the stack pointer at least at a given value to skip the calls
+    /// and recursions in between.
+    Address { address: u16, sp: Option<u16> },
+    /// Break once a return instruction leaves the stack pointer above a value, when the current
+    /// function returns.
+    Return { sp: u16 },
+}
 
 #[derive(Serialize, Deserialize)]
 struct DebugStatus {
-    breakpoints: Vec<u16>,
+    breakpoints: Vec<Breakpoint>,
     should_break: bool,
+    run_target: Option<RunTarget>,
+    break_on_interrupt: bool,
+    break_on_illegal: bool,
+    /// Why the execution stopped, displayed when entering the debugger.
+    break_reason: Option<String>,
+    /// Set when leaving the prompt so that the instruction it stopped on does not hit its
+    /// breakpoint again, cleared once that instruction is fetched.
+    #[serde(skip)]
+    resuming: bool,
+    #[serde(skip)]
+    symbols: Symbols,
+}
+
+/// How a Game Boy is created by `Gameboy::new`.
+#[derive(Debug, Clone, Copy)]
+pub struct GameboyOptions {
+    /// Roms supporting the Super Game Boy run in Super Game Boy mode when set and no Game Boy
+    /// Color mode is used, otherwise they run as on a plain Game Boy.
+    pub sgb: bool,
+    /// Enters the debugger before running the first instruction.
+    pub debug: bool,
 }
+
+impl Default for GameboyOptions {
+    fn default() -> Self {
+        Self {
+            sgb: true,
+            debug: false,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Gameboy {
     cpu: Cpu,
@@ -30,18 +132,26 @@ pub struct Gameboy {
 }
 
 impl Gameboy {
+    /// Cycles are counted in real time, so this stays the same in CGB double speed mode.
     pub const CYCLES_PER_SECOND: u64 = 4_194_304;
+    pub const CYCLES_PER_FRAME: u64 = 70_224;
 
     pub fn new(
         rom: Vec<u8>,
         bootrom: Option<Vec<u8>>,
         save_data: Option<Vec<u8>>,
-        debug: bool,
+        options: GameboyOptions,
     ) -> Result<Self, Error> {
-        let cpu = Cpu::new(rom, bootrom, save_data)?;
+        let cpu = Cpu::new(rom, bootrom, save_data, options.sgb)?;
         let debug_status = DebugStatus {
             breakpoints: vec![],
-            should_break: debug,
+            should_break: options.debug,
+            run_target: None,
+            break_on_interrupt: false,
+            break_on_illegal: false,
+            break_reason: None,
+            resuming: false,
+            symbols: Symbols::new(),
         };
         Ok(Self { cpu, debug_status })
     }
@@ -49,22 +159,80 @@ impl Gameboy {
     pub fn run_instruction(&mut self) -> u64 {
         self.run_debugger();
         let cycles_start = self.cpu.cycles;
-        let cycles_end = self.cpu.next_instruction();
-        cycles_end - cycles_start
+        let cycles_end = self.next_instruction();
+        let cycles = cycles_end - cycles_start;
+        self.cpu.mmu.io.serial.link.advance(cycles);
+        cycles
+    }
+
+    /// Runs until the PPU enters VBlank, or for a frame worth of cycles if the LCD is off.
+    /// Returns the number of elapsed cycles.
+    pub fn run_frame(&mut self) -> u64 {
+        self.begin_frame();
+        let mut cycles = 0;
+        while !self.frame_finished(cycles) {
+            cycles += self.run_instruction();
+        }
+        cycles
+    }
+
+    /// Runs for at least the given number of cycles, returns the number of elapsed cycles.
+    pub fn run_cycles(&mut self, cycles: u64) -> u64 {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.run_instruction();
+        }
+        elapsed
     }
 
-    pub const fn screen(&self) -> &[ppu::Color; 160 * 144] {
+    pub(crate) fn begin_frame(&mut self) {
+        self.cpu.mmu.frame_ready = false;
+    }
+
+    pub(crate) fn frame_finished(&self, cycles: u64) -> bool {
+        self.cpu.mmu.frame_ready
+            || (!self.cpu.mmu.ppu.lcd_enabled() && cycles >= Self::CYCLES_PER_FRAME)
+    }
+
+    pub fn screen(&self) -> &[ppu::Color; 160 * 144] {
         self.cpu.mmu.ppu.screen()
     }
 
+    /// The screen with its border in Super Game Boy mode.
+    pub fn sgb_screen(&self) -> Option<&[ppu::Color; 256 * 224]> {
+        self.cpu.mmu.sgb.as_ref().map(|sgb| sgb.screen())
+    }
+
+    /// Saves the screen as displayed, including the border in Super Game Boy mode.
+    pub fn save_screenshot(&self, path: &Path) -> std::io::Result<()> {
+        match self.sgb_screen() {
+            Some(screen) => ppu::write_png(path, sgb::Sgb::SCREEN_WIDTH, screen),
+            None => ppu::write_png(path, 160, self.screen()),
+        }
+    }
+
     pub fn sound_deltas(&mut self) -> (&[i32], &[i32], &[usize]) {
         self.cpu.mmu.apu.deltas()
     }
 
+    pub fn is_cgb(&self) -> bool {
+        self.cpu.mmu.cgb_mode != CgbMode::Disabled
+    }
+
+    /// Super Game Boy mode is used for DMG cartridges with SGB support.
+    pub fn is_sgb(&self) -> bool {
+        self.cpu.mmu.sgb.is_some()
+    }
+
     pub fn can_save(&self) -> bool {
         self.cpu.mmu.cartridge.mapper.has_battery()
     }
 
+    /// Whether the cartridge rumble motor is currently running.
+    pub fn rumble(&self) -> bool {
+        self.cpu.mmu.cartridge.mapper.rumble()
+    }
+
     pub fn save_data(&self) -> SaveData {
         self.cpu.mmu.cartridge.save_data()
     }
@@ -81,11 +249,23 @@ impl Gameboy {
             &mut self.cpu.mmu.cartridge.bootrom,
             &mut gameboy.cpu.mmu.cartridge.bootrom,
         );
+        std::mem::swap(
+            &mut self.cpu.mmu.io.serial.link,
+            &mut gameboy.cpu.mmu.io.serial.link,
+        );
+        std::mem::swap(
+            &mut self.debug_status.symbols,
+            &mut gameboy.debug_status.symbols,
+        );
+        std::mem::swap(
+            &mut self.cpu.mmu.watchpoints,
+            &mut gameboy.cpu.mmu.watchpoints,
+        );
         std::mem::swap(self, &mut gameboy);
         Ok(())
     }
 
-    pub const fn rom_header(&self) -> &cartridge::Header {
+    pub const fn rom_header(&self) -> &Header {
         &self.cpu.mmu.cartridge.header
     }
 
@@ -93,32 +273,243 @@ impl Gameboy {
         &self.cpu.mmu.cartridge.mapper
     }
 
+    pub fn registers(&self) -> CpuRegisters {
+        let registers = &self.cpu.registers;
+        let [a, f] = registers.af().to_be_bytes();
+        CpuRegisters {
+            a,
+            f,
+            b: registers.b,
+            c: registers.c,
+            d: registers.d,
+            e: registers.e,
+            h: registers.h,
+            l: registers.l,
+            sp: registers.sp,
+            pc: registers.pc,
+        }
+    }
+
+    /// Reads memory as seen by the cpu, ignoring OAM DMA bus conflicts.
+    pub fn read_memory(&mut self, address: u16) -> u8 {
+        self.cpu.mmu.read_byte_no_conflict(address)
+    }
+
     pub fn set_button(&mut self, button: Button, set: bool) {
-        self.cpu.mmu.io.buttons.set_button(button, set);
+        self.set_player_button(0, button, set);
+    }
+
+    /// Sets a button on one of the 4 joypads available in Super Game Boy multiplayer mode, `player`
+    /// going from 0 to 3. Other players are ignored.
+    pub fn set_player_button(&mut self, player: usize, button: Button, set: bool) {
+        self.cpu.mmu.io.buttons.set_button(player, button, set);
+    }
+
+    /// Sets the images seen by the Pocket Camera sensor, a test pattern by default.
+    pub fn set_camera_source(&mut self, source: Box<dyn CameraSource>) {
+        if let cartridge::Mapper::PocketCamera(mapper) = &mut self.cpu.mmu.cartridge.mapper {
+            mapper.set_source(source);
+        }
+    }
+
+    /// Tilts the cartridge accelerometer, in g on each axis with x to the right and y to the bottom.
+    /// Only used by MBC7 cartridges.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let cartridge::Mapper::Mbc7(mapper) = &mut self.cpu.mmu.cartridge.mapper {
+            mapper.set_tilt(x, y);
+        }
+    }
+
+    pub fn set_serial_link(&mut self, link: Box<dyn SerialLink>) {
+        self.cpu.mmu.io.serial.set_link(link);
+    }
+
+    /// Sets the labels shown by the debugger.
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.debug_status.symbols = symbols;
     }
 
     pub fn debug_break(&mut self) {
         self.debug_status.should_break = true;
     }
 
+    /// Runs the next cpu instruction, breaking if it hit a watchpoint, serviced an interrupt or
+    /// was illegal when the debugger is set to break on those.
+    fn next_instruction(&mut self) -> u64 {
+        let pc = self.cpu.registers.pc;
+        let was_illegal = matches!(self.cpu.execution_state, ExecutionState::IllegalInstruction);
+        let return_target = match self.debug_status.run_target {
+            Some(RunTarget::Return { sp }) if self.cpu.fetches_instruction() => {
+                Some((sp, self.cpu.mmu.read_byte_no_conflict(pc)))
+            }
+            _ => None,
+        };
+        let cycles = self.cpu.next_instruction();
+        if let Some((sp, opcode)) = return_target {
+            // Only a taken RET, RETI or RET cc completes the target, not the pops or other sp
+            // changes in the function.
+            let returned = matches!(opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9)
+                && self.cpu.registers.pc != pc.wrapping_add(1);
+            if returned && self.cpu.registers.sp > sp {
+                self.debug_status.run_target = Some(RunTarget::Steps(0));
+            }
+        }
+        if let Some(hit) = self.cpu.mmu.watchpoints.take_hit() {
+            self.break_with_reason(format!("Watchpoint hit by 0x{pc:04X}: {hit}"));
+        }
+        if let Some(interrupt) = self.cpu.serviced_interrupt {
+            if self.debug_status.break_on_interrupt {
+                self.break_with_reason(format!("{interrupt:?} interrupt serviced from 0x{pc:04X}"));
+            }
+        }
+        if self.debug_status.break_on_illegal
+            && !was_illegal
+            && matches!(self.cpu.execution_state, ExecutionState::IllegalInstruction)
+        {
+            let opcode = self.cpu.mmu.read_byte_no_conflict(pc);
+            self.break_with_reason(format!("Illegal opcode 0x{opcode:02X} at 0x{pc:04X}"));
+        }
+        cycles
+    }
+
+    fn break_with_reason(&mut self, reason: String) {
+        self.debug_status.break_reason.get_or_insert(reason);
+        self.debug_status.should_break = true;
+    }
+
+    /// Whether the target of a step command is reached, counting the steps.
+    fn check_run_target(&mut self) -> bool {
+        let registers = &self.cpu.registers;
+        match &mut self.debug_status.run_target {
+            None => false,
+            Some(RunTarget::Steps(0)) => true,
+            Some(RunTarget::Steps(steps)) => {
+                *steps -= 1;
+                false
+            }
+            Some(RunTarget::Address { address, sp }) => {
+                registers.pc == *address && sp.map_or(true, |sp| registers.sp >= sp)
+            }
+            // Replaced by a step target once the return instruction ran.
+            Some(RunTarget::Return { .. }) => false,
+        }
+    }
+
+    /// Exits the debugger prompt, running until a target is reached.
+    fn run_to(&mut self, target: RunTarget) {
+        self.debug_status.run_target = Some(target);
+    }
+
+    /// Formats an address with its current bank and label.
+    fn describe_address(&self, address: u16) -> String {
+        self.debug_status
+            .symbols
+            .describe(self.cpu.mmu.bank(address), address)
+    }
+
+    /// Parses a `bank:address` pair as in symbol files, an address, or looks up a label in the
+    /// symbols. The bank is only known in the first and last cases.
+    fn parse_banked_location(&self, location: &str) -> Option<(Option<usize>, u16)> {
+        if let Some((bank, address)) = location.split_once(':') {
+            let bank = usize::from_str_radix(split_radix(bank).0, 16).ok()?;
+            let address = u16::from_str_radix(split_radix(address).0, 16).ok()?;
+            return Some((Some(bank), address));
+        }
+        parse_address(location)
+            .map(|address| (None, address))
+            .ok()
+            .or_else(|| {
+                self.debug_status
+                    .symbols
+                    .address(location)
+                    .map(|(bank, address)| (Some(bank), address))
+            })
+    }
+
+    /// Parses a location, ignoring its bank for commands accessing the memory as currently mapped.
+    fn parse_location(&self, location: &str) -> Option<u16> {
+        self.parse_banked_location(location)
+            .map(|(_, address)| address)
+    }
+
+    /// Counts hits on the breakpoints matching the instruction about to be fetched, returns
+    /// whether one of them should break.
+    fn check_breakpoints(&mut self) -> bool {
+        if mem::take(&mut self.debug_status.resuming) {
+            return false;
+        }
+        let pc = self.cpu.registers.pc;
+        let breakpoints = &mut self.debug_status.breakpoints;
+        if !breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.address == pc)
+        {
+            return false;
+        }
+
+        let bank = self.cpu.mmu.bank(pc);
+        let mut should_break = false;
+        for breakpoint in breakpoints.iter_mut() {
+            if breakpoint.address != pc || matches!(breakpoint.bank, Some(b) if b != bank) {
+                continue;
+            }
+            if let Some(condition) = &breakpoint.condition {
+                if !condition.is_true(&mut self.cpu) {
+                    continue;
+                }
+            }
+            breakpoint.hit_count += 1;
+            should_break |= breakpoint.hit_count > breakpoint.ignore_count;
+        }
+        should_break
+    }
+
+    fn print_disassembly(&mut self, mut address: u16, count: usize) {
+        for _ in 0..count {
+            let bank = self.cpu.mmu.bank(address);
+            if let Some(label) = self.debug_status.symbols.label(bank, address) {
+                println!("{label}:");
+            }
+            let instruction = self.cpu.disassemble(address, &self.debug_status.symbols);
+            let bytes = instruction
+                .bytes
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let marker = if address == self.cpu.registers.pc {
+                '>'
+            } else {
+                ' '
+            };
+            println!(
+                "{marker} {bank:02X}:{address:04X}  {bytes:<8}  {}",
+                instruction.text
+            );
+            address = address.wrapping_add(instruction.bytes.len() as u16);
+        }
+    }
+
     // TODO: Probably move to Emulator
     fn run_debugger(&mut self) {
-        if !self
-            .debug_status
-            .breakpoints
-            .contains(&self.cpu.registers.pc)
-            && !self.debug_status.should_break
-        {
+        // Breakpoints and run targets are only checked when an instruction is fetched, not while
+        // the cpu is waiting in halt, stop or a VRAM DMA copy.
+        let fetching = self.cpu.fetches_instruction();
+        let target_reached = fetching && self.check_run_target();
+        let breakpoint_hit = fetching && self.check_breakpoints();
+        if !breakpoint_hit && !self.debug_status.should_break && !target_reached {
             return;
         }
         self.debug_status.should_break = false;
+        self.debug_status.run_target = None;
         let mut buf = String::new();
         loop {
+            if let Some(reason) = self.debug_status.break_reason.take() {
+                println!("{reason}");
+            }
             let pc = self.cpu.registers.pc;
-            println!(
-                "Breaked on 0x{pc:04X} (op 0x{:02X})",
-                self.cpu.mmu.read_byte(pc)
-            );
+            println!("Breaked on {}", self.describe_address(pc));
+            self.print_disassembly(pc, 1);
             buf.clear();
             print!("> ");
             let _ = std::io::stdout().flush();
@@ -134,13 +525,67 @@ impl Gameboy {
                 }
             };
             match arg {
-                DebugCommand::Breakpoint { address } => {
-                    if !self.debug_status.breakpoints.contains(&address) {
-                        self.debug_status.breakpoints.push(address);
+                DebugCommand::Breakpoint {
+                    location,
+                    ignore,
+                    condition,
+                } => {
+                    let Some((bank, address)) = self.parse_banked_location(&location) else {
+                        println!("Unknown address or label {location}");
+                        continue;
+                    };
+                    let condition = match condition.split_first() {
+                        None => None,
+                        Some((keyword, expression)) if keyword == "if" => {
+                            match Expression::parse(&expression.join(" ")) {
+                                Ok(expression) => Some(expression),
+                                Err(error) => {
+                                    println!("{error}");
+                                    continue;
+                                }
+                            }
+                        }
+                        Some(_) => {
+                            println!("Conditions must start with \"if\"");
+                            continue;
+                        }
+                    };
+                    // Setting a breakpoint again replaces its condition.
+                    let breakpoints = &mut self.debug_status.breakpoints;
+                    breakpoints.retain(|breakpoint| {
+                        breakpoint.bank != bank || breakpoint.address != address
+                    });
+                    breakpoints.push(Breakpoint {
+                        bank,
+                        address,
+                        condition,
+                        ignore_count: ignore.unwrap_or(0),
+                        hit_count: 0,
+                    });
+                }
+                DebugCommand::Delete { location } => {
+                    let Some((bank, address)) = self.parse_banked_location(&location) else {
+                        println!("Unknown address or label {location}");
+                        continue;
+                    };
+                    self.debug_status.breakpoints.retain(|breakpoint| {
+                        breakpoint.address != address
+                            || bank.map_or(false, |b| breakpoint.bank != Some(b))
+                    });
+                }
+                DebugCommand::Watch { kind, start, end } => {
+                    let end = end.unwrap_or(start);
+                    if end < start {
+                        println!("Invalid range");
+                        continue;
                     }
+                    self.cpu
+                        .mmu
+                        .watchpoints
+                        .add(Watchpoint { kind, start, end });
                 }
-                DebugCommand::Delete { address } => {
-                    self.debug_status.breakpoints.retain(|&a| a != address);
+                DebugCommand::Unwatch { address } => {
+                    self.cpu.mmu.watchpoints.delete(address);
                 }
                 DebugCommand::List => {
                     println!(
@@ -152,13 +597,112 @@ impl Gameboy {
                         }
                     );
                     for breakpoint in &self.debug_status.breakpoints {
-                        println!("0x{breakpoint:04X}");
+                        let mut description = match breakpoint.bank {
+                            Some(bank) => {
+                                self.debug_status.symbols.describe(bank, breakpoint.address)
+                            }
+                            None => format!("0x{:04X} in any bank", breakpoint.address),
+                        };
+                        if let Some(condition) = &breakpoint.condition {
+                            description.push_str(&format!(" if {condition}"));
+                        }
+                        description.push_str(&format!(" (hit {} times", breakpoint.hit_count));
+                        if breakpoint.ignore_count > 0 {
+                            description
+                                .push_str(&format!(", ignoring {}", breakpoint.ignore_count));
+                        }
+                        println!("{description})");
+                    }
+                    println!(
+                        "{}",
+                        if self.cpu.mmu.watchpoints.is_empty() {
+                            "No watchpoints"
+                        } else {
+                            "Current watchpoints:"
+                        }
+                    );
+                    for watchpoint in self.cpu.mmu.watchpoints.iter() {
+                        println!("{watchpoint}");
                     }
                 }
                 DebugCommand::Read { address } => {
-                    let value = self.cpu.mmu.read_byte(address);
+                    let value = self.cpu.mmu.read_byte_no_conflict(address);
                     println!("(0x{address:04X}) = 0x{value:02X}");
                 }
+                DebugCommand::Dump { location, length } => {
+                    let Some(address) = self.parse_location(&location) else {
+                        println!("Unknown address or label {location}");
+                        continue;
+                    };
+                    let length = length.unwrap_or(0x80);
+                    for line_start in (0..length).step_by(16) {
+                        let line_address = address.wrapping_add(line_start);
+                        let bytes: Vec<u8> = (0..(length - line_start).min(16))
+                            .map(|i| {
+                                self.cpu
+                                    .mmu
+                                    .read_byte_no_conflict(line_address.wrapping_add(i))
+                            })
+                            .collect();
+                        let hex = bytes
+                            .iter()
+                            .map(|byte| format!("{byte:02X}"))
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        let ascii: String = bytes
+                            .iter()
+                            .map(|&byte| {
+                                if byte.is_ascii_graphic() || byte == b' ' {
+                                    byte as char
+                                } else {
+                                    '.'
+                                }
+                            })
+                            .collect();
+                        let bank = self.cpu.mmu.bank(line_address);
+                        println!("{bank:02X}:{line_address:04X}  {hex:<47}  |{ascii}|");
+                    }
+                }
+                DebugCommand::Write { location, bytes } => {
+                    let Some(address) = self.parse_location(&location) else {
+                        println!("Unknown address or label {location}");
+                        continue;
+                    };
+                    for (i, &byte) in bytes.iter().enumerate() {
+                        self.cpu
+                            .mmu
+                            .write_byte_no_conflict(address.wrapping_add(i as u16), byte);
+                    }
+                }
+                DebugCommand::Fill {
+                    location,
+                    length,
+                    value,
+                } => {
+                    let Some(address) = self.parse_location(&location) else {
+                        println!("Unknown address or label {location}");
+                        continue;
+                    };
+                    for i in 0..length {
+                        self.cpu
+                            .mmu
+                            .write_byte_no_conflict(address.wrapping_add(i), value);
+                    }
+                }
+                DebugCommand::Search { pattern } => {
+                    let matches = self.cpu.mmu.search(&pattern);
+                    if matches.is_empty() {
+                        println!("No matches");
+                    }
+                    for (bank, address) in matches {
+                        println!("{bank:02X}:{address:04X}");
+                    }
+                }
+                DebugCommand::Set { register, value } => {
+                    if let Err(error) = register.set(&mut self.cpu.registers, value) {
+                        println!("{error}");
+                    }
+                }
                 DebugCommand::Registers => {
                     // TODO Better debug print
                     println!(
@@ -169,38 +713,158 @@ impl Gameboy {
                         self.cpu.execution_state,
                     );
                 }
-                DebugCommand::Step => {
-                    self.cpu.next_instruction();
+                DebugCommand::Disassemble { location, count } => {
+                    let address = match location {
+                        Some(location) => match self.parse_location(&location) {
+                            Some(address) => address,
+                            None => {
+                                println!("Unknown address or label {location}");
+                                continue;
+                            }
+                        },
+                        None => pc,
+                    };
+                    self.print_disassembly(address, count.unwrap_or(10));
+                }
+                DebugCommand::Stack { count } => {
+                    let sp = self.cpu.registers.sp;
+                    for i in 0..count.unwrap_or(8) {
+                        let address = sp.wrapping_add(i * 2);
+                        let value = u16::from_le_bytes([
+                            self.cpu.mmu.read_byte_no_conflict(address),
+                            self.cpu.mmu.read_byte_no_conflict(address.wrapping_add(1)),
+                        ]);
+                        println!("0x{address:04X}: {}", self.describe_address(value));
+                    }
+                }
+                DebugCommand::Step { count } => {
+                    // The first instruction runs right after leaving the prompt.
+                    self.run_to(RunTarget::Steps(count.unwrap_or(1).saturating_sub(1)));
+                    break;
+                }
+                DebugCommand::Next => {
+                    let opcode = self.cpu.mmu.read_byte_no_conflict(pc);
+                    let is_call = matches!(opcode, 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC)
+                        || opcode & 0b1100_0111 == 0b1100_0111;
+                    let target = if is_call {
+                        let length = self
+                            .cpu
+                            .disassemble(pc, &self.debug_status.symbols)
+                            .bytes
+                            .len();
+                        RunTarget::Address {
+                            address: pc.wrapping_add(length as u16),
+                            sp: Some(self.cpu.registers.sp),
+                        }
+                    } else {
+                        RunTarget::Steps(0)
+                    };
+                    self.run_to(target);
+                    break;
+                }
+                DebugCommand::Finish => {
+                    self.run_to(RunTarget::Return {
+                        sp: self.cpu.registers.sp,
+                    });
+                    break;
+                }
+                DebugCommand::Until { location } => {
+                    let Some(address) = self.parse_location(&location) else {
+                        println!("Unknown address or label {location}");
+                        continue;
+                    };
+                    self.run_to(RunTarget::Address { address, sp: None });
+                    break;
+                }
+                DebugCommand::Catch { event } => {
+                    let (name, enabled) = match event {
+                        CatchEvent::Interrupt => {
+                            ("interrupts", &mut self.debug_status.break_on_interrupt)
+                        }
+                        CatchEvent::Illegal => (
+                            "illegal instructions",
+                            &mut self.debug_status.break_on_illegal,
+                        ),
+                    };
+                    *enabled = !*enabled;
+                    println!(
+                        "Break on {name}: {}",
+                        if *enabled { "enabled" } else { "disabled" }
+                    );
                 }
                 DebugCommand::Continue => break,
             };
         }
+        self.debug_status.resuming = true;
     }
 }
 
-fn parse_address(address: &str) -> Result<u16, &'static str> {
-    let (address, radix) = if let Some(s_address) = address.strip_prefix("0x") {
-        (s_address, 16)
+fn split_radix(value: &str) -> (&str, u32) {
+    if let Some(hex_value) = value.strip_prefix("0x") {
+        (hex_value, 16)
     } else {
-        (address, 10)
-    };
+        (value, 10)
+    }
+}
+
+fn parse_address(address: &str) -> Result<u16, &'static str> {
+    let (address, radix) = split_radix(address);
     u16::from_str_radix(address, radix).map_err(|_| "Invalid address")
 }
 
+fn parse_byte(value: &str) -> Result<u8, &'static str> {
+    let (value, radix) = split_radix(value);
+    u8::from_str_radix(value, radix).map_err(|_| "Invalid byte")
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum CatchEvent {
+    /// Break when the cpu jumps to an interrupt handler
+    Interrupt,
+    /// Break when the cpu executes an illegal opcode and locks up
+    Illegal,
+}
+
 #[derive(Parser, Clone)]
 #[command(multicall = true)]
 enum DebugCommand {
-    /// Set a breakpoint to an address
+    /// Set a breakpoint to an address, optionally with a condition such as
+    /// `b 01:4000 if a == 3 && [0xC000] > 10`
     #[command(visible_alias = "b", arg_required_else_help = true)]
     Breakpoint {
-        /// The address to break on, either in decimal or in hexadecimal prefixed by "0x"
-        #[arg(value_parser=parse_address)]
-        address: u16,
+        /// Number of hits to ignore before breaking
+        #[arg(short, long, value_name = "COUNT")]
+        ignore: Option<usize>,
+        /// The address to break on, either in decimal, in hexadecimal prefixed by "0x", as a
+        /// hexadecimal bank:address pair or a label. Only labels and pairs are limited to a bank
+        location: String,
+        /// A condition starting with "if", over registers, bytes in memory written [address],
+        /// numbers and the + - & | == != < <= > >= ! && || operators
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        condition: Vec<String>,
     },
-    /// Delete a breakpoint at an address
+    /// Delete the breakpoints at an address, in all banks unless one is given
     #[command(visible_alias = "d", arg_required_else_help = true)]
     Delete {
-        /// The address for which the breakpoint must be deleted
+        /// The address for which the breakpoints must be deleted
+        location: String,
+    },
+    /// Break when the cpu accesses memory in an address range
+    #[command(visible_alias = "w", arg_required_else_help = true)]
+    Watch {
+        /// The kind of access to break on
+        kind: WatchKind,
+        /// The first address of the range, either in decimal or in hexadecimal prefixed by "0x"
+        #[arg(value_parser=parse_address)]
+        start: u16,
+        /// The last address of the range, included. By default, only the first address is watched
+        #[arg(value_parser=parse_address)]
+        end: Option<u16>,
+    },
+    /// Delete the watchpoints starting at an address
+    #[command(arg_required_else_help = true)]
+    Unwatch {
+        /// The first address of the watchpoints to delete
         #[arg(value_parser=parse_address)]
         address: u16,
     },
@@ -209,16 +873,154 @@ enum DebugCommand {
         #[arg(value_parser=parse_address)]
         address: u16,
     },
-    /// List all breakpoints
+    /// List all breakpoints and watchpoints
     #[command(visible_alias = "l")]
     List,
+    /// Disassemble instructions
+    #[command(visible_alias = "x")]
+    Disassemble {
+        /// The address to start from, either in decimal, in hexadecimal prefixed by "0x" or a
+        /// label. By default, the current instruction
+        location: Option<String>,
+        /// The number of instructions to disassemble, 10 by default
+        count: Option<usize>,
+    },
+    /// Display the values on the top of the stack, with the labels they point into
+    #[command(visible_alias = "bt")]
+    Stack {
+        /// The number of values to display, 8 by default
+        count: Option<u16>,
+    },
+    /// Display memory as hexadecimal and ASCII
+    #[command(arg_required_else_help = true)]
+    Dump {
+        /// The address to start from, either in decimal, in hexadecimal prefixed by "0x" or a label
+        location: String,
+        /// The number of bytes to display, 128 by default
+        #[arg(value_parser=parse_address)]
+        length: Option<u16>,
+    },
+    /// Write bytes to memory
+    #[command(arg_required_else_help = true)]
+    Write {
+        /// The address to start from, either in decimal, in hexadecimal prefixed by "0x" or a label
+        location: String,
+        /// The bytes to write, either in decimal or in hexadecimal prefixed by "0x"
+        #[arg(value_parser=parse_byte, required = true)]
+        bytes: Vec<u8>,
+    },
+    /// Fill memory with a byte
+    #[command(arg_required_else_help = true)]
+    Fill {
+        /// The address to start from, either in decimal, in hexadecimal prefixed by "0x" or a label
+        location: String,
+        /// The number of bytes to fill
+        #[arg(value_parser=parse_address)]
+        length: u16,
+        /// The byte to fill with, either in decimal or in hexadecimal prefixed by "0x"
+        #[arg(value_parser=parse_byte)]
+        value: u8,
+    },
+    /// Search a byte sequence in VRAM, cartridge RAM, WRAM and HRAM, in all their banks
+    #[command(arg_required_else_help = true)]
+    Search {
+        /// The bytes to search for, either in decimal or in hexadecimal prefixed by "0x"
+        #[arg(value_parser=parse_byte, required = true)]
+        pattern: Vec<u8>,
+    },
+    /// Set a register value
+    #[command(arg_required_else_help = true)]
+    Set {
+        /// The register to set
+        register: Register,
+        /// The value to set, either in decimal or in hexadecimal prefixed by "0x"
+        #[arg(value_parser=parse_address)]
+        value: u16,
+    },
     /// Display the current registers state
     #[command(visible_alias = "r")]
     Registers,
-    /// Step one instruction
+    /// Step instructions, entering calls
     #[command(visible_alias = "s")]
-    Step,
+    Step {
+        /// The number of instructions to run, 1 by default
+        count: Option<usize>,
+    },
+    /// Step one instruction, running calls and restarts until they return
+    #[command(visible_alias = "n")]
+    Next,
+    /// Run until the current function returns
+    #[command(visible_alias = "f")]
+    Finish,
+    /// Run until an address is reached
+    #[command(visible_alias = "u", arg_required_else_help = true)]
+    Until {
+        /// The address to stop at, either in decimal, in hexadecimal prefixed by "0x" or a label
+        location: String,
+    },
+    /// Toggle breaking when an interrupt is serviced or an illegal instruction is executed
+    #[command(arg_required_else_help = true)]
+    Catch {
+        /// The event to break on
+        event: CatchEvent,
+    },
     /// Resume execution
     #[command(visible_alias = "c")]
     Continue,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finish_breaks_on_return() {
+        let mut rom = vec![0; 0x8000];
+        // call 0x0200, nop
+        rom[0x100..0x104].copy_from_slice(&[0xCD, 0x00, 0x02, 0x00]);
+        // push bc, pop bc, push bc, add sp, 2, ret
+        rom[0x200..0x206].copy_from_slice(&[0xC5, 0xC1, 0xC5, 0xE8, 0x02, 0xC9]);
+        let mut gameboy = Gameboy::new(rom, None, None, GameboyOptions::default()).unwrap();
+        let sp = gameboy.cpu.registers.sp;
+
+        gameboy.next_instruction();
+        gameboy.next_instruction();
+        assert_eq!(gameboy.cpu.registers.pc, 0x201);
+        gameboy.run_to(RunTarget::Return {
+            sp: gameboy.cpu.registers.sp,
+        });
+        for _ in 0..4 {
+            assert!(!gameboy.check_run_target());
+            gameboy.next_instruction();
+        }
+        assert!(gameboy.check_run_target());
+        assert_eq!(gameboy.cpu.registers.pc, 0x103);
+        assert_eq!(gameboy.cpu.registers.sp, sp);
+    }
+
+    #[test]
+    fn breakpoint_in_single_instruction_loop() {
+        let mut rom = vec![0; 0x8000];
+        // jr @
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+        let mut gameboy = Gameboy::new(rom, None, None, GameboyOptions::default()).unwrap();
+        gameboy.debug_status.breakpoints.push(Breakpoint {
+            bank: None,
+            address: 0x100,
+            condition: None,
+            ignore_count: 2,
+            hit_count: 0,
+        });
+
+        for should_break in [false, false, true, true] {
+            assert_eq!(gameboy.check_breakpoints(), should_break);
+            gameboy.next_instruction();
+        }
+        // Only the instruction the prompt stopped on is skipped when resuming.
+        gameboy.debug_status.resuming = true;
+        assert!(!gameboy.check_breakpoints());
+        gameboy.next_instruction();
+        assert!(gameboy.check_breakpoints());
+        assert_eq!(gameboy.debug_status.breakpoints[0].hit_count, 5);
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+use super::Color;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CgbPalettes {
+    #[serde(with = "BigArray")]
+    data: [u8; Self::SIZE],
+    index: u8,
+    auto_increment: bool,
+}
+
+impl Default for CgbPalettes {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl CgbPalettes {
+    const SIZE: usize = 64;
+    const INDEX_MASK: u8 = 0b11_1111;
+
+    pub(crate) const fn new(fill: u8) -> Self {
+        Self {
+            data: [fill; Self::SIZE],
+            index: 0,
+            auto_increment: false,
+        }
+    }
+
+    pub(crate) const fn spec_value(&self) -> u8 {
+        (self.auto_increment as u8) << 7 | 0b0100_0000 | self.index
+    }
+
+    pub(crate) fn set_spec_value(&mut self, value: u8) {
+        self.auto_increment = value & 0b1000_0000 != 0;
+        self.index = value & Self::INDEX_MASK;
+    }
+
+    pub(crate) const fn data_value(&self) -> u8 {
+        self.data[self.index as usize]
+    }
+
+    pub(crate) fn set_data_value(&mut self, value: u8, writable: bool) {
+        if writable {
+            self.data[self.index as usize] = value;
+        }
+        if self.auto_increment {
+            self.index = (self.index + 1) & Self::INDEX_MASK;
+        }
+    }
+
+    pub(crate) fn color(&self, palette: u8, index: u8) -> Color {
+        let offset = palette as usize * 8 + index as usize * 2;
+        Color::from_rgb555(u16::from_le_bytes([
+            self.data[offset],
+            self.data[offset + 1],
+        ]))
+    }
+}
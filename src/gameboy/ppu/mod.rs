@@ -1,22 +1,29 @@
+mod cgb_palette;
 mod lcd_control;
 mod lcd_status;
 mod obj;
-mod palette;
+pub(crate) mod palette;
 mod pixel_transfer;
 
-use std::convert::TryInto;
+use std::{
+    convert::TryInto,
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+};
 
 use flagset::FlagSet;
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 
 use self::{
+    cgb_palette::CgbPalettes,
     lcd_control::LcdControl,
     lcd_status::{LcdStatus, Mode},
     palette::Palette,
-    pixel_transfer::{mix_pixels, BgPixel, Fetcher, ObjPixel, PixelFifo},
+    pixel_transfer::{dmg_shade, mix_pixels, BgPixel, Fetcher, ObjPixel, PixelFifo},
 };
-use super::interrupts::Interrupt;
+use super::{interrupts::Interrupt, CgbMode};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) enum DmaRequest {
@@ -24,6 +31,12 @@ pub(crate) enum DmaRequest {
     Start(u8),
 }
 
+pub(crate) struct PpuTick {
+    pub(crate) interrupts: FlagSet<Interrupt>,
+    pub(crate) dma_request: DmaRequest,
+    pub(crate) hblank_started: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Color([u8; 4]);
 
@@ -38,6 +51,16 @@ impl From<palette::Color> for Color {
     }
 }
 
+impl Color {
+    pub(crate) fn from_rgb555(value: u16) -> Self {
+        let channel = |shift: u16| {
+            let value = ((value >> shift) & 0b1_1111) as u8;
+            (value << 3) | (value >> 2)
+        };
+        Self([channel(0), channel(5), channel(10), 0xFF])
+    }
+}
+
 impl From<Color> for [u8; 4] {
     fn from(val: Color) -> Self {
         val.0
@@ -50,19 +73,36 @@ impl From<[u8; 4]> for Color {
     }
 }
 
+pub(crate) fn write_png(path: &Path, width: usize, pixels: &[Color]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let height = pixels.len() / width;
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = pixels.iter().flat_map(|&color| color.0).collect();
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub(crate) struct Palettes {
     pub(crate) bg: Palette,
     pub(crate) obj_0: Palette,
     pub(crate) obj_1: Palette,
+    pub(crate) bg_cgb: CgbPalettes,
+    pub(crate) obj_cgb: CgbPalettes,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct Ppu {
+    // Kept on the heap, as moving it around when building the Gameboy in debug builds would
+    // overflow the default thread stack.
+    screen: Vec<Color>,
+    /// Shades of the screen before coloring, as seen by the Super Game Boy. Only drawn on DMG.
+    shades: Vec<palette::Color>,
     #[serde(with = "BigArray")]
-    screen: [Color; Self::LCD_SIZE_X as usize * Self::LCD_SIZE_Y as usize],
-    #[serde(with = "BigArray")]
-    vram: [u8; Self::VRAM_SIZE],
+    vram: [u8; Self::VRAM_SIZE * Self::VRAM_BANK_COUNT],
+    vram_bank: u8,
     #[serde(with = "BigArray")]
     oam: [u8; Self::OAM_SIZE],
     bg_fifo: PixelFifo<BgPixel>,
@@ -86,11 +126,14 @@ pub(crate) struct Ppu {
     window_y: u8,
     window_x: u8,
     line_cycles_count: u8,
+    cgb_mode: CgbMode,
+    oam_priority: bool,
 }
 
 impl Ppu {
     pub(crate) const OAM_SIZE: usize = 0xA0;
     const VRAM_SIZE: usize = 0x2000;
+    const VRAM_BANK_COUNT: usize = 2;
 
     const LCD_SIZE_X: u8 = 160;
     const LCD_SIZE_Y: u8 = 144;
@@ -109,9 +152,12 @@ impl Ppu {
     const OBJ_PALETTE_1: u16 = 0xFF49;
     const WINDOW_Y: u16 = 0xFF4A;
     const WINDOW_X: u16 = 0xFF4B;
-    const UNUSED_START: u16 = 0xFF4C;
-    const UNUSED_END: u16 = 0xFF4E;
     const CGB_VRAM_BANK_SELECT: u16 = 0xFF4F;
+    const CGB_BG_PALETTE_SPEC: u16 = 0xFF68;
+    const CGB_BG_PALETTE_DATA: u16 = 0xFF69;
+    const CGB_OBJ_PALETTE_SPEC: u16 = 0xFF6A;
+    const CGB_OBJ_PALETTE_DATA: u16 = 0xFF6B;
+    const CGB_OBJ_PRIORITY_MODE: u16 = 0xFF6C;
 
     const _WINDOW_X_OFFSET: u8 = 7;
 
@@ -120,11 +166,18 @@ impl Ppu {
 
     const MAX_VISIBLE_SPRITES: usize = 10;
 
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(cgb_mode: CgbMode) -> Self {
         Self {
-            screen: [palette::Color::Black.into();
-                Self::LCD_SIZE_X as usize * Self::LCD_SIZE_Y as usize],
-            vram: [0; Self::VRAM_SIZE],
+            screen: vec![
+                palette::Color::Black.into();
+                Self::LCD_SIZE_X as usize * Self::LCD_SIZE_Y as usize
+            ],
+            shades: vec![
+                palette::Color::Black;
+                Self::LCD_SIZE_X as usize * Self::LCD_SIZE_Y as usize
+            ],
+            vram: [0; Self::VRAM_SIZE * Self::VRAM_BANK_COUNT],
+            vram_bank: 0,
             oam: [0; Self::OAM_SIZE],
             bg_fifo: PixelFifo::new(),
             obj_fifo: PixelFifo::new(),
@@ -147,24 +200,57 @@ impl Ppu {
             window_y: 0,
             window_x: 0,
             line_cycles_count: 0,
+            cgb_mode,
+            oam_priority: cgb_mode == CgbMode::Enabled,
         }
     }
 
-    pub(crate) fn new_post_bootrom() -> Self {
-        let mut ppu = Self::new();
+    pub(crate) fn new_post_bootrom(cgb_mode: CgbMode) -> Self {
+        let mut ppu = Self::new(cgb_mode);
         ppu.lcdc.bg_window_enable = true;
         ppu.lcdc.lcd_enable = true;
+        if cgb_mode == CgbMode::Enabled {
+            // The CGB bootrom initializes all background palettes to white.
+            ppu.palettes.bg_cgb = CgbPalettes::new(0xFF);
+        }
         ppu
     }
 
-    pub(crate) fn tick(&mut self) -> (FlagSet<Interrupt>, DmaRequest) {
+    pub(crate) const fn lcd_enabled(&self) -> bool {
+        self.lcdc.lcd_enable
+    }
+
+    pub(crate) fn set_cgb_mode(&mut self, cgb_mode: CgbMode) {
+        self.cgb_mode = cgb_mode;
+        if cgb_mode != CgbMode::Enabled {
+            self.vram_bank = 0;
+        }
+    }
+
+    const fn vram_address(&self, address: u16) -> usize {
+        address as usize + self.vram_bank as usize * Self::VRAM_SIZE
+    }
+
+    const fn cgb_palettes_accessible(&self) -> bool {
+        match self.stat.mode {
+            Mode::OamSearch | Mode::HBlank | Mode::VBlank => true,
+            Mode::PixelTransfer => false,
+        }
+    }
+
+    pub(crate) fn tick(&mut self) -> PpuTick {
         if !self.lcdc.lcd_enable {
             self.line_y = 0;
             self.stat.mode = Mode::VBlank;
-            return (FlagSet::default(), DmaRequest::None);
+            return PpuTick {
+                interrupts: FlagSet::default(),
+                dma_request: DmaRequest::None,
+                hblank_started: false,
+            };
         }
 
         let mut interrupts = FlagSet::default();
+        let mut hblank_started = false;
 
         match self.stat.mode {
             Mode::OamSearch => {
@@ -197,6 +283,7 @@ impl Ppu {
                         self.window_triggered = false;
 
                         self.stat.mode = Mode::HBlank;
+                        hblank_started = true;
 
                         if self.stat.hblank_interrupt_enabled() {
                             interrupts |= Interrupt::LcdStat;
@@ -232,10 +319,11 @@ impl Ppu {
             }
         }
 
-        (
+        PpuTick {
             interrupts,
-            std::mem::replace(&mut self.dma, DmaRequest::None),
-        )
+            dma_request: std::mem::replace(&mut self.dma, DmaRequest::None),
+            hblank_started,
+        }
     }
 
     fn tick_oam_search(&mut self) {
@@ -279,6 +367,8 @@ impl Ppu {
             &self.vram,
             self.window_triggered,
             self.line_y_window,
+            self.cgb_mode,
+            self.oam_priority,
         );
 
         if pending {
@@ -289,32 +379,56 @@ impl Ppu {
             if self.to_discard_x > 0 {
                 self.to_discard_x -= 1;
             } else {
-                let color = mix_pixels(bg_pixel, self.obj_fifo.pop(), &self.lcdc, &self.palettes);
+                let obj_pixel = self.obj_fifo.pop();
                 if self.x_pos >= 8 {
-                    self.screen[(self.x_pos - 8) as usize
-                        + (self.line_y as usize * Self::LCD_SIZE_X as usize)] = color.into();
+                    let index = (self.x_pos - 8) as usize
+                        + (self.line_y as usize * Self::LCD_SIZE_X as usize);
+                    if self.cgb_mode == CgbMode::Disabled {
+                        let (shade, _) = dmg_shade(bg_pixel, obj_pixel, &self.lcdc, &self.palettes);
+                        self.shades[index] = shade;
+                    }
+                    self.screen[index] = mix_pixels(
+                        bg_pixel,
+                        obj_pixel,
+                        &self.lcdc,
+                        &self.palettes,
+                        self.cgb_mode,
+                    );
                 }
                 self.x_pos += 1;
             }
         }
     }
 
-    pub(crate) const fn screen(
-        &self,
-    ) -> &[Color; Self::LCD_SIZE_X as usize * Self::LCD_SIZE_Y as usize] {
-        &self.screen
+    pub(crate) fn screen(&self) -> &[Color; Self::LCD_SIZE_X as usize * Self::LCD_SIZE_Y as usize] {
+        self.screen.as_slice().try_into().unwrap()
+    }
+
+    pub(crate) fn shades(&self) -> &[palette::Color] {
+        &self.shades
+    }
+
+    pub(crate) const fn vram_bank(&self) -> usize {
+        self.vram_bank as usize
+    }
+
+    /// Both VRAM banks, regardless of the PPU mode.
+    pub(crate) const fn vram(&self) -> &[u8] {
+        &self.vram
     }
 
     pub(crate) const fn read_vram(&self, address: u16) -> u8 {
         match self.stat.mode {
-            Mode::OamSearch | Mode::HBlank | Mode::VBlank => self.vram[address as usize],
+            Mode::OamSearch | Mode::HBlank | Mode::VBlank => self.vram[self.vram_address(address)],
             Mode::PixelTransfer => 0xFF,
         }
     }
 
     pub(crate) fn write_vram(&mut self, address: u16, value: u8) {
         match self.stat.mode {
-            Mode::OamSearch | Mode::HBlank | Mode::VBlank => self.vram[address as usize] = value,
+            Mode::OamSearch | Mode::HBlank | Mode::VBlank => {
+                self.vram[self.vram_address(address)] = value
+            }
             Mode::PixelTransfer => {}
         }
     }
@@ -347,7 +461,25 @@ impl Ppu {
             Self::OBJ_PALETTE_1 => self.palettes.obj_1.value(),
             Self::WINDOW_Y => self.window_y,
             Self::WINDOW_X => self.window_x,
-            Self::UNUSED_START..=Self::UNUSED_END | Self::CGB_VRAM_BANK_SELECT => 0xFF,
+            Self::CGB_VRAM_BANK_SELECT => match self.cgb_mode {
+                CgbMode::Enabled => 0b1111_1110 | self.vram_bank,
+                CgbMode::Disabled | CgbMode::Compatibility => 0xFF,
+            },
+            Self::CGB_BG_PALETTE_SPEC..=Self::CGB_OBJ_PRIORITY_MODE
+                if self.cgb_mode == CgbMode::Disabled =>
+            {
+                0xFF
+            }
+            Self::CGB_BG_PALETTE_SPEC => self.palettes.bg_cgb.spec_value(),
+            Self::CGB_BG_PALETTE_DATA if self.cgb_palettes_accessible() => {
+                self.palettes.bg_cgb.data_value()
+            }
+            Self::CGB_OBJ_PALETTE_SPEC => self.palettes.obj_cgb.spec_value(),
+            Self::CGB_OBJ_PALETTE_DATA if self.cgb_palettes_accessible() => {
+                self.palettes.obj_cgb.data_value()
+            }
+            Self::CGB_BG_PALETTE_DATA | Self::CGB_OBJ_PALETTE_DATA => 0xFF,
+            Self::CGB_OBJ_PRIORITY_MODE => 0b1111_1110 | u8::from(!self.oam_priority),
             _ => panic!("Tried to read ppu register at invalid address"),
         }
     }
@@ -375,7 +507,24 @@ impl Ppu {
             Self::OBJ_PALETTE_1 => self.palettes.obj_1.set_value(value),
             Self::WINDOW_Y => self.window_y = value,
             Self::WINDOW_X => self.window_x = value,
-            Self::UNUSED_START..=Self::UNUSED_END | Self::CGB_VRAM_BANK_SELECT => {}
+            Self::CGB_VRAM_BANK_SELECT => {
+                if self.cgb_mode == CgbMode::Enabled {
+                    self.vram_bank = value & 0b1;
+                }
+            }
+            Self::CGB_BG_PALETTE_SPEC..=Self::CGB_OBJ_PRIORITY_MODE
+                if self.cgb_mode == CgbMode::Disabled => {}
+            Self::CGB_BG_PALETTE_SPEC => self.palettes.bg_cgb.set_spec_value(value),
+            Self::CGB_BG_PALETTE_DATA => {
+                let writable = self.cgb_palettes_accessible();
+                self.palettes.bg_cgb.set_data_value(value, writable);
+            }
+            Self::CGB_OBJ_PALETTE_SPEC => self.palettes.obj_cgb.set_spec_value(value),
+            Self::CGB_OBJ_PALETTE_DATA => {
+                let writable = self.cgb_palettes_accessible();
+                self.palettes.obj_cgb.set_data_value(value, writable);
+            }
+            Self::CGB_OBJ_PRIORITY_MODE => self.oam_priority = value & 0b1 == 0,
             invalid_address => {
                 panic!("Tried to write ppu register at invalid address 0x{invalid_address:X}")
             }
@@ -23,6 +23,8 @@ pub(crate) struct Attributes {
     pub(crate) flip_y: bool,
     pub(crate) priority: Priority,
     pub(crate) palette: Palette,
+    pub(crate) bank: u8,
+    pub(crate) cgb_palette: u8,
 }
 
 impl Attributes {
@@ -46,6 +48,9 @@ impl Attributes {
             Palette::ObjP1
         };
 
+        let bank = (values[3] >> 3) & 0b1;
+        let cgb_palette = values[3] & 0b111;
+
         Self {
             x,
             y,
@@ -54,6 +59,8 @@ impl Attributes {
             flip_y,
             priority,
             palette,
+            bank,
+            cgb_palette,
         }
     }
 }
@@ -3,9 +3,30 @@ use serde::{Deserialize, Serialize};
 use super::{
     lcd_control::{LcdControl, SpriteSize, TileDataAddressing},
     obj::{self, Attributes, Priority},
-    palette, Palettes,
+    palette, CgbMode, Color, Palettes,
 };
 
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
+struct TileAttributes {
+    priority: bool,
+    flip_y: bool,
+    flip_x: bool,
+    bank: u8,
+    palette: u8,
+}
+
+impl TileAttributes {
+    const fn parse(value: u8) -> Self {
+        Self {
+            priority: value & (1 << 7) != 0,
+            flip_y: value & (1 << 6) != 0,
+            flip_x: value & (1 << 5) != 0,
+            bank: (value >> 3) & 0b1,
+            palette: value & 0b111,
+        }
+    }
+}
+
 // TODO: simplify this mess
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 enum Action {
@@ -24,10 +45,12 @@ enum Action {
     BgReadTile,
     BgReadDataL {
         tile_index: u8,
+        attributes: TileAttributes,
     },
     BgReadDataH {
         data_address: u16,
         data_l: u8,
+        attributes: TileAttributes,
     },
     BgWait {
         pixels: [BgPixel; 8],
@@ -54,6 +77,7 @@ pub(crate) struct Fetcher {
 impl Fetcher {
     const TILE_MAP_WIDTH: u16 = 32;
     const SPRITE_HEIGHT: u16 = 8;
+    const VRAM_BANK_SIZE: u16 = 0x2000;
 
     fn unpack_indices(data_l: u8, data_h: u8) -> [u8; 8] {
         let mut indices = [0; 8];
@@ -106,7 +130,10 @@ impl Fetcher {
         vram: &[u8],
         window_triggered: bool,
         line_y_window: u8,
+        cgb_mode: CgbMode,
+        oam_priority: bool,
     ) -> bool {
+        let cgb_enabled = cgb_mode == CgbMode::Enabled;
         let next_obj = |visible_objs: &[Attributes], drawn_objs: &[bool]| {
             visible_objs
                 .iter()
@@ -148,8 +175,11 @@ impl Fetcher {
                     SpriteSize::S8x8 => obj.tile_index,
                     SpriteSize::S8x16 => obj.tile_index & !0b1,
                 };
-                let data_address =
+                let mut data_address =
                     TileDataAddressing::Unsigned.address_from_index_obj(index, line, lcdc.obj_size);
+                if cgb_enabled {
+                    data_address += obj.bank as u16 * Self::VRAM_BANK_SIZE;
+                }
                 let mut data_l = vram[data_address as usize];
                 if obj.flip_x {
                     data_l = data_l.reverse_bits();
@@ -175,9 +205,11 @@ impl Fetcher {
                 let pixels = indices.map(|index| ObjPixel {
                     index,
                     palette: obj.palette,
+                    cgb_palette: obj.cgb_palette,
                     priority: obj.priority,
+                    obj_index: attr_index as u8,
                 });
-                obj_fifo.push_line(&pixels);
+                obj_fifo.push_line(&pixels, oam_priority);
                 self.drawn_objs[attr_index] = true;
                 if next_obj(visible_objs, &self.drawn_objs).is_some() {
                     Action::ObjReadAttr
@@ -190,7 +222,7 @@ impl Fetcher {
             Action::BgReadStartTile => Action::BgReadStartDataL,
             Action::BgReadStartDataL => Action::BgReadStartDataH,
             Action::BgReadStartDataH => {
-                let pixels = [BgPixel { index: 0 }; 8];
+                let pixels = [BgPixel::default(); 8];
                 if bg_fifo.push_line(&pixels) {
                     if next_obj(visible_objs, &self.drawn_objs).is_some() {
                         Action::ObjReadAttr
@@ -209,29 +241,54 @@ impl Fetcher {
                 };
                 let tile_map_index =
                     (self.tile_map_index as u16 + scroll_offset as u16) % Self::TILE_MAP_WIDTH;
+                let map_address =
+                    tile_map.base_address() + bg_w_line / 8 * Self::TILE_MAP_WIDTH + tile_map_index;
+                let attributes = if cgb_enabled {
+                    TileAttributes::parse(vram[(map_address + Self::VRAM_BANK_SIZE) as usize])
+                } else {
+                    TileAttributes::default()
+                };
 
                 Action::BgReadDataL {
-                    tile_index: vram[(tile_map.base_address()
-                        + bg_w_line / 8 * Self::TILE_MAP_WIDTH
-                        + tile_map_index) as usize],
+                    tile_index: vram[map_address as usize],
+                    attributes,
                 }
             }
-            Action::BgReadDataL { tile_index } => {
+            Action::BgReadDataL {
+                tile_index,
+                attributes,
+            } => {
+                let line = if attributes.flip_y {
+                    7 - bg_w_line % 8
+                } else {
+                    bg_w_line
+                };
                 let data_address = lcdc
                     .bg_window_addressing
-                    .address_from_index_bg(tile_index, bg_w_line);
+                    .address_from_index_bg(tile_index, line)
+                    + attributes.bank as u16 * Self::VRAM_BANK_SIZE;
                 Action::BgReadDataH {
                     data_address,
                     data_l: vram[data_address as usize],
+                    attributes,
                 }
             }
             Action::BgReadDataH {
                 data_address,
-                data_l,
+                mut data_l,
+                attributes,
             } => {
-                let data_h = vram[data_address as usize + 1];
+                let mut data_h = vram[data_address as usize + 1];
+                if attributes.flip_x {
+                    data_l = data_l.reverse_bits();
+                    data_h = data_h.reverse_bits();
+                }
                 let indices = Self::unpack_indices(data_l, data_h);
-                let pixels = indices.map(|index| BgPixel { index });
+                let pixels = indices.map(|index| BgPixel {
+                    index,
+                    palette: attributes.palette,
+                    priority: attributes.priority,
+                });
                 if bg_fifo.push_line(&pixels) {
                     self.tile_map_index = (self.tile_map_index + 1) % Self::TILE_MAP_WIDTH as u8;
                     Action::BgReadTile
@@ -256,13 +313,17 @@ impl Fetcher {
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
 pub(crate) struct BgPixel {
     pub(crate) index: u8,
+    pub(crate) palette: u8,
+    pub(crate) priority: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
 pub(crate) struct ObjPixel {
     pub(crate) index: u8,
     pub(crate) palette: obj::Palette,
+    pub(crate) cgb_palette: u8,
     pub(crate) priority: obj::Priority,
+    pub(crate) obj_index: u8,
 }
 
 pub(crate) fn mix_pixels(
@@ -270,24 +331,65 @@ pub(crate) fn mix_pixels(
     obj_pixel: Option<ObjPixel>,
     lcdc: &LcdControl,
     palettes: &Palettes,
-) -> palette::Color {
+    cgb_mode: CgbMode,
+) -> Color {
+    if cgb_mode == CgbMode::Enabled {
+        if let Some(obj_pixel) = obj_pixel {
+            // On CGB, the bg/window enable bit acts as a master priority toggle instead.
+            let bg_priority = lcdc.bg_window_enable
+                && bg_pixel.index != 0
+                && (bg_pixel.priority || obj_pixel.priority == Priority::BehindNonZeroBg);
+            if lcdc.obj_enable && obj_pixel.index != 0 && !bg_priority {
+                return palettes
+                    .obj_cgb
+                    .color(obj_pixel.cgb_palette, obj_pixel.index);
+            }
+        }
+
+        return palettes.bg_cgb.color(bg_pixel.palette, bg_pixel.index);
+    }
+
+    let (color, layer) = dmg_shade(bg_pixel, obj_pixel, lcdc, palettes);
+    match (cgb_mode, layer) {
+        (CgbMode::Compatibility, Layer::Bg) => palettes.bg_cgb.color(0, color as u8),
+        (CgbMode::Compatibility, Layer::Obj(palette)) => {
+            palettes.obj_cgb.color(palette, color as u8)
+        }
+        (CgbMode::Disabled | CgbMode::Enabled, _) => color.into(),
+    }
+}
+
+/// The layer a DMG pixel comes from, with the index of the object palette used.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Layer {
+    Bg,
+    Obj(u8),
+}
+
+pub(crate) fn dmg_shade(
+    bg_pixel: BgPixel,
+    obj_pixel: Option<ObjPixel>,
+    lcdc: &LcdControl,
+    palettes: &Palettes,
+) -> (palette::Color, Layer) {
     if let Some(obj_pixel) = obj_pixel {
         if lcdc.obj_enable
             && obj_pixel.index != 0
             && (obj_pixel.priority != Priority::BehindNonZeroBg || bg_pixel.index == 0)
         {
             return match obj_pixel.palette {
-                obj::Palette::ObjP0 => palettes.obj_0[obj_pixel.index],
-                obj::Palette::ObjP1 => palettes.obj_1[obj_pixel.index],
+                obj::Palette::ObjP0 => (palettes.obj_0[obj_pixel.index], Layer::Obj(0)),
+                obj::Palette::ObjP1 => (palettes.obj_1[obj_pixel.index], Layer::Obj(1)),
             };
         }
     }
 
-    if lcdc.bg_window_enable {
+    let color = if lcdc.bg_window_enable {
         palettes.bg[bg_pixel.index]
     } else {
         palette::Color::White
-    }
+    };
+    (color, Layer::Bg)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -302,20 +404,19 @@ impl<T> PixelFifo<T> {
 }
 
 impl PixelFifo<ObjPixel> {
-    fn push_line(&mut self, pixels: &[ObjPixel; 8]) {
+    fn push_line(&mut self, pixels: &[ObjPixel; 8], oam_priority: bool) {
         // Fill available Fifo space with transparent pixels
         for i in self.size..Self::SIZE {
-            self.fifo[(self.start + i) % Self::SIZE] = ObjPixel {
-                index: 0,
-                palette: obj::Palette::ObjP0,
-                priority: Priority::BehindNonZeroBg,
-            };
+            self.fifo[(self.start + i) % Self::SIZE] = ObjPixel::default();
         }
         self.size = Self::SIZE;
 
         for (i, new_pixel) in pixels.iter().copied().enumerate() {
             let pixel = &mut self.fifo[(self.start + i) % Self::SIZE];
-            if pixel.index == 0 && new_pixel.index > 0 {
+            // With OAM priority (CGB), objects earlier in OAM win regardless of their x position.
+            let replace =
+                pixel.index == 0 || (oam_priority && new_pixel.obj_index < pixel.obj_index);
+            if replace && new_pixel.index > 0 {
                 *pixel = new_pixel;
             }
         }
@@ -0,0 +1,248 @@
+mod printer;
+mod tcp;
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+pub use printer::{PrintedImage, Printer, Prints};
+pub use tcp::TcpLink;
+
+/// A device plugged in the link port.
+///
+/// Transfers happen a byte at a time: the side driving the clock calls [`SerialLink::exchange`]
+/// once its 8 bits are shifted, while a side waiting on an external clock polls
+/// [`SerialLink::poll_external`].
+pub trait SerialLink: fmt::Debug + Send {
+    /// Sends a byte as the clock master and returns the byte shifted in from the other end.
+    fn exchange(&mut self, value: u8) -> u8;
+
+    /// Publishes the byte and readiness presented to a remote clock master.
+    fn set_slave_state(&mut self, _value: u8, _ready: bool) {}
+
+    /// Returns the byte shifted in by a remote clock master, if a transfer completed.
+    fn poll_external(&mut self) -> Option<u8> {
+        None
+    }
+
+    /// Called after each instruction with the number of elapsed cycles, for links that need to
+    /// stay in time with a remote side.
+    fn advance(&mut self, _cycles: u64) {}
+}
+
+/// No device connected, reads return 0xFF.
+#[derive(Debug, Default)]
+pub struct NullLink;
+
+impl SerialLink for NullLink {
+    fn exchange(&mut self, _value: u8) -> u8 {
+        0xFF
+    }
+}
+
+/// Output wired back to the input, every sent byte is received back.
+#[derive(Debug, Default)]
+pub struct LoopbackLink;
+
+impl SerialLink for LoopbackLink {
+    fn exchange(&mut self, value: u8) -> u8 {
+        value
+    }
+}
+
+/// Records every byte sent with nothing plugged on the other end, clones share the same output.
+#[derive(Debug, Default, Clone)]
+pub struct CaptureLink {
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CaptureLink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn output(&self) -> Vec<u8> {
+        self.output.lock().unwrap().clone()
+    }
+}
+
+impl SerialLink for CaptureLink {
+    fn exchange(&mut self, value: u8) -> u8 {
+        self.output.lock().unwrap().push(value);
+        0xFF
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct CableSide {
+    value: u8,
+    ready: bool,
+    incoming: Option<u8>,
+}
+
+/// One end of a cable linking two in-process Game Boys.
+#[derive(Debug)]
+pub struct LinkCable {
+    sides: Arc<Mutex<[CableSide; 2]>>,
+    side: usize,
+}
+
+impl LinkCable {
+    pub fn pair() -> (Self, Self) {
+        let sides = Arc::new(Mutex::new([CableSide::default(); 2]));
+        (
+            Self {
+                sides: sides.clone(),
+                side: 0,
+            },
+            Self { sides, side: 1 },
+        )
+    }
+}
+
+impl SerialLink for LinkCable {
+    fn exchange(&mut self, value: u8) -> u8 {
+        let mut sides = self.sides.lock().unwrap();
+        let other = &mut sides[1 - self.side];
+        if other.ready {
+            other.ready = false;
+            other.incoming = Some(value);
+            other.value
+        } else {
+            0xFF
+        }
+    }
+
+    fn set_slave_state(&mut self, value: u8, ready: bool) {
+        let side = &mut self.sides.lock().unwrap()[self.side];
+        side.value = value;
+        side.ready = ready;
+    }
+
+    fn poll_external(&mut self) -> Option<u8> {
+        self.sides.lock().unwrap()[self.side].incoming.take()
+    }
+}
+
+fn default_link() -> Box<dyn SerialLink> {
+    Box::new(NullLink)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum Clock {
+    External = 0,
+    Internal = 1,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Serial {
+    data: u8,
+    transfer_enabled: bool,
+    fast_clock: bool,
+    clock: Clock,
+    bits: u8,
+    cgb: bool,
+    #[serde(skip, default = "default_link")]
+    pub(crate) link: Box<dyn SerialLink>,
+}
+
+impl Serial {
+    pub(crate) const TRANSFER_ADDRESS: u16 = 0xFF01;
+    pub(crate) const CONTROL_ADDRESS: u16 = 0xFF02;
+
+    pub(crate) fn new(cgb: bool) -> Self {
+        Self {
+            data: 0,
+            transfer_enabled: false,
+            fast_clock: false,
+            clock: Clock::External,
+            bits: 0,
+            cgb,
+            link: default_link(),
+        }
+    }
+
+    // The internal clock runs at 8192Hz (262144Hz in CGB fast mode) and is derived from DIV.
+    const fn clock_bit(&self) -> u16 {
+        if self.fast_clock {
+            1 << 3
+        } else {
+            1 << 8
+        }
+    }
+
+    /// Returns true when a transfer completed and the serial interrupt must be raised.
+    pub(crate) fn tick(&mut self, old_divider: u16, divider: u16) -> bool {
+        if !self.transfer_enabled {
+            return false;
+        }
+
+        match self.clock {
+            Clock::Internal => {
+                let clock_bit = self.clock_bit();
+                if old_divider & clock_bit == 0 || divider & clock_bit != 0 {
+                    return false;
+                }
+                self.bits += 1;
+                if self.bits < 8 {
+                    return false;
+                }
+                self.data = self.link.exchange(self.data);
+            }
+            Clock::External => match self.link.poll_external() {
+                Some(value) => self.data = value,
+                None => return false,
+            },
+        }
+
+        self.bits = 0;
+        self.transfer_enabled = false;
+        self.update_slave_state();
+        true
+    }
+
+    pub(crate) fn set_link(&mut self, link: Box<dyn SerialLink>) {
+        self.link = link;
+        self.update_slave_state();
+    }
+
+    fn update_slave_state(&mut self) {
+        let ready = self.transfer_enabled && self.clock == Clock::External;
+        self.link.set_slave_state(self.data, ready);
+    }
+
+    pub(crate) fn read(&self, address: u16) -> u8 {
+        match address {
+            Self::TRANSFER_ADDRESS => self.data,
+            Self::CONTROL_ADDRESS => {
+                let fast_clock = if self.cgb { self.fast_clock } else { true };
+                (self.transfer_enabled as u8) << 7
+                    | 0b0111_1100
+                    | (fast_clock as u8) << 1
+                    | self.clock as u8
+            }
+            _ => panic!("Tried to read serial register out of range"),
+        }
+    }
+
+    pub(crate) fn write(&mut self, address: u16, value: u8) {
+        match address {
+            Self::TRANSFER_ADDRESS => self.data = value,
+            Self::CONTROL_ADDRESS => {
+                self.transfer_enabled = value & 0b1000_0000 != 0;
+                self.fast_clock = self.cgb && value & 0b10 != 0;
+                self.clock = if value & 0b1 == 0 {
+                    Clock::External
+                } else {
+                    Clock::Internal
+                };
+                self.bits = 0;
+            }
+            _ => panic!("Tried to write serial register out of range"),
+        }
+        self.update_slave_state();
+    }
+}
@@ -0,0 +1,366 @@
+use std::{
+    io, mem,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use super::SerialLink;
+use crate::gameboy::ppu::{palette::Palette, write_png, Color};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Magic0,
+    Magic1,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+#[derive(Debug, Default)]
+struct Packet {
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+}
+
+impl Packet {
+    fn computed_checksum(&self) -> u16 {
+        let [length_low, length_high] = self.length.to_le_bytes();
+        [self.command, self.compressed as u8, length_low, length_high]
+            .iter()
+            .chain(&self.data)
+            .fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16))
+    }
+
+    fn decompressed_data(&self) -> Vec<u8> {
+        if !self.compressed {
+            return self.data.clone();
+        }
+
+        let mut data = vec![];
+        let mut bytes = self.data.iter().copied();
+        while let Some(control) = bytes.next() {
+            if control & 0b1000_0000 != 0 {
+                let Some(value) = bytes.next() else {
+                    break;
+                };
+                let count = (control & 0b0111_1111) as usize + 2;
+                data.extend(std::iter::repeat(value).take(count));
+            } else {
+                data.extend(bytes.by_ref().take(control as usize + 1));
+            }
+        }
+        data
+    }
+}
+
+/// An image fed out of the printer, `Printer::WIDTH` pixels wide.
+#[derive(Debug, Clone)]
+pub struct PrintedImage {
+    pixels: Vec<Color>,
+}
+
+impl PrintedImage {
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_png(path, Printer::WIDTH, &self.pixels)
+    }
+}
+
+/// The images fed out of a printer, clones share the same queue.
+#[derive(Debug, Default, Clone)]
+pub struct Prints {
+    images: Arc<Mutex<Vec<PrintedImage>>>,
+}
+
+impl Prints {
+    /// Returns the images printed since the last call.
+    pub fn take(&self) -> Vec<PrintedImage> {
+        mem::take(&mut *self.images.lock().unwrap())
+    }
+}
+
+/// The Game Boy Printer, queuing each printed image once it is fed out.
+#[derive(Debug)]
+pub struct Printer {
+    prints: Prints,
+    state: State,
+    packet: Packet,
+    status: u8,
+    /// Tile data received since the last print.
+    buffer: Vec<u8>,
+    /// Printed lines not yet followed by a feed, as some games print an image in several parts.
+    image: Vec<Color>,
+    busy_polls: u8,
+}
+
+impl Printer {
+    const MAGIC_0: u8 = 0x88;
+    const MAGIC_1: u8 = 0x33;
+    const ALIVE: u8 = 0x81;
+
+    const COMMAND_INIT: u8 = 0x01;
+    const COMMAND_PRINT: u8 = 0x02;
+    const COMMAND_DATA: u8 = 0x04;
+    const COMMAND_BREAK: u8 = 0x08;
+
+    const STATUS_CHECKSUM_ERROR: u8 = 0b0000_0001;
+    const STATUS_PRINTING: u8 = 0b0000_0010;
+    const STATUS_FULL: u8 = 0b0000_0100;
+    const STATUS_UNPROCESSED: u8 = 0b0000_1000;
+
+    pub const WIDTH: usize = 160;
+    /// Each data packet holds two rows of 20 tiles.
+    const BAND_SIZE: usize = 640;
+    const BAND_HEIGHT: usize = 16;
+    const MAX_BANDS: usize = 9;
+    /// Number of status requests during which a print is reported as ongoing.
+    const PRINT_POLLS: u8 = 4;
+
+    pub fn new() -> Self {
+        Self {
+            prints: Prints::default(),
+            state: State::Magic0,
+            packet: Packet::default(),
+            status: 0,
+            buffer: vec![],
+            image: vec![],
+            busy_polls: 0,
+        }
+    }
+
+    /// The queue receiving the printed images, still available once the printer is plugged.
+    pub fn prints(&self) -> Prints {
+        self.prints.clone()
+    }
+
+    fn status(&mut self) -> u8 {
+        let mut status = self.status;
+        if self.busy_polls > 0 {
+            self.busy_polls -= 1;
+            status |= Self::STATUS_PRINTING;
+        }
+        if self.buffer.len() >= Self::BAND_SIZE * Self::MAX_BANDS {
+            status |= Self::STATUS_FULL;
+        }
+        if !self.buffer.is_empty() {
+            status |= Self::STATUS_UNPROCESSED;
+        }
+        status
+    }
+
+    fn process_packet(&mut self) {
+        let packet = mem::take(&mut self.packet);
+        if packet.computed_checksum() != packet.checksum {
+            self.status |= Self::STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !Self::STATUS_CHECKSUM_ERROR;
+
+        match packet.command {
+            Self::COMMAND_INIT => {
+                self.buffer.clear();
+                self.busy_polls = 0;
+                self.status = 0;
+            }
+            Self::COMMAND_DATA => {
+                let data = packet.decompressed_data();
+                let capacity = Self::BAND_SIZE * Self::MAX_BANDS - self.buffer.len();
+                self.buffer
+                    .extend_from_slice(&data[..usize::min(data.len(), capacity)]);
+            }
+            Self::COMMAND_PRINT if packet.data.len() >= 4 => {
+                let sheets = packet.data[0];
+                let margin_after = packet.data[1] & 0x0F;
+                let mut palette = Palette::default();
+                palette.set_value(packet.data[2]);
+                if sheets > 0 {
+                    self.print(&palette);
+                }
+                self.buffer.clear();
+                self.busy_polls = Self::PRINT_POLLS;
+                if margin_after != 0 {
+                    self.feed();
+                }
+            }
+            Self::COMMAND_BREAK => {
+                self.buffer.clear();
+                self.busy_polls = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, palette: &Palette) {
+        let height = self.buffer.len() / Self::BAND_SIZE * Self::BAND_HEIGHT;
+        for y in 0..height {
+            let band = &self.buffer[y / Self::BAND_HEIGHT * Self::BAND_SIZE..];
+            let y = y % Self::BAND_HEIGHT;
+            for x in 0..Self::WIDTH {
+                let tile = (y / 8) * (Self::WIDTH / 8) + x / 8;
+                let offset = tile * 16 + (y % 8) * 2;
+                let bit = 7 - x % 8;
+                let index = (band[offset] >> bit) & 0b1 | ((band[offset + 1] >> bit) & 0b1) << 1;
+                self.image.push(palette[index].into());
+            }
+        }
+    }
+
+    /// Ends the current image and queues it.
+    fn feed(&mut self) {
+        if self.image.is_empty() {
+            return;
+        }
+        let pixels = mem::take(&mut self.image);
+        self.prints
+            .images
+            .lock()
+            .unwrap()
+            .push(PrintedImage { pixels });
+    }
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialLink for Printer {
+    fn exchange(&mut self, value: u8) -> u8 {
+        let mut response = 0x00;
+        self.state = match self.state {
+            State::Magic0 if value == Self::MAGIC_0 => State::Magic1,
+            State::Magic0 => State::Magic0,
+            State::Magic1 if value == Self::MAGIC_1 => State::Command,
+            State::Magic1 => State::Magic0,
+            State::Command => {
+                self.packet.command = value;
+                State::Compression
+            }
+            State::Compression => {
+                self.packet.compressed = value & 0b1 != 0;
+                State::LengthLow
+            }
+            State::LengthLow => {
+                self.packet.length = value as u16;
+                State::LengthHigh
+            }
+            State::LengthHigh => {
+                self.packet.length |= (value as u16) << 8;
+                if self.packet.length == 0 {
+                    State::ChecksumLow
+                } else {
+                    State::Data
+                }
+            }
+            State::Data => {
+                self.packet.data.push(value);
+                if self.packet.data.len() == self.packet.length as usize {
+                    State::ChecksumLow
+                } else {
+                    State::Data
+                }
+            }
+            State::ChecksumLow => {
+                self.packet.checksum = value as u16;
+                State::ChecksumHigh
+            }
+            State::ChecksumHigh => {
+                self.packet.checksum |= (value as u16) << 8;
+                self.process_packet();
+                State::Alive
+            }
+            State::Alive => {
+                response = Self::ALIVE;
+                State::Status
+            }
+            State::Status => {
+                response = self.status();
+                State::Magic0
+            }
+        };
+        response
+    }
+}
+
+impl Drop for Printer {
+    fn drop(&mut self) {
+        self.feed();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_packet(printer: &mut Printer, command: u8, data: &[u8]) -> u8 {
+        let mut packet = Packet {
+            command,
+            compressed: false,
+            length: data.len() as u16,
+            data: data.to_vec(),
+            checksum: 0,
+        };
+        packet.checksum = packet.computed_checksum();
+        let [length_low, length_high] = packet.length.to_le_bytes();
+        let [checksum_low, checksum_high] = packet.checksum.to_le_bytes();
+        let header = [
+            Printer::MAGIC_0,
+            Printer::MAGIC_1,
+            command,
+            0,
+            length_low,
+            length_high,
+        ];
+        for byte in header.into_iter().chain(data.iter().copied()) {
+            printer.exchange(byte);
+        }
+        printer.exchange(checksum_low);
+        printer.exchange(checksum_high);
+        assert_eq!(printer.exchange(0), Printer::ALIVE);
+        printer.exchange(0)
+    }
+
+    #[test]
+    fn prints_are_queued_once_fed() {
+        let mut printer = Printer::new();
+        let prints = printer.prints();
+        send_packet(&mut printer, Printer::COMMAND_INIT, &[]);
+        // A band with the first tile row of each tile set to color 3.
+        let band: Vec<u8> = (0..Printer::BAND_SIZE)
+            .map(|i| if i % 16 < 2 { 0xFF } else { 0x00 })
+            .collect();
+        send_packet(&mut printer, Printer::COMMAND_DATA, &band);
+
+        // Without a margin, the image is kept for the next print.
+        send_packet(&mut printer, Printer::COMMAND_PRINT, &[1, 0x00, 0xE4, 0x40]);
+        assert!(prints.take().is_empty());
+        send_packet(&mut printer, Printer::COMMAND_DATA, &band);
+        let status = send_packet(&mut printer, Printer::COMMAND_PRINT, &[1, 0x03, 0xE4, 0x40]);
+        assert_ne!(status & Printer::STATUS_PRINTING, 0);
+
+        let images = prints.take();
+        assert_eq!(images.len(), 1);
+        let pixels = images[0].pixels();
+        assert_eq!(pixels.len(), Printer::WIDTH * Printer::BAND_HEIGHT * 2);
+        let mut palette = Palette::default();
+        palette.set_value(0xE4);
+        let (black, white): (Color, Color) = (palette[3].into(), palette[0].into());
+        assert_eq!(pixels[0], black);
+        assert_eq!(pixels[Printer::WIDTH], white);
+        assert_eq!(pixels[Printer::WIDTH * Printer::BAND_HEIGHT], black);
+        assert!(prints.take().is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    io::{self, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+use super::SerialLink;
+use crate::gameboy::Gameboy;
+
+#[derive(Debug, Clone, Copy)]
+enum Message {
+    /// The sender emulated at least this many cycles.
+    Clock(u64),
+    /// The sender shifted a byte out as the clock master and waits for a reply.
+    Transfer(u8),
+    /// The byte shifted back in reply to a transfer, 0xFF if the receiver was not ready.
+    Reply(u8),
+}
+
+impl Message {
+    const CLOCK: u8 = 0;
+    const TRANSFER: u8 = 1;
+    const REPLY: u8 = 2;
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut tag = [0; 1];
+        reader.read_exact(&mut tag)?;
+        match tag[0] {
+            Self::CLOCK => {
+                let mut cycles = [0; 8];
+                reader.read_exact(&mut cycles)?;
+                Ok(Self::Clock(u64::from_le_bytes(cycles)))
+            }
+            Self::TRANSFER | Self::REPLY => {
+                let mut value = [0; 1];
+                reader.read_exact(&mut value)?;
+                if tag[0] == Self::TRANSFER {
+                    Ok(Self::Transfer(value[0]))
+                } else {
+                    Ok(Self::Reply(value[0]))
+                }
+            }
+            tag => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown link message tag {tag}"),
+            )),
+        }
+    }
+
+    /// Messages are written in a single call so that both threads can send on the same stream.
+    fn write(self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::Clock(cycles) => {
+                let mut message = [Self::CLOCK; 9];
+                message[1..].copy_from_slice(&cycles.to_le_bytes());
+                writer.write_all(&message)
+            }
+            Self::Transfer(value) => writer.write_all(&[Self::TRANSFER, value]),
+            Self::Reply(value) => writer.write_all(&[Self::REPLY, value]),
+        }
+    }
+}
+
+/// State shared with the thread receiving messages.
+#[derive(Debug)]
+struct Shared {
+    connected: bool,
+    remote_cycles: u64,
+    /// The reply to the last transfer sent.
+    reply: Option<u8>,
+    /// The byte and readiness presented to the remote clock master.
+    value: u8,
+    ready: bool,
+    /// The byte shifted in by the remote clock master.
+    incoming: Option<u8>,
+}
+
+/// A link cable to another emulator over TCP.
+///
+/// Each byte transfer is a request answered by the remote side with the byte it presents at that
+/// time, so that a transfer is seen by both sides at once. Both sides also exchange their cycle
+/// count and the one running ahead blocks until the other is back within
+/// [`TcpLink::SYNC_WINDOW`] cycles.
+#[derive(Debug)]
+pub struct TcpLink {
+    stream: Arc<Mutex<TcpStream>>,
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+    cycles: u64,
+    sent_cycles: u64,
+}
+
+impl TcpLink {
+    /// Maximum number of cycles a side can run ahead of the other.
+    pub const SYNC_WINDOW: u64 = Gameboy::CYCLES_PER_SECOND / 60;
+    const SYNC_INTERVAL: u64 = Self::SYNC_WINDOW / 8;
+
+    /// Waits for a single remote emulator to connect.
+    pub fn listen(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::accept(&TcpListener::bind(address)?)
+    }
+
+    /// Waits for a single remote emulator to connect on an already bound listener.
+    pub fn accept(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::new(TcpStream::connect(address)?)
+    }
+
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let shared = Arc::new((
+            Mutex::new(Shared {
+                connected: true,
+                remote_cycles: 0,
+                reply: None,
+                value: 0xFF,
+                ready: false,
+                incoming: None,
+            }),
+            Condvar::new(),
+        ));
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let stream = Arc::new(Mutex::new(stream));
+        let thread_stream = stream.clone();
+        let thread_shared = shared.clone();
+        thread::spawn(move || {
+            let (shared, condvar) = &*thread_shared;
+            loop {
+                let message = Message::read(&mut reader);
+                let mut shared = shared.lock().unwrap();
+                let reply = match message {
+                    Ok(Message::Clock(cycles)) => {
+                        shared.remote_cycles = cycles;
+                        None
+                    }
+                    // Transfers are answered right away so that the remote master does not wait
+                    // on this side's emulation.
+                    Ok(Message::Transfer(value)) if shared.ready => {
+                        shared.ready = false;
+                        shared.incoming = Some(value);
+                        Some(shared.value)
+                    }
+                    Ok(Message::Transfer(_)) => Some(0xFF),
+                    Ok(Message::Reply(value)) => {
+                        shared.reply = Some(value);
+                        None
+                    }
+                    Err(_) => {
+                        shared.connected = false;
+                        condvar.notify_all();
+                        return;
+                    }
+                };
+                condvar.notify_all();
+                drop(shared);
+
+                if let Some(reply) = reply {
+                    if Message::Reply(reply)
+                        .write(&mut *thread_stream.lock().unwrap())
+                        .is_err()
+                    {
+                        thread_shared.0.lock().unwrap().connected = false;
+                        condvar.notify_all();
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            stream,
+            shared,
+            cycles: 0,
+            sent_cycles: 0,
+        })
+    }
+
+    fn send(&mut self, message: Message) {
+        if message.write(&mut *self.stream.lock().unwrap()).is_err() {
+            self.shared.0.lock().unwrap().connected = false;
+        }
+    }
+}
+
+impl SerialLink for TcpLink {
+    fn exchange(&mut self, value: u8) -> u8 {
+        self.shared.0.lock().unwrap().reply = None;
+        self.send(Message::Transfer(value));
+
+        let (shared, condvar) = &*self.shared;
+        let mut shared = condvar
+            .wait_while(shared.lock().unwrap(), |shared| {
+                shared.connected && shared.reply.is_none()
+            })
+            .unwrap();
+        shared.reply.take().unwrap_or(0xFF)
+    }
+
+    fn set_slave_state(&mut self, value: u8, ready: bool) {
+        let mut shared = self.shared.0.lock().unwrap();
+        shared.value = value;
+        shared.ready = ready;
+        // A byte shifted in after this side stopped waiting for it is not received.
+        if !ready {
+            shared.incoming = None;
+        }
+    }
+
+    fn poll_external(&mut self) -> Option<u8> {
+        self.shared.0.lock().unwrap().incoming.take()
+    }
+
+    fn advance(&mut self, cycles: u64) {
+        self.cycles += cycles;
+        if self.cycles - self.sent_cycles < Self::SYNC_INTERVAL {
+            return;
+        }
+
+        self.sent_cycles = self.cycles;
+        self.send(Message::Clock(self.cycles));
+
+        let (shared, condvar) = &*self.shared;
+        let _shared = condvar
+            .wait_while(shared.lock().unwrap(), |shared| {
+                shared.connected && self.cycles > shared.remote_cycles + Self::SYNC_WINDOW
+            })
+            .unwrap();
+    }
+}
+
+impl Drop for TcpLink {
+    fn drop(&mut self) {
+        let _ = self.stream.lock().unwrap().shutdown(Shutdown::Both);
+    }
+}
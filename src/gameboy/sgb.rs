@@ -0,0 +1,505 @@
+use std::convert::TryInto;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    io::Buttons,
+    ppu::{palette, Color},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum Mask {
+    None,
+    /// Keep showing the last frame.
+    Freeze,
+    Black,
+    /// Fill the screen with the shared color 0.
+    Color0,
+}
+
+/// Data sent by displaying it, captured from the next frame.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+enum Transfer {
+    SystemPalettes,
+    BorderTiles { high: bool },
+    Border,
+    AttributeFiles,
+}
+
+/// The Super Game Boy, coloring the screen and drawing a border around it.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Sgb {
+    /// Packets of the command being received.
+    command: Vec<u8>,
+    /// RGB555 colors, color 0 is shared by all palettes.
+    palettes: [[u16; 4]; 4],
+    system_palettes: Vec<u8>,
+    /// Palette used by each tile of the screen.
+    attributes: Vec<u8>,
+    attribute_files: Vec<u8>,
+    /// SNES 4 bits per pixel tiles.
+    border_tiles: Vec<u8>,
+    /// Border tile map, followed by the border palettes.
+    border: Vec<u8>,
+    mask: Mask,
+    transfer: Option<Transfer>,
+    screen: Vec<Color>,
+}
+
+impl Sgb {
+    pub(crate) const SCREEN_WIDTH: usize = 256;
+    pub(crate) const SCREEN_HEIGHT: usize = 224;
+    const GAME_X: usize = 48;
+    const GAME_Y: usize = 40;
+    const GAME_WIDTH: usize = 160;
+    const GAME_HEIGHT: usize = 144;
+    const TILES_X: usize = Self::GAME_WIDTH / 8;
+    const TILES_Y: usize = Self::GAME_HEIGHT / 8;
+
+    const PACKET_SIZE: usize = 16;
+    const TRANSFER_SIZE: usize = 0x1000;
+    const ATTRIBUTE_FILE_SIZE: usize = 90;
+    const ATTRIBUTE_FILE_COUNT: usize = 45;
+    const BORDER_TILE_SIZE: usize = 32;
+    const BORDER_MAP_WIDTH: usize = 32;
+    const BORDER_PALETTES_OFFSET: usize = 0x800;
+
+    const PAL01: u8 = 0x00;
+    const PAL23: u8 = 0x01;
+    const PAL03: u8 = 0x02;
+    const PAL12: u8 = 0x03;
+    const ATTR_BLK: u8 = 0x04;
+    const ATTR_LIN: u8 = 0x05;
+    const ATTR_DIV: u8 = 0x06;
+    const ATTR_CHR: u8 = 0x07;
+    const PAL_SET: u8 = 0x0A;
+    const PAL_TRN: u8 = 0x0B;
+    const MLT_REQ: u8 = 0x11;
+    const CHR_TRN: u8 = 0x13;
+    const PCT_TRN: u8 = 0x14;
+    const ATTR_TRN: u8 = 0x15;
+    const ATTR_SET: u8 = 0x16;
+    const MASK_EN: u8 = 0x17;
+
+    /// Shown until the game sets its own palettes.
+    const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+    pub(crate) fn new() -> Self {
+        Self {
+            command: vec![],
+            palettes: [Self::DEFAULT_PALETTE; 4],
+            system_palettes: vec![0; Self::TRANSFER_SIZE],
+            attributes: vec![0; Self::TILES_X * Self::TILES_Y],
+            attribute_files: vec![0; Self::ATTRIBUTE_FILE_SIZE * Self::ATTRIBUTE_FILE_COUNT],
+            border_tiles: vec![0; Self::TRANSFER_SIZE * 2],
+            border: vec![0; Self::TRANSFER_SIZE],
+            mask: Mask::None,
+            transfer: None,
+            screen: vec![Color::from_rgb555(0); Self::SCREEN_WIDTH * Self::SCREEN_HEIGHT],
+        }
+    }
+
+    pub(crate) fn screen(&self) -> &[Color; Self::SCREEN_WIDTH * Self::SCREEN_HEIGHT] {
+        self.screen.as_slice().try_into().unwrap()
+    }
+
+    /// Commands span from 1 to 7 packets, the count being set in the first one.
+    pub(crate) fn receive_packet(
+        &mut self,
+        packet: [u8; Self::PACKET_SIZE],
+        buttons: &mut Buttons,
+    ) {
+        self.command.extend_from_slice(&packet);
+        let packet_count = usize::max((self.command[0] & 0b111) as usize, 1);
+        if self.command.len() < packet_count * Self::PACKET_SIZE {
+            return;
+        }
+
+        let command = std::mem::take(&mut self.command);
+        match command[0] >> 3 {
+            Self::PAL01 => self.set_palettes(&command, 0, 1),
+            Self::PAL23 => self.set_palettes(&command, 2, 3),
+            Self::PAL03 => self.set_palettes(&command, 0, 3),
+            Self::PAL12 => self.set_palettes(&command, 1, 2),
+            Self::ATTR_BLK => self.attribute_blocks(&command),
+            Self::ATTR_LIN => self.attribute_lines(&command),
+            Self::ATTR_DIV => self.attribute_division(&command),
+            Self::ATTR_CHR => self.attribute_characters(&command),
+            Self::PAL_SET => {
+                for (i, palette) in self.palettes.iter_mut().enumerate() {
+                    let index = u16::from_le_bytes([command[1 + i * 2], command[2 + i * 2]]);
+                    let offset = (index as usize & 0x1FF) * 8;
+                    *palette = [0, 1, 2, 3].map(|color| {
+                        u16::from_le_bytes([
+                            self.system_palettes[offset + color * 2],
+                            self.system_palettes[offset + color * 2 + 1],
+                        ])
+                    });
+                }
+                let color_0 = self.palettes[0][0];
+                for palette in &mut self.palettes {
+                    palette[0] = color_0;
+                }
+                if command[9] & 0b1000_0000 != 0 {
+                    self.apply_attribute_file(command[9] & 0b11_1111);
+                }
+                if command[9] & 0b0100_0000 != 0 {
+                    self.mask = Mask::None;
+                }
+            }
+            Self::PAL_TRN => self.transfer = Some(Transfer::SystemPalettes),
+            Self::MLT_REQ => {
+                let player_count = match command[1] & 0b11 {
+                    0b01 => 2,
+                    0b11 => 4,
+                    _ => 1,
+                };
+                buttons.set_player_count(player_count);
+            }
+            Self::CHR_TRN => {
+                self.transfer = Some(Transfer::BorderTiles {
+                    high: command[1] & 0b1 != 0,
+                });
+            }
+            Self::PCT_TRN => self.transfer = Some(Transfer::Border),
+            Self::ATTR_TRN => self.transfer = Some(Transfer::AttributeFiles),
+            Self::ATTR_SET => {
+                self.apply_attribute_file(command[1] & 0b11_1111);
+                if command[1] & 0b0100_0000 != 0 {
+                    self.mask = Mask::None;
+                }
+            }
+            Self::MASK_EN => {
+                self.mask = match command[1] & 0b11 {
+                    0 => Mask::None,
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    3 => Mask::Color0,
+                    _ => unreachable!(),
+                };
+            }
+            // TODO: Sound, SNES program and OBJ mode commands are not supported.
+            _ => {}
+        }
+    }
+
+    fn set_palettes(&mut self, command: &[u8], first: usize, second: usize) {
+        let color =
+            |index: usize| u16::from_le_bytes([command[1 + index * 2], command[2 + index * 2]]);
+        for palette in &mut self.palettes {
+            palette[0] = color(0);
+        }
+        for (i, palette) in [first, second].into_iter().enumerate() {
+            for index in 1..4 {
+                self.palettes[palette][index] = color(i * 3 + index);
+            }
+        }
+    }
+
+    fn set_attribute(&mut self, x: usize, y: usize, palette: u8) {
+        if x < Self::TILES_X && y < Self::TILES_Y {
+            self.attributes[y * Self::TILES_X + x] = palette;
+        }
+    }
+
+    fn attribute_blocks(&mut self, command: &[u8]) {
+        let count = command[1] as usize;
+        for block in command[2..].chunks_exact(6).take(count) {
+            let [control, palettes, x_1, y_1, x_2, y_2] = block.try_into().unwrap();
+            let (x_1, y_1, x_2, y_2) = (x_1 as usize, y_1 as usize, x_2 as usize, y_2 as usize);
+            let inside = (control & 0b001 != 0).then_some(palettes & 0b11);
+            let outside = (control & 0b100 != 0).then_some((palettes >> 4) & 0b11);
+            // The border takes the inside or outside palette when it is the only one set.
+            let border = match (control & 0b111, inside, outside) {
+                (0b001, inside, _) => inside,
+                (0b100, _, outside) => outside,
+                _ => (control & 0b010 != 0).then_some((palettes >> 2) & 0b11),
+            };
+
+            for y in 0..Self::TILES_Y {
+                for x in 0..Self::TILES_X {
+                    let palette = if x > x_1 && x < x_2 && y > y_1 && y < y_2 {
+                        inside
+                    } else if (x_1..=x_2).contains(&x) && (y_1..=y_2).contains(&y) {
+                        border
+                    } else {
+                        outside
+                    };
+                    if let Some(palette) = palette {
+                        self.set_attribute(x, y, palette);
+                    }
+                }
+            }
+        }
+    }
+
+    fn attribute_lines(&mut self, command: &[u8]) {
+        let count = command[1] as usize;
+        for &line in command[2..].iter().take(count) {
+            let index = (line & 0b1_1111) as usize;
+            let palette = (line >> 5) & 0b11;
+            if line & 0b1000_0000 != 0 {
+                for x in 0..Self::TILES_X {
+                    self.set_attribute(x, index, palette);
+                }
+            } else {
+                for y in 0..Self::TILES_Y {
+                    self.set_attribute(index, y, palette);
+                }
+            }
+        }
+    }
+
+    fn attribute_division(&mut self, command: &[u8]) {
+        let after = command[1] & 0b11;
+        let before = (command[1] >> 2) & 0b11;
+        let on_line = (command[1] >> 4) & 0b11;
+        let horizontal = command[1] & 0b0100_0000 != 0;
+        let line = command[2] as usize;
+        for y in 0..Self::TILES_Y {
+            for x in 0..Self::TILES_X {
+                let position = if horizontal { y } else { x };
+                let palette = match position.cmp(&line) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+                self.set_attribute(x, y, palette);
+            }
+        }
+    }
+
+    fn attribute_characters(&mut self, command: &[u8]) {
+        let (mut x, mut y) = (command[1] as usize, command[2] as usize);
+        let count = u16::from_le_bytes([command[3], command[4]]) as usize;
+        let vertical = command[5] & 0b1 != 0;
+        for i in 0..usize::min(count, Self::TILES_X * Self::TILES_Y) {
+            let Some(&value) = command.get(6 + i / 4) else {
+                break;
+            };
+            self.set_attribute(x, y, (value >> (6 - (i % 4) * 2)) & 0b11);
+            if vertical {
+                y += 1;
+                if y == Self::TILES_Y {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == Self::TILES_X {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    fn apply_attribute_file(&mut self, file: u8) {
+        let file = file as usize;
+        if file >= Self::ATTRIBUTE_FILE_COUNT {
+            return;
+        }
+        let data = &self.attribute_files[file * Self::ATTRIBUTE_FILE_SIZE..];
+        for (i, attribute) in self.attributes.iter_mut().enumerate() {
+            *attribute = (data[i / 4] >> (6 - (i % 4) * 2)) & 0b11;
+        }
+    }
+
+    /// Reads back transferred data from the screen, filled with 256 tiles in order.
+    fn transfer_data(shades: &[palette::Color]) -> Vec<u8> {
+        let mut data = vec![0; Self::TRANSFER_SIZE];
+        for (tile, tile_data) in data.chunks_exact_mut(16).enumerate() {
+            let (tile_x, tile_y) = (tile % Self::TILES_X, tile / Self::TILES_X);
+            for row in 0..8 {
+                let line = (tile_y * 8 + row) * Self::GAME_WIDTH + tile_x * 8;
+                for (column, &shade) in shades[line..line + 8].iter().enumerate() {
+                    let shade = shade as u8;
+                    tile_data[row * 2] |= (shade & 0b1) << (7 - column);
+                    tile_data[row * 2 + 1] |= (shade >> 1) << (7 - column);
+                }
+            }
+        }
+        data
+    }
+
+    /// Called when the PPU enters VBlank with the shades of the finished frame.
+    pub(crate) fn end_frame(&mut self, shades: &[palette::Color]) {
+        if let Some(transfer) = self.transfer.take() {
+            let data = Self::transfer_data(shades);
+            match transfer {
+                Transfer::SystemPalettes => self.system_palettes = data,
+                Transfer::BorderTiles { high } => {
+                    let offset = high as usize * Self::TRANSFER_SIZE;
+                    self.border_tiles[offset..offset + Self::TRANSFER_SIZE].copy_from_slice(&data);
+                }
+                Transfer::Border => self.border = data,
+                Transfer::AttributeFiles => {
+                    let size = self.attribute_files.len();
+                    self.attribute_files.copy_from_slice(&data[..size]);
+                }
+            }
+        }
+
+        self.draw_border();
+        for y in 0..Self::GAME_HEIGHT {
+            for x in 0..Self::GAME_WIDTH {
+                let color = match self.mask {
+                    Mask::Freeze => continue,
+                    Mask::Black => 0,
+                    Mask::Color0 => self.palettes[0][0],
+                    Mask::None => {
+                        let palette = self.attributes[y / 8 * Self::TILES_X + x / 8];
+                        let shade = shades[y * Self::GAME_WIDTH + x];
+                        self.palettes[palette as usize][shade as usize]
+                    }
+                };
+                self.screen[(Self::GAME_Y + y) * Self::SCREEN_WIDTH + Self::GAME_X + x] =
+                    Color::from_rgb555(color);
+            }
+        }
+    }
+
+    /// Draws the border around the game screen, transparent pixels showing the shared color 0.
+    fn draw_border(&mut self) {
+        for y in 0..Self::SCREEN_HEIGHT {
+            for x in 0..Self::SCREEN_WIDTH {
+                let in_game_screen = (Self::GAME_X..Self::GAME_X + Self::GAME_WIDTH).contains(&x)
+                    && (Self::GAME_Y..Self::GAME_Y + Self::GAME_HEIGHT).contains(&y);
+                if in_game_screen {
+                    continue;
+                }
+
+                let map_offset = (y / 8 * Self::BORDER_MAP_WIDTH + x / 8) * 2;
+                let entry =
+                    u16::from_le_bytes([self.border[map_offset], self.border[map_offset + 1]]);
+                let tile = &self.border_tiles[(entry & 0xFF) as usize * Self::BORDER_TILE_SIZE..];
+                let palette = ((entry >> 10) & 0b11) as usize;
+                let column = if entry & 0x4000 != 0 {
+                    7 - x % 8
+                } else {
+                    x % 8
+                };
+                let row = if entry & 0x8000 != 0 {
+                    7 - y % 8
+                } else {
+                    y % 8
+                };
+
+                let bit = 7 - column;
+                let index = [
+                    tile[row * 2],
+                    tile[row * 2 + 1],
+                    tile[16 + row * 2],
+                    tile[17 + row * 2],
+                ]
+                .iter()
+                .enumerate()
+                .fold(0, |index, (plane, &data)| {
+                    index | ((data >> bit) & 0b1) << plane
+                }) as usize;
+                let color = if index == 0 {
+                    self.palettes[0][0]
+                } else {
+                    let offset = Self::BORDER_PALETTES_OFFSET + palette * 32 + index * 2;
+                    u16::from_le_bytes([self.border[offset], self.border[offset + 1]])
+                };
+                self.screen[y * Self::SCREEN_WIDTH + x] = Color::from_rgb555(color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::mmu::{MemoryOps, Mmu};
+
+    const JOYPAD: u16 = 0xFF00;
+    const SGB_FLAG: usize = 0x146;
+    const OLD_LICENSEE_CODE: usize = 0x14B;
+
+    fn sgb_mmu() -> Mmu {
+        let mut rom = vec![0; 0x8000];
+        rom[SGB_FLAG] = 0x03;
+        rom[OLD_LICENSEE_CODE] = 0x33;
+        Mmu::new(rom, None, None, true).unwrap()
+    }
+
+    /// Sends a packet as games do: a reset pulse, then each bit by pulling P14 low for a 0 or P15
+    /// low for a 1 before releasing both, and a 0 stop bit.
+    fn send_packet(mmu: &mut Mmu, packet: [u8; Sgb::PACKET_SIZE]) {
+        mmu.write_byte(JOYPAD, 0x00);
+        mmu.write_byte(JOYPAD, 0x30);
+        for bit in 0..Sgb::PACKET_SIZE * 8 {
+            let value = (packet[bit / 8] >> (bit % 8)) & 0b1;
+            mmu.write_byte(JOYPAD, if value != 0 { 0x10 } else { 0x20 });
+            mmu.write_byte(JOYPAD, 0x30);
+        }
+        mmu.write_byte(JOYPAD, 0x20);
+        mmu.write_byte(JOYPAD, 0x30);
+    }
+
+    fn command(command: u8, data: &[u8]) -> [u8; Sgb::PACKET_SIZE] {
+        let mut packet = [0; Sgb::PACKET_SIZE];
+        packet[0] = (command << 3) | 1;
+        packet[1..=data.len()].copy_from_slice(data);
+        packet
+    }
+
+    /// Returns the current joypad, reported in the low nibble with no line selected.
+    fn current_player(mmu: &mut Mmu) -> u8 {
+        0xF - (mmu.read_byte(JOYPAD) & 0xF)
+    }
+
+    /// Pulses P15 as games do to switch to the next joypad.
+    fn next_player(mmu: &mut Mmu) {
+        mmu.write_byte(JOYPAD, 0x10);
+        mmu.write_byte(JOYPAD, 0x30);
+    }
+
+    #[test]
+    fn pal01() {
+        let mut mmu = sgb_mmu();
+        let colors: [u16; 7] = [0x7FFF, 0x001F, 0x03E0, 0x7C00, 0x1111, 0x2222, 0x3333];
+        let data: Vec<u8> = colors
+            .iter()
+            .flat_map(|color| color.to_le_bytes())
+            .collect();
+        send_packet(&mut mmu, command(Sgb::PAL01, &data));
+
+        let palettes = mmu.sgb.as_ref().unwrap().palettes;
+        assert_eq!(palettes[0], [0x7FFF, 0x001F, 0x03E0, 0x7C00]);
+        assert_eq!(palettes[1], [0x7FFF, 0x1111, 0x2222, 0x3333]);
+        // Color 0 is shared, the other palettes are left untouched.
+        for palette in &palettes[2..] {
+            assert_eq!(palette[0], 0x7FFF);
+            assert_eq!(palette[1..], Sgb::DEFAULT_PALETTE[1..]);
+        }
+    }
+
+    #[test]
+    fn mlt_req() {
+        let mut mmu = sgb_mmu();
+        mmu.write_byte(JOYPAD, 0x30);
+        assert_eq!(current_player(&mut mmu), 0);
+        next_player(&mut mmu);
+        assert_eq!(current_player(&mut mmu), 0);
+
+        send_packet(&mut mmu, command(Sgb::MLT_REQ, &[0b01]));
+        for player in [0, 1, 0] {
+            assert_eq!(current_player(&mut mmu), player);
+            next_player(&mut mmu);
+        }
+
+        send_packet(&mut mmu, command(Sgb::MLT_REQ, &[0b11]));
+        for player in [0, 1, 2, 3, 0] {
+            assert_eq!(current_player(&mut mmu), player);
+            next_player(&mut mmu);
+        }
+
+        send_packet(&mut mmu, command(Sgb::MLT_REQ, &[0b00]));
+        assert_eq!(current_player(&mut mmu), 0);
+        next_player(&mut mmu);
+        assert_eq!(current_player(&mut mmu), 0);
+    }
+}
@@ -0,0 +1,89 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+/// Labels loaded from a RGBDS symbol file, made of `bank:address label` lines.
+#[derive(Debug, Default)]
+pub struct Symbols {
+    labels: BTreeMap<(usize, u16), String>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the content of a symbol file, ignoring comments and malformed lines.
+    pub fn parse(content: &str) -> Self {
+        let labels = content
+            .lines()
+            .filter_map(|line| {
+                let line = line.split(';').next()?;
+                let (location, label) = line.trim().split_once(char::is_whitespace)?;
+                let (bank, address) = location.split_once(':')?;
+                let bank = usize::from_str_radix(bank, 16).ok()?;
+                let address = u16::from_str_radix(address, 16).ok()?;
+                Some(((bank, address), label.trim().to_owned()))
+            })
+            .collect();
+        Self { labels }
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// The label defined exactly at an address.
+    pub(crate) fn label(&self, bank: usize, address: u16) -> Option<&str> {
+        self.labels.get(&(bank, address)).map(String::as_str)
+    }
+
+    /// The closest label before an address in the same bank and memory region, with the offset
+    /// from it.
+    pub(crate) fn nearest_label(&self, bank: usize, address: u16) -> Option<(&str, u16)> {
+        self.labels
+            .range((bank, Self::region_start(address))..=(bank, address))
+            .next_back()
+            .map(|(&(_, label_address), label)| (label.as_str(), address - label_address))
+    }
+
+    /// The start of the memory region an address is in, as banks of different regions share
+    /// their numbers.
+    fn region_start(address: u16) -> u16 {
+        match address {
+            0x0000..=0x3FFF => 0x0000,
+            0x4000..=0x7FFF => 0x4000,
+            0x8000..=0x9FFF => 0x8000,
+            0xA000..=0xBFFF => 0xA000,
+            0xC000..=0xCFFF => 0xC000,
+            0xD000..=0xDFFF => 0xD000,
+            0xE000..=0xFDFF => 0xE000,
+            0xFE00..=0xFEFF => 0xFE00,
+            0xFF00..=0xFF7F => 0xFF00,
+            0xFF80..=0xFFFE => 0xFF80,
+            0xFFFF => 0xFFFF,
+        }
+    }
+
+    pub(crate) fn address(&self, label: &str) -> Option<(usize, u16)> {
+        self.labels
+            .iter()
+            .find(|(_, name)| name.as_str() == label)
+            .map(|(&location, _)| location)
+    }
+
+    /// Formats an address with its bank, and the label it is in if any.
+    pub(crate) fn describe(&self, bank: usize, address: u16) -> String {
+        match self.nearest_label(bank, address) {
+            Some((label, 0)) => format!("{bank:02X}:{address:04X} <{label}>"),
+            Some((label, offset)) => format!("{bank:02X}:{address:04X} <{label}+{offset}>"),
+            None => format!("{bank:02X}:{address:04X}"),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum WatchKind {
+    /// Break when the cpu reads from the range
+    Read,
+    /// Break when the cpu writes to the range
+    Write,
+    /// Break when the cpu reads from or writes to the range
+    Access,
+    /// Break when the cpu writes a different value than the one read back from the range
+    Change,
+}
+
+impl fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+            WatchKind::Change => "change",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Watchpoint {
+    pub(crate) kind: WatchKind,
+    pub(crate) start: u16,
+    /// Inclusive.
+    pub(crate) end: u16,
+}
+
+impl Watchpoint {
+    fn contains(&self, address: u16) -> bool {
+        (self.start..=self.end).contains(&address)
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{} 0x{:04X}", self.kind, self.start)
+        } else {
+            write!(f, "{} 0x{:04X}-0x{:04X}", self.kind, self.start, self.end)
+        }
+    }
+}
+
+/// The access that triggered a watchpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WatchpointHit {
+    Read {
+        address: u16,
+        value: u8,
+    },
+    Write {
+        address: u16,
+        value: u8,
+    },
+    Change {
+        address: u16,
+        old_value: u8,
+        value: u8,
+    },
+}
+
+impl fmt::Display for WatchpointHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchpointHit::Read { address, value } => {
+                write!(f, "read 0x{value:02X} from 0x{address:04X}")
+            }
+            WatchpointHit::Write { address, value } => {
+                write!(f, "write 0x{value:02X} to 0x{address:04X}")
+            }
+            WatchpointHit::Change {
+                address,
+                old_value,
+                value,
+            } => write!(
+                f,
+                "change 0x{address:04X} from 0x{old_value:02X} to 0x{value:02X}"
+            ),
+        }
+    }
+}
+
+/// Memory watchpoints, checked on cpu accesses. Only the first hit of an instruction is kept.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    hit: Option<WatchpointHit>,
+}
+
+impl Watchpoints {
+    pub(crate) fn add(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    /// Deletes all the watchpoints starting at an address.
+    pub(crate) fn delete(&mut self, start: u16) {
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.start != start);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watchpoints.iter()
+    }
+
+    pub(crate) fn take_hit(&mut self) -> Option<WatchpointHit> {
+        self.hit.take()
+    }
+
+    fn find(&self, address: u16, kinds: &[WatchKind]) -> bool {
+        self.watchpoints
+            .iter()
+            .any(|watchpoint| kinds.contains(&watchpoint.kind) && watchpoint.contains(address))
+    }
+
+    fn set_hit(&mut self, hit: WatchpointHit) {
+        self.hit.get_or_insert(hit);
+    }
+
+    pub(crate) fn check_read(&mut self, address: u16, value: u8) {
+        if self.find(address, &[WatchKind::Read, WatchKind::Access]) {
+            self.set_hit(WatchpointHit::Read { address, value });
+        }
+    }
+
+    pub(crate) fn watches_change(&self, address: u16) -> bool {
+        self.find(address, &[WatchKind::Change])
+    }
+
+    /// The old value is only known when a change watchpoint covers the address.
+    pub(crate) fn check_write(&mut self, address: u16, old_value: Option<u8>, value: u8) {
+        if self.find(address, &[WatchKind::Write, WatchKind::Access]) {
+            self.set_hit(WatchpointHit::Write { address, value });
+        }
+        if let Some(old_value) = old_value.filter(|&old_value| old_value != value) {
+            self.set_hit(WatchpointHit::Change {
+                address,
+                old_value,
+                value,
+            });
+        }
+    }
+}
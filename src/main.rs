@@ -1,7 +1,8 @@
 use std::{
     fs::{self, File, OpenOptions},
     io::{self, Read, Seek, SeekFrom, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 use blip_buf::BlipBuf;
@@ -23,7 +24,185 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
-use oxidegb::gameboy::{Button, Gameboy};
+use oxidegb::gameboy::{
+    Button, CaptureLink, Color, Gameboy, GameboyOptions, LinkedPair, Printer, Prints, StaticImage,
+    Symbols, TcpLink,
+};
+
+enum Machine {
+    Single(Box<Gameboy>),
+    Linked(Box<LinkedPair>),
+}
+
+impl Machine {
+    fn run_frame(&mut self) -> u64 {
+        match self {
+            Self::Single(gameboy) => gameboy.run_frame(),
+            Self::Linked(pair) => pair.run_frame(),
+        }
+    }
+
+    fn gameboys(&self) -> &[Gameboy] {
+        match self {
+            Self::Single(gameboy) => std::slice::from_ref(gameboy),
+            Self::Linked(pair) => pair.gameboys(),
+        }
+    }
+
+    fn gameboys_mut(&mut self) -> &mut [Gameboy] {
+        match self {
+            Self::Single(gameboy) => std::slice::from_mut(gameboy),
+            Self::Linked(pair) => pair.gameboys_mut(),
+        }
+    }
+}
+
+/// The screen as displayed and its width, with the border in Super Game Boy mode.
+fn display(gameboy: &Gameboy) -> (&[Color], usize) {
+    match gameboy.sgb_screen() {
+        Some(screen) => (screen, 256),
+        None => (gameboy.screen(), 160),
+    }
+}
+
+/// Saves the images of a Game Boy Printer as PNG files in a directory.
+struct PrintOutput {
+    prints: Prints,
+    directory: PathBuf,
+}
+
+struct Player {
+    /// The rom path without its extension, used to name the savestates.
+    base_path: PathBuf,
+    save_file: Option<File>,
+    print_output: Option<PrintOutput>,
+}
+
+impl Player {
+    fn save_prints(&self) {
+        let Some(PrintOutput { prints, directory }) = &self.print_output else {
+            return;
+        };
+        for image in prints.take() {
+            let path = (0..)
+                .map(|index| directory.join(format!("print_{index:03}.png")))
+                .find(|path| !path.exists())
+                .unwrap();
+            if let Err(error) = image.save(&path) {
+                eprintln!("Cannot save printed image to {}: {error}", path.display());
+            }
+        }
+    }
+
+    fn write_save(&mut self, gameboy: &Gameboy) -> io::Result<()> {
+        let Some(save_file) = self.save_file.as_mut() else {
+            return Ok(());
+        };
+        if !gameboy.can_save() {
+            return Ok(());
+        }
+        let save_data = gameboy.save_data();
+        let len = save_data.ram.map_or(0, |ram| ram.len())
+            + save_data.rtc.as_ref().map_or(0, |rtc| rtc.len());
+        save_file.seek(SeekFrom::Start(0))?;
+        if let Some(ram) = save_data.ram {
+            save_file.write_all(ram)?;
+        }
+        if let Some(rtc) = save_data.rtc {
+            save_file.write_all(&rtc)?;
+        }
+        save_file.set_len(len as u64)
+    }
+}
+
+fn print_info(gameboy: &Gameboy) {
+    let model = if gameboy.is_cgb() {
+        "Game Boy Color"
+    } else if gameboy.is_sgb() {
+        "Super Game Boy"
+    } else {
+        "Game Boy"
+    };
+    println!("{}", gameboy.rom_header());
+    println!("Emulated model:    {model}");
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+#[derive(Clone, Copy)]
+struct LoadOptions {
+    should_save: bool,
+    gameboy: GameboyOptions,
+}
+
+/// The suffix is appended to the rom name for the default save file and the savestates, so that
+/// two linked Game Boys running the same rom do not share them.
+fn load_gameboy(
+    rom: Vec<u8>,
+    bootrom: Option<Vec<u8>>,
+    rom_path: PathBuf,
+    name_suffix: &str,
+    save_path: Option<PathBuf>,
+    options: LoadOptions,
+) -> color_eyre::Result<(Gameboy, Player)> {
+    let LoadOptions {
+        should_save,
+        gameboy: gameboy_options,
+    } = options;
+    let base_path = with_suffix(&rom_path.with_extension(""), name_suffix);
+    let save_path = save_path.unwrap_or_else(|| with_suffix(&base_path, ".sav"));
+    let (save_data, save_file) = if should_save {
+        let mut save_file = OpenOptions::new();
+        let file_res = save_file.read(true).write(true).open(&save_path);
+        match file_res {
+            Ok(mut save_file) => {
+                let mut save_data = vec![];
+                save_file.read_to_end(&mut save_data)?;
+                (Some(save_data), Some(save_file))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (None, None),
+            Err(error) => return Err(error.into()),
+        }
+    } else {
+        (None, None)
+    };
+
+    let gameboy = Gameboy::new(rom, bootrom, save_data, gameboy_options)?;
+    // Homebrew and test roms often ship with a wrong checksum, only warn about it.
+    if let Err(error) = gameboy.rom_header().check_header_checksum() {
+        eprintln!("Warning: {error} in {}", rom_path.display());
+    }
+
+    let save_file = if should_save && gameboy.can_save() {
+        if save_file.is_some() {
+            save_file
+        } else {
+            let mut save_file = OpenOptions::new();
+            Some(
+                save_file
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .open(&save_path)?,
+            )
+        }
+    } else {
+        None
+    };
+
+    Ok((
+        gameboy,
+        Player {
+            base_path,
+            save_file,
+            print_output: None,
+        },
+    ))
+}
 
 struct Emulator {
     event_loop: Option<EventLoop<()>>,
@@ -34,77 +213,43 @@ struct Emulator {
     resampling_bufs: (BlipBuf, BlipBuf),
     sound_prod: HeapProducer<i16>,
     _sound_stream: Stream,
-    gameboy: Gameboy,
-    rom_path: PathBuf,
-    save_file: Option<File>,
-    delta: u64,
-    audio_cycles: u64,
+    machine: Machine,
+    players: Vec<Player>,
+    /// Index of the Game Boy receiving inputs.
+    controlled: usize,
+    next_frame: Instant,
     fast_forward: bool,
+    rumble: bool,
 }
 
 impl Emulator {
-    const AUDIO_FRAME_CYCLES: u64 = (0.025 * Gameboy::CYCLES_PER_SECOND as f32) as u64;
-
-    fn new(
-        rom: Vec<u8>,
-        bootrom: Option<Vec<u8>>,
-        rom_path: PathBuf,
-        save_path: Option<PathBuf>,
-        should_save: bool,
-        fast_forward: bool,
-        debug: bool,
-    ) -> color_eyre::Result<Self> {
+    const MAX_LAG: Duration = Duration::from_millis(100);
+
+    fn new(machine: Machine, players: Vec<Player>, fast_forward: bool) -> color_eyre::Result<Self> {
         let event_loop = EventLoop::new();
 
         let window = WindowBuilder::new()
             .with_title("Oxidegb")
             .build(&event_loop)?;
 
+        // Screens are displayed side by side when two Game Boys are linked.
+        let screens: Vec<_> = machine.gameboys().iter().map(display).collect();
+        let width: usize = screens.iter().map(|(_, width)| width).sum();
+        let height = screens
+            .iter()
+            .map(|(screen, width)| screen.len() / width)
+            .max()
+            .unwrap_or_default();
+
         let window_size = window.inner_size();
         let pixels = PixelsBuilder::new(
-            160,
-            144,
+            width as u32,
+            height as u32,
             SurfaceTexture::new(window_size.width, window_size.height, &window),
         )
         .enable_vsync(!fast_forward)
         .build()?;
 
-        let save_path = save_path.unwrap_or_else(|| rom_path.with_extension("sav"));
-        let (save_data, save_file) = if should_save {
-            let mut save_file = OpenOptions::new();
-            let file_res = save_file.read(true).write(true).open(&save_path);
-            match file_res {
-                Ok(mut save_file) => {
-                    let mut save_data = vec![];
-                    save_file.read_to_end(&mut save_data)?;
-                    (Some(save_data), Some(save_file))
-                }
-                Err(error) if error.kind() == io::ErrorKind::NotFound => (None, None),
-                Err(error) => return Err(error.into()),
-            }
-        } else {
-            (None, None)
-        };
-
-        let gameboy = Gameboy::new(rom, bootrom, save_data, debug)?;
-
-        let save_file = if should_save && gameboy.can_save() {
-            if save_file.is_some() {
-                save_file
-            } else {
-                let mut save_file = OpenOptions::new();
-                Some(
-                    save_file
-                        .read(true)
-                        .write(true)
-                        .create(true)
-                        .open(&save_path)?,
-                )
-            }
-        } else {
-            None
-        };
-
         let event_loop = Some(event_loop);
 
         let sample_rate_out = 44100;
@@ -160,24 +305,33 @@ impl Emulator {
             tmp_sound_buf: [0; 4096],
             _sound_stream: sound_stream,
             resampling_bufs,
-            gameboy,
-            rom_path,
-            save_file,
-            delta: 0,
-            audio_cycles: 0,
+            machine,
+            players,
+            controlled: 0,
+            next_frame: Instant::now(),
+            rumble: false,
         })
     }
 
     fn run(mut self) -> ! {
         let event_loop = self.event_loop.take().unwrap();
         event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Poll;
-
             match event {
                 Event::RedrawRequested(_) => {
-                    let screen = self.gameboy.screen();
+                    let screens: Vec<_> = self.machine.gameboys().iter().map(display).collect();
+                    let width: usize = screens.iter().map(|(_, width)| width).sum();
                     for (i, pixel) in self.pixels.frame_mut().chunks_exact_mut(4).enumerate() {
-                        let color: [u8; 4] = screen[i].into();
+                        let (mut x, y) = (i % width, i / width);
+                        let mut color = [0, 0, 0, 0xFF];
+                        for &(screen, screen_width) in &screens {
+                            if x < screen_width {
+                                if let Some(&screen_color) = screen.get(y * screen_width + x) {
+                                    color = screen_color.into();
+                                }
+                                break;
+                            }
+                            x -= screen_width;
+                        }
                         pixel.copy_from_slice(&color);
                     }
                     if self.pixels.render().is_err() {
@@ -200,20 +354,24 @@ impl Emulator {
                     };
                     // TODO: Inputs are hardcoded for now.
                     // TODO: Check how to handle input being pressed during gb frame loop instead of waiting for the end of the frame
+                    let gameboy = &mut self.machine.gameboys_mut()[self.controlled];
                     match event.physical_key {
-                        KeyCode::KeyP if set => self.gameboy.debug_break(),
+                        KeyCode::KeyP if set => gameboy.debug_break(),
+                        KeyCode::Tab if set => {
+                            self.controlled = (self.controlled + 1) % self.players.len();
+                        }
                         KeyCode::KeyF if set => {
                             self.fast_forward = !self.fast_forward;
                             self.pixels.enable_vsync(!self.fast_forward);
                         }
-                        KeyCode::ArrowUp => self.gameboy.set_button(Button::Up, set),
-                        KeyCode::ArrowDown => self.gameboy.set_button(Button::Down, set),
-                        KeyCode::ArrowLeft => self.gameboy.set_button(Button::Left, set),
-                        KeyCode::ArrowRight => self.gameboy.set_button(Button::Right, set),
-                        KeyCode::KeyJ => self.gameboy.set_button(Button::B, set),
-                        KeyCode::KeyK => self.gameboy.set_button(Button::A, set),
-                        KeyCode::KeyU => self.gameboy.set_button(Button::Select, set),
-                        KeyCode::KeyI => self.gameboy.set_button(Button::Start, set),
+                        KeyCode::ArrowUp => gameboy.set_button(Button::Up, set),
+                        KeyCode::ArrowDown => gameboy.set_button(Button::Down, set),
+                        KeyCode::ArrowLeft => gameboy.set_button(Button::Left, set),
+                        KeyCode::ArrowRight => gameboy.set_button(Button::Right, set),
+                        KeyCode::KeyJ => gameboy.set_button(Button::B, set),
+                        KeyCode::KeyK => gameboy.set_button(Button::A, set),
+                        KeyCode::KeyU => gameboy.set_button(Button::Select, set),
+                        KeyCode::KeyI => gameboy.set_button(Button::Start, set),
                         _ => {}
                     }
 
@@ -232,13 +390,10 @@ impl Emulator {
                     };
 
                     if let Some(index) = savestate_index {
-                        let mut savestate_filename = self.rom_path.file_stem().unwrap().to_owned();
-                        savestate_filename.push("_");
-                        savestate_filename.push(index.to_string());
-                        let savestate_path = self
-                            .rom_path
-                            .with_file_name(savestate_filename)
-                            .with_extension("oxidegb");
+                        let savestate_path = with_suffix(
+                            &self.players[self.controlled].base_path,
+                            &format!("_{index}.oxidegb"),
+                        );
                         if self.modifiers == ModifiersState::SHIFT {
                             let load_res: Result<(), eyre::Error> = (|| {
                                 let savestate = File::open(savestate_path).wrap_err_with(|| {
@@ -248,7 +403,7 @@ impl Emulator {
                                     ciborium::from_reader(savestate).wrap_err_with(|| {
                                         format!("Cannot load savestate {index} content")
                                     })?;
-                                self.gameboy.reinit(gameboy)?;
+                                self.machine.gameboys_mut()[self.controlled].reinit(gameboy)?;
                                 Ok(())
                             })(
                             );
@@ -259,7 +414,7 @@ impl Emulator {
                         } else if self.modifiers.is_empty() {
                             let save_res: Result<(), eyre::Error> = (|| {
                                 ciborium::into_writer(
-                                    &self.gameboy,
+                                    &self.machine.gameboys()[self.controlled],
                                     File::create(savestate_path).wrap_err_with(|| {
                                         format!("Cannot create savestate {index} file")
                                     })?,
@@ -276,6 +431,19 @@ impl Emulator {
                         }
                     }
                 }
+                Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::CursorMoved { position, .. },
+                } if window_id == self.window.id() => {
+                    // The cartridge is tilted toward the cursor, by 1g at the window borders.
+                    let size = self.window.inner_size();
+                    let tilt =
+                        |position: f64, size: u32| (position / size as f64 * 2.0 - 1.0) as f32;
+                    self.machine.gameboys_mut()[self.controlled].set_tilt(
+                        tilt(position.x, size.width).clamp(-1.0, 1.0),
+                        tilt(position.y, size.height).clamp(-1.0, 1.0),
+                    );
+                }
                 Event::WindowEvent {
                     window_id,
                     event: WindowEvent::Resized(size),
@@ -286,72 +454,68 @@ impl Emulator {
                     window_id,
                     event: WindowEvent::CloseRequested,
                 } if window_id == self.window.id() => {
-                    if self.gameboy.can_save() {
-                        let save_data = self.gameboy.save_data();
-                        let len = save_data.ram.map_or(0, |ram| ram.len())
-                            + save_data.rtc.as_ref().map_or(0, |rtc| rtc.len());
-                        if let Some(save_file) = self.save_file.as_mut() {
-                            if let Err(error) = (|| {
-                                save_file.seek(SeekFrom::Start(0))?;
-                                if let Some(ram) = save_data.ram {
-                                    save_file.write_all(ram)?;
-                                }
-                                if let Some(rtc) = save_data.rtc {
-                                    save_file.write_all(&rtc)?;
-                                }
-                                save_file.set_len(len as u64)
-                            })() {
-                                eprintln!("{error:?}");
-                            }
+                    for (gameboy, player) in self.machine.gameboys().iter().zip(&mut self.players) {
+                        if let Err(error) = player.write_save(gameboy) {
+                            eprintln!("{error:?}");
                         }
+                        player.save_prints();
                     }
 
                     *control_flow = ControlFlow::Exit;
                 }
                 Event::MainEventsCleared => {
-                    let refresh_rate = self
-                        .window
-                        .current_monitor()
-                        .unwrap()
-                        .refresh_rate_millihertz()
-                        .unwrap() as f32;
-                    let ticks = (1000.0 * Gameboy::CYCLES_PER_SECOND as f32 / refresh_rate) as u64;
-
-                    let mut total_cycles = 0;
-                    self.delta = loop {
-                        let cycles_elapsed = self.gameboy.run_instruction();
-                        total_cycles += cycles_elapsed;
-                        // Handle audio.
-                        {
-                            let (left, right, offsets) = self.gameboy.sound_deltas();
-                            let (left_buf, right_buf) = &mut self.resampling_bufs;
-                            if !offsets.is_empty() {
-                                for ((&left, &right), &offset) in
-                                    left.iter().zip(right).zip(offsets)
-                                {
-                                    left_buf
-                                        .add_delta(self.audio_cycles as u32 + offset as u32, left);
-                                    right_buf
-                                        .add_delta(self.audio_cycles as u32 + offset as u32, right);
-                                }
-                            }
+                    // Frames are paced on the wall clock, the display refresh rate does not matter.
+                    let now = Instant::now();
+                    if !self.fast_forward && now < self.next_frame {
+                        *control_flow = ControlFlow::WaitUntil(self.next_frame);
+                        return;
+                    }
 
-                            self.audio_cycles += cycles_elapsed;
-                            if self.audio_cycles >= Self::AUDIO_FRAME_CYCLES {
-                                left_buf.end_frame(self.audio_cycles as u32);
-                                right_buf.end_frame(self.audio_cycles as u32);
-                                let read_left =
-                                    left_buf.read_samples(&mut self.tmp_sound_buf, true);
-                                let read_right =
-                                    right_buf.read_samples(&mut self.tmp_sound_buf[1..], true);
-                                self.sound_prod
-                                    .push_slice(&self.tmp_sound_buf[..read_left + read_right]);
-                                self.audio_cycles = 0;
-                            }
-                        }
-                        if total_cycles >= ticks - self.delta {
-                            break total_cycles - (ticks - self.delta);
+                    let cycles = self.machine.run_frame();
+                    for player in &self.players {
+                        player.save_prints();
+                    }
+                    // Only the first Game Boy is heard when two are linked.
+                    {
+                        let (left, right, offsets) = self.machine.gameboys_mut()[0].sound_deltas();
+                        let (left_buf, right_buf) = &mut self.resampling_bufs;
+                        for ((&left, &right), &offset) in left.iter().zip(right).zip(offsets) {
+                            left_buf.add_delta(offset as u32, left);
+                            right_buf.add_delta(offset as u32, right);
                         }
+                        left_buf.end_frame(cycles as u32);
+                        right_buf.end_frame(cycles as u32);
+                        let read_left = left_buf.read_samples(&mut self.tmp_sound_buf, true);
+                        let read_right = right_buf.read_samples(&mut self.tmp_sound_buf[1..], true);
+                        self.sound_prod
+                            .push_slice(&self.tmp_sound_buf[..read_left + read_right]);
+                    }
+
+                    // No force feedback support, rumbling is shown in the window title instead.
+                    let rumble = self.machine.gameboys().iter().any(Gameboy::rumble);
+                    if rumble != self.rumble {
+                        self.rumble = rumble;
+                        self.window.set_title(if rumble {
+                            "Oxidegb (rumble)"
+                        } else {
+                            "Oxidegb"
+                        });
+                    }
+
+                    let frame_duration =
+                        Duration::from_secs_f64(cycles as f64 / Gameboy::CYCLES_PER_SECOND as f64);
+                    // Catching up is not attempted after fast forwarding or a long stall.
+                    self.next_frame = if self.fast_forward || now > self.next_frame + Self::MAX_LAG
+                    {
+                        now + frame_duration
+                    } else {
+                        self.next_frame + frame_duration
+                    };
+                    // Only spin when fast forwarding, frames are otherwise waited for.
+                    *control_flow = if self.fast_forward {
+                        ControlFlow::Poll
+                    } else {
+                        ControlFlow::WaitUntil(self.next_frame)
                     };
                     self.window.request_redraw();
                 }
@@ -375,15 +539,146 @@ struct Arguments {
     /// The bootrom file to load.
     #[arg(short, long)]
     bootrom_file: Option<PathBuf>,
+    /// Run Super Game Boy enhanced roms as on a plain Game Boy.
+    #[arg(long)]
+    no_sgb: bool,
     /// Display rom header info.
     #[arg(short, long)]
     info: bool,
     /// Enable the debugger.
     #[arg(short, long)]
     debug: bool,
+    /// A second rom file to run on another Game Boy, linked to the first one and displayed side by side. Its save file and savestates are suffixed with ".link".
+    #[arg(short, long, conflicts_with_all = ["link_listen", "link_connect", "printer"])]
+    link: Option<PathBuf>,
+    /// Wait for another oxidegb instance to connect its link cable on this address.
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["link_connect", "printer"])]
+    link_listen: Option<String>,
+    /// Connect the link cable to another oxidegb instance listening on this address.
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "printer")]
+    link_connect: Option<String>,
+    /// Plug a Game Boy Printer in the link port, saving printed images in this directory.
+    #[arg(long, value_name = "DIRECTORY")]
+    printer: Option<PathBuf>,
+    /// Image seen by the Game Boy Camera sensor. By default, a test pattern is used.
+    #[arg(long, value_name = "PNG_FILE")]
+    camera_image: Option<PathBuf>,
+    /// RGBDS symbol file with the labels shown by the debugger. By default, oxidegb will load a sym file with the same base name as the rom file if it exists.
+    #[arg(long, value_name = "SYM_FILE")]
+    symbols: Option<PathBuf>,
     /// Do not limit fps.
     #[arg(short, long)]
     fast_forward: bool,
+    /// Run without window or audio, for automated testing.
+    /// Exits with status 0 on success, 1 if the run failed.
+    #[arg(long, conflicts_with = "link")]
+    headless: bool,
+    /// Number of frames to run in headless mode. By default, runs until a serial condition is met.
+    #[arg(long, requires = "headless")]
+    frames: Option<u64>,
+    /// Fail the headless run if no serial condition is met after this number of frames, when no frame count is given. By default, 36000 frames (10 minutes).
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        requires = "headless",
+        conflicts_with = "frames"
+    )]
+    timeout_frames: Option<u64>,
+    /// Save the screen at the end of the headless run to a PNG file.
+    #[arg(long, value_name = "PNG_FILE", requires = "headless")]
+    screenshot: Option<PathBuf>,
+    /// Print the bytes sent on the serial port in headless mode.
+    #[arg(long, requires = "headless", conflicts_with_all = ["link_listen", "link_connect", "printer"])]
+    print_serial: bool,
+    /// Stop the headless run successfully once the serial output contains this text.
+    #[arg(long, value_name = "TEXT", requires = "headless", conflicts_with_all = ["link_listen", "link_connect", "printer"])]
+    until_serial: Option<String>,
+    /// Stop the headless run with a failure once the serial output contains this text.
+    #[arg(long, value_name = "TEXT", requires = "headless", conflicts_with_all = ["link_listen", "link_connect", "printer"])]
+    fail_serial: Option<String>,
+}
+
+struct HeadlessOptions {
+    frames: Option<u64>,
+    timeout_frames: Option<u64>,
+    screenshot: Option<PathBuf>,
+    print_serial: bool,
+    until_serial: Option<String>,
+    fail_serial: Option<String>,
+}
+
+const DEFAULT_TIMEOUT_FRAMES: u64 = 36000;
+
+/// Runs until the frame count or a serial condition is reached, returning whether the run succeeded.
+fn run_headless(
+    mut gameboy: Gameboy,
+    mut player: Player,
+    options: HeadlessOptions,
+) -> color_eyre::Result<bool> {
+    if options.frames.is_none() && options.until_serial.is_none() && options.fail_serial.is_none() {
+        return Err(eyre!(
+            "Headless mode needs a frame count or a serial condition to stop"
+        ));
+    }
+
+    let serial = CaptureLink::new();
+    let capture_serial =
+        options.print_serial || options.until_serial.is_some() || options.fail_serial.is_some();
+    if capture_serial {
+        gameboy.set_serial_link(Box::new(serial.clone()));
+    }
+
+    // Without a frame count, the run must not hang forever if the serial condition is never met.
+    let frames = options
+        .frames
+        .unwrap_or_else(|| options.timeout_frames.unwrap_or(DEFAULT_TIMEOUT_FRAMES));
+    let mut printed = 0;
+    let mut success = options.until_serial.is_none();
+    let mut stopped = false;
+    for _ in 0..frames {
+        gameboy.run_frame();
+
+        if !capture_serial {
+            continue;
+        }
+        let output = serial.output();
+        if options.print_serial && output.len() > printed {
+            print!("{}", String::from_utf8_lossy(&output[printed..]));
+            io::stdout().flush()?;
+            printed = output.len();
+        }
+        let output = String::from_utf8_lossy(&output);
+        if let Some(text) = &options.fail_serial {
+            if output.contains(text.as_str()) {
+                success = false;
+                stopped = true;
+                break;
+            }
+        }
+        if let Some(text) = &options.until_serial {
+            if output.contains(text.as_str()) {
+                success = true;
+                stopped = true;
+                break;
+            }
+        }
+    }
+    if options.frames.is_none() && !stopped {
+        eprintln!("Headless run timed out after {frames} frames");
+        success = false;
+    }
+
+    if let Some(path) = &options.screenshot {
+        gameboy
+            .save_screenshot(path)
+            .wrap_err_with(|| format!("Cannot save screenshot to {}", path.display()))?;
+    }
+    player.write_save(&gameboy)?;
+    // The image still being printed is fed out when the printer is dropped.
+    drop(gameboy);
+    player.save_prints();
+
+    Ok(success)
 }
 
 fn main() -> color_eyre::Result<()> {
@@ -395,21 +690,94 @@ fn main() -> color_eyre::Result<()> {
         .bootrom_file
         .map_or(Ok(None), |bootrom_file| fs::read(bootrom_file).map(Some))?;
 
-    let emulator = Emulator::new(
+    let symbols_path = arguments.symbols.or_else(|| {
+        let path = arguments.file.with_extension("sym");
+        path.exists().then_some(path)
+    });
+
+    let load_options = LoadOptions {
+        should_save: !arguments.no_save,
+        gameboy: GameboyOptions {
+            sgb: !arguments.no_sgb,
+            debug: arguments.debug,
+        },
+    };
+    let (mut gameboy, mut player) = load_gameboy(
         rom,
-        bootrom,
+        bootrom.clone(),
         arguments.file,
+        "",
         arguments.save_file,
-        !arguments.no_save,
-        arguments.fast_forward,
-        arguments.debug,
+        load_options,
     )?;
+    if let Some(address) = arguments.link_listen {
+        println!("Waiting for a link cable connection on {address}");
+        let link = TcpLink::listen(&address)
+            .wrap_err_with(|| format!("Cannot listen for a link cable on {address}"))?;
+        gameboy.set_serial_link(Box::new(link));
+    } else if let Some(address) = arguments.link_connect {
+        let link = TcpLink::connect(&address)
+            .wrap_err_with(|| format!("Cannot connect the link cable to {address}"))?;
+        gameboy.set_serial_link(Box::new(link));
+    } else if let Some(directory) = arguments.printer {
+        let printer = Printer::new();
+        player.print_output = Some(PrintOutput {
+            prints: printer.prints(),
+            directory,
+        });
+        gameboy.set_serial_link(Box::new(printer));
+    }
+    if let Some(path) = arguments.camera_image {
+        let image = StaticImage::open(&path)
+            .wrap_err_with(|| format!("Cannot load camera image {}", path.display()))?;
+        gameboy.set_camera_source(Box::new(image));
+    }
+    if let Some(path) = symbols_path {
+        let symbols = Symbols::open(&path)
+            .wrap_err_with(|| format!("Cannot load symbols {}", path.display()))?;
+        gameboy.set_symbols(symbols);
+    }
+
+    if arguments.headless {
+        if arguments.info {
+            print_info(&gameboy);
+        }
+        let options = HeadlessOptions {
+            frames: arguments.frames,
+            timeout_frames: arguments.timeout_frames,
+            screenshot: arguments.screenshot,
+            print_serial: arguments.print_serial,
+            until_serial: arguments.until_serial,
+            fail_serial: arguments.fail_serial,
+        };
+        let success = run_headless(gameboy, player, options)?;
+        std::process::exit(if success { 0 } else { 1 });
+    }
+
+    let (machine, players) = match arguments.link {
+        Some(linked_rom_path) => {
+            let linked_rom = fs::read(&linked_rom_path)?;
+            let (linked_gameboy, linked_player) = load_gameboy(
+                linked_rom,
+                bootrom,
+                linked_rom_path,
+                ".link",
+                None,
+                load_options,
+            )?;
+            (
+                Machine::Linked(Box::new(LinkedPair::new(gameboy, linked_gameboy))),
+                vec![player, linked_player],
+            )
+        }
+        None => (Machine::Single(Box::new(gameboy)), vec![player]),
+    };
+
+    let emulator = Emulator::new(machine, players, arguments.fast_forward)?;
     if arguments.info {
-        println!(
-            "{:?}\n{:?}",
-            emulator.gameboy.rom_header(),
-            emulator.gameboy.mapper()
-        );
+        for gameboy in emulator.machine.gameboys() {
+            print_info(gameboy);
+        }
     }
     emulator.run();
 }
@@ -0,0 +1,41 @@
+// Shared by several test crates, each using only part of it.
+#![allow(dead_code)]
+
+use std::{env, path::PathBuf};
+
+use oxidegb::gameboy::Gameboy;
+
+/// Test ROMs are not distributed with the repository, they are looked up in the directory set by
+/// `OXIDEGB_TEST_ROMS`, or `tests/roms` by default.
+pub fn test_roms_directory() -> PathBuf {
+    env::var_os("OXIDEGB_TEST_ROMS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/roms"))
+}
+
+/// Whether running no test rom at all is accepted, when `OXIDEGB_SKIP_TEST_ROMS` is set.
+/// Otherwise, a missing test rom directory fails the tests instead of silently passing.
+pub fn skip_missing_roms() -> bool {
+    env::var_os("OXIDEGB_SKIP_TEST_ROMS").is_some()
+}
+
+/// FNV-1a hash of RGBA pixels, stable across platforms and compiler versions.
+pub fn pixels_hash(pixels: impl IntoIterator<Item = u8>) -> u64 {
+    pixels
+        .into_iter()
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+        })
+}
+
+pub fn screen_pixels(gameboy: &Gameboy) -> Vec<u8> {
+    gameboy
+        .screen()
+        .iter()
+        .flat_map(|&color| <[u8; 4]>::from(color))
+        .collect()
+}
+
+pub fn screen_hash(gameboy: &Gameboy) -> u64 {
+    pixels_hash(screen_pixels(gameboy))
+}
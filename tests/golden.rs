@@ -0,0 +1,296 @@
+mod common;
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use oxidegb::gameboy::{Button, Gameboy, GameboyOptions};
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 144;
+
+#[derive(Debug, Clone, Copy)]
+struct Input {
+    button: Button,
+    first_frame: u64,
+    last_frame: u64,
+}
+
+impl Input {
+    fn parse(input: &str) -> Self {
+        let (button, frames) = input
+            .split_once('@')
+            .unwrap_or_else(|| panic!("Invalid input \"{input}\""));
+        let button = match button {
+            "up" => Button::Up,
+            "down" => Button::Down,
+            "left" => Button::Left,
+            "right" => Button::Right,
+            "a" => Button::A,
+            "b" => Button::B,
+            "start" => Button::Start,
+            "select" => Button::Select,
+            _ => panic!("Invalid button \"{button}\""),
+        };
+        let (first_frame, last_frame) = frames.split_once('-').unwrap_or((frames, frames));
+        Self {
+            button,
+            first_frame: first_frame.parse().expect("Invalid input frame"),
+            last_frame: last_frame.parse().expect("Invalid input frame"),
+        }
+    }
+
+    fn is_held(&self, frame: u64) -> bool {
+        (self.first_frame..=self.last_frame).contains(&frame)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    /// Official reference image distributed with the test rom, relative to the test ROM directory.
+    /// It is never blessed.
+    Reference(String),
+    /// Image saved from the emulator output with `OXIDEGB_BLESS=1`.
+    Blessed,
+}
+
+/// The DMG palette of the emulator and the gray shades used by reference images.
+const DMG_SHADES: [([u8; 4], [u8; 4]); 4] = [
+    ([0xE0, 0xF8, 0xD0, 0xFF], [0xFF, 0xFF, 0xFF, 0xFF]),
+    ([0x88, 0xC0, 0x70, 0xFF], [0xAA, 0xAA, 0xAA, 0xFF]),
+    ([0x34, 0x68, 0x56, 0xFF], [0x55, 0x55, 0x55, 0xFF]),
+    ([0x08, 0x18, 0x20, 0xFF], [0x00, 0x00, 0x00, 0xFF]),
+];
+
+struct GoldenTest {
+    name: String,
+    rom: String,
+    frames: u64,
+    inputs: Vec<Input>,
+    source: Source,
+    /// Known failures are reported without failing the test.
+    expected_pass: bool,
+}
+
+impl GoldenTest {
+    fn parse(line: &str) -> Self {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [name, rom, frames, inputs, source, expected] = fields[..] else {
+            panic!("Invalid golden test line \"{line}\"");
+        };
+        let inputs = match inputs {
+            "-" => vec![],
+            _ => inputs.split(',').map(Input::parse).collect(),
+        };
+        let source = match source.split_once(':') {
+            Some(("reference", path)) => Source::Reference(path.to_owned()),
+            None if source == "blessed" => Source::Blessed,
+            _ => panic!("Invalid golden image source \"{source}\""),
+        };
+        let expected_pass = match expected {
+            "pass" => true,
+            "fail" => false,
+            _ => panic!("Invalid golden test expected result \"{expected}\""),
+        };
+        Self {
+            name: name.to_owned(),
+            rom: rom.to_owned(),
+            frames: frames.parse().expect("Invalid frame count"),
+            inputs,
+            source,
+            expected_pass,
+        }
+    }
+
+    /// Runs the ROM with the scripted inputs and returns the final screen as RGBA bytes.
+    /// DMG screens compared to a reference image are converted to its gray shades.
+    fn run(&self, rom: Vec<u8>) -> Vec<u8> {
+        let mut gameboy = Gameboy::new(rom, None, None, GameboyOptions::default())
+            .expect("Cannot load golden rom");
+        for frame in 0..self.frames {
+            for input in &self.inputs {
+                gameboy.set_button(input.button, input.is_held(frame));
+            }
+            gameboy.run_frame();
+        }
+        let mut pixels = common::screen_pixels(&gameboy);
+        if matches!(self.source, Source::Reference(_)) && !gameboy.is_cgb() {
+            for pixel in pixels.chunks_exact_mut(4) {
+                if let Some((_, shade)) = DMG_SHADES.iter().find(|(color, _)| color == pixel) {
+                    pixel.copy_from_slice(shade);
+                }
+            }
+        }
+        pixels
+    }
+}
+
+fn golden_directory() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn output_directory() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden")
+}
+
+fn read_png(path: &Path) -> io::Result<Vec<u8>> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    buffer.truncate(info.buffer_size());
+
+    if (info.width as usize, info.height as usize) != (WIDTH, HEIGHT) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "unexpected golden image size {}x{}",
+                info.width, info.height
+            ),
+        ));
+    }
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF])
+            .collect(),
+        color_type => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported golden image color type {color_type:?}"),
+            ))
+        }
+    };
+    Ok(pixels)
+}
+
+fn write_png(path: &Path, pixels: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        WIDTH as u32,
+        HEIGHT as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(pixels)?;
+    Ok(())
+}
+
+/// Differing pixels are drawn in red over a faded version of the expected image.
+fn diff_image(expected: &[u8], actual: &[u8]) -> (Vec<u8>, usize) {
+    let mut differences = 0;
+    let diff = expected
+        .chunks_exact(4)
+        .zip(actual.chunks_exact(4))
+        .flat_map(|(expected, actual)| {
+            if expected == actual {
+                let luma = (expected[0] as u16 + expected[1] as u16 + expected[2] as u16) / 3;
+                let faded = (0xC0 + luma / 4) as u8;
+                [faded, faded, faded, 0xFF]
+            } else {
+                differences += 1;
+                [0xFF, 0x00, 0x00, 0xFF]
+            }
+        })
+        .collect();
+    (diff, differences)
+}
+
+/// Records the failure of a test expected to pass, known failures are only noted.
+fn failure_note<'a>(test: &'a GoldenTest, failures: &mut Vec<&'a str>) -> &'static str {
+    if test.expected_pass {
+        failures.push(&test.name);
+        ""
+    } else {
+        " (known failure)"
+    }
+}
+
+#[test]
+fn golden_images() {
+    let roms_directory = common::test_roms_directory();
+    let bless = env::var_os("OXIDEGB_BLESS").is_some();
+    let tests: Vec<GoldenTest> = include_str!("golden.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(GoldenTest::parse)
+        .collect();
+
+    let mut failures = vec![];
+    let mut ran = 0;
+    let output = output_directory();
+    fs::create_dir_all(&output).expect("Cannot create golden output directory");
+    for test in &tests {
+        let Ok(rom) = fs::read(roms_directory.join(Path::new(&test.rom))) else {
+            println!("{:<20} missing rom", test.name);
+            continue;
+        };
+        ran += 1;
+
+        let actual = test.run(rom);
+        let actual_hash = common::pixels_hash(actual.iter().copied());
+        let golden_path = match &test.source {
+            Source::Reference(path) => roms_directory.join(Path::new(path)),
+            Source::Blessed => golden_directory().join(format!("{}.png", test.name)),
+        };
+        let actual_path = output.join(format!("{}_actual.png", test.name));
+        if bless && test.source == Source::Blessed {
+            fs::create_dir_all(golden_directory()).expect("Cannot create golden directory");
+            write_png(&golden_path, &actual).expect("Cannot write golden image");
+            println!("{:<20} {actual_hash:016X} blessed", test.name);
+            continue;
+        }
+
+        let expected = match read_png(&golden_path) {
+            Ok(expected) => expected,
+            // A missing golden image is a failure, the actual screen is saved to be checked by hand.
+            Err(error) => {
+                write_png(&actual_path, &actual).expect("Cannot write actual image");
+                let note = failure_note(test, &mut failures);
+                println!(
+                    "{:<20} {actual_hash:016X} cannot read {}: {error}{note}, see {}",
+                    test.name,
+                    golden_path.display(),
+                    actual_path.display()
+                );
+                continue;
+            }
+        };
+        if common::pixels_hash(expected.iter().copied()) == actual_hash {
+            let note = if test.expected_pass {
+                ""
+            } else {
+                " <- now passing, update golden.txt"
+            };
+            println!("{:<20} {actual_hash:016X} ok{note}", test.name);
+            continue;
+        }
+
+        let (diff, differences) = diff_image(&expected, &actual);
+        let diff_path = output.join(format!("{}_diff.png", test.name));
+        write_png(&actual_path, &actual).expect("Cannot write actual image");
+        write_png(&diff_path, &diff).expect("Cannot write diff image");
+        let note = failure_note(test, &mut failures);
+        println!(
+            "{:<20} {actual_hash:016X} {differences} pixels differ{note}, see {}",
+            test.name,
+            diff_path.display()
+        );
+    }
+
+    if ran == 0 {
+        let message = format!(
+            "No golden test rom found in {}, set OXIDEGB_TEST_ROMS to run them or OXIDEGB_SKIP_TEST_ROMS to skip them",
+            roms_directory.display()
+        );
+        assert!(common::skip_missing_roms(), "{message}");
+        println!("{message}");
+    }
+    assert!(failures.is_empty(), "Golden image failures in {failures:?}");
+}
@@ -0,0 +1,66 @@
+use std::{net::TcpListener, thread};
+
+use oxidegb::gameboy::{Gameboy, GameboyOptions, TcpLink};
+
+const SB: u16 = 0xFF01;
+
+/// A rom only made of the given program at the entry point, followed by an infinite loop.
+fn rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    let end = 0x100 + program.len();
+    rom[0x100..end].copy_from_slice(program);
+    // jr -2
+    rom[end..end + 2].copy_from_slice(&[0x18, 0xFE]);
+    rom
+}
+
+/// Runs a Game Boy linked through `link` and returns its SB register.
+fn run_linked(program: &[u8], link: TcpLink) -> u8 {
+    let mut gameboy =
+        Gameboy::new(rom(program), None, None, GameboyOptions::default()).expect("Cannot load rom");
+    gameboy.set_serial_link(Box::new(link));
+    for _ in 0..30 {
+        gameboy.run_frame();
+    }
+    gameboy.read_memory(SB)
+}
+
+#[test]
+fn tcp_link_transfer() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Cannot bind a local port");
+    let address = listener.local_addr().unwrap();
+
+    // The master waits longer than a sync window before transferring, so that the slave is ready
+    // whatever the order in which both threads are scheduled.
+    #[rustfmt::skip]
+    let master = [
+        0x3E, 0x42,       // ld a,0x42
+        0xE0, 0x01,       // ldh (SB),a
+        0x01, 0x00, 0x40, // ld bc,0x4000
+        0x0B,             // dec bc
+        0x78,             // ld a,b
+        0xB1,             // or c
+        0x20, 0xFB,       // jr nz,-5
+        0x3E, 0x81,       // ld a,0x81
+        0xE0, 0x02,       // ldh (SC),a
+    ];
+    #[rustfmt::skip]
+    let slave = [
+        0x3E, 0x24, // ld a,0x24
+        0xE0, 0x01, // ldh (SB),a
+        0x3E, 0x80, // ld a,0x80
+        0xE0, 0x02, // ldh (SC),a
+    ];
+
+    let master = thread::spawn(move || {
+        let link = TcpLink::accept(&listener).expect("Cannot accept the link");
+        run_linked(&master, link)
+    });
+    let slave = thread::spawn(move || {
+        let link = TcpLink::connect(address).expect("Cannot connect the link");
+        run_linked(&slave, link)
+    });
+
+    assert_eq!(master.join().unwrap(), 0x24);
+    assert_eq!(slave.join().unwrap(), 0x42);
+}
@@ -0,0 +1,236 @@
+mod common;
+
+use std::{fmt, fs, path::Path};
+
+use oxidegb::gameboy::{CaptureLink, Gameboy, GameboyOptions};
+
+#[derive(Debug, Clone, Copy)]
+enum Check {
+    Serial,
+    Memory,
+    Mooneye,
+    ScreenHash(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expected {
+    Pass,
+    Fail,
+    Unknown,
+}
+
+struct TestRom {
+    path: String,
+    check: Check,
+    frames: u64,
+    expected: Expected,
+}
+
+impl TestRom {
+    fn parse(line: &str) -> Self {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [path, check, frames, expected] = fields[..] else {
+            panic!("Invalid test rom line \"{line}\"");
+        };
+        let check = match check {
+            "serial" => Check::Serial,
+            "memory" => Check::Memory,
+            "mooneye" => Check::Mooneye,
+            _ => match check.strip_prefix("screen:") {
+                Some(hash) => {
+                    Check::ScreenHash(u64::from_str_radix(hash, 16).expect("Invalid screen hash"))
+                }
+                None => panic!("Invalid test rom check \"{check}\""),
+            },
+        };
+        let expected = match expected {
+            "pass" => Expected::Pass,
+            "fail" => Expected::Fail,
+            "-" => Expected::Unknown,
+            _ => panic!("Invalid test rom expected result \"{expected}\""),
+        };
+        Self {
+            path: path.to_owned(),
+            check,
+            frames: frames.parse().expect("Invalid frame count"),
+            expected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Fail(String),
+    Timeout,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "pass"),
+            Self::Fail(reason) => write!(f, "fail ({reason})"),
+            Self::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const MOONEYE_FAIL: [u8; 6] = [0x42; 6];
+const LD_B_B: u8 = 0x40;
+
+fn serial_outcome(output: &[u8]) -> Option<Outcome> {
+    let output = String::from_utf8_lossy(output);
+    if output.contains("Passed") {
+        Some(Outcome::Pass)
+    } else if output.contains("Failed") {
+        let reason = output.lines().last().unwrap_or_default().trim().to_owned();
+        Some(Outcome::Fail(reason))
+    } else {
+        None
+    }
+}
+
+/// Blargg tests write 0x80 at 0xA000 while running, then their result code, after a signature.
+fn memory_outcome(gameboy: &mut Gameboy) -> Option<Outcome> {
+    let signature = [0xA001, 0xA002, 0xA003].map(|address| gameboy.read_memory(address));
+    if signature != [0xDE, 0xB0, 0x61] {
+        return None;
+    }
+    match gameboy.read_memory(0xA000) {
+        0x80 => None,
+        0x00 => Some(Outcome::Pass),
+        code => Some(Outcome::Fail(format!("code {code}"))),
+    }
+}
+
+fn mooneye_outcome(gameboy: &mut Gameboy) -> Option<Outcome> {
+    let registers = gameboy.registers();
+    if gameboy.read_memory(registers.pc) != LD_B_B {
+        return None;
+    }
+    let values = [
+        registers.b,
+        registers.c,
+        registers.d,
+        registers.e,
+        registers.h,
+        registers.l,
+    ];
+    if values == MOONEYE_PASS {
+        Some(Outcome::Pass)
+    } else if values == MOONEYE_FAIL {
+        Some(Outcome::Fail("failure signature".to_owned()))
+    } else {
+        None
+    }
+}
+
+fn run(test_rom: &TestRom, rom: Vec<u8>) -> (Outcome, u64) {
+    let mut gameboy =
+        Gameboy::new(rom, None, None, GameboyOptions::default()).expect("Cannot load test rom");
+    let serial = CaptureLink::new();
+    gameboy.set_serial_link(Box::new(serial.clone()));
+
+    for _ in 0..test_rom.frames {
+        let outcome = match test_rom.check {
+            Check::Mooneye => {
+                let mut cycles = 0;
+                let mut outcome = None;
+                while outcome.is_none() && cycles < Gameboy::CYCLES_PER_FRAME {
+                    outcome = mooneye_outcome(&mut gameboy);
+                    cycles += gameboy.run_instruction();
+                }
+                outcome
+            }
+            Check::Serial => {
+                gameboy.run_frame();
+                serial_outcome(&serial.output())
+            }
+            Check::Memory => {
+                gameboy.run_frame();
+                memory_outcome(&mut gameboy)
+            }
+            Check::ScreenHash(_) => {
+                gameboy.run_frame();
+                None
+            }
+        };
+        if let Some(outcome) = outcome {
+            return (outcome, common::screen_hash(&gameboy));
+        }
+    }
+
+    let hash = common::screen_hash(&gameboy);
+    let outcome = match test_rom.check {
+        Check::ScreenHash(expected) if expected == hash => Outcome::Pass,
+        Check::ScreenHash(_) => Outcome::Fail("screen hash mismatch".to_owned()),
+        _ => Outcome::Timeout,
+    };
+    (outcome, hash)
+}
+
+#[test]
+fn test_roms() {
+    let directory = common::test_roms_directory();
+    let manifest = include_str!("test_roms.txt");
+    let test_roms: Vec<TestRom> = manifest
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(TestRom::parse)
+        .collect();
+
+    let mut regressions = vec![];
+    let mut unrecorded = vec![];
+    let mut ran = 0;
+    println!(
+        "{:<50} {:<10} {:<16} result",
+        "rom", "expected", "screen hash"
+    );
+    for test_rom in &test_roms {
+        let Ok(rom) = fs::read(directory.join(Path::new(&test_rom.path))) else {
+            println!("{:<50} {:<10} {:<16} missing", test_rom.path, "", "");
+            continue;
+        };
+        ran += 1;
+
+        let (outcome, hash) = run(test_rom, rom);
+        let expected = match test_rom.expected {
+            Expected::Pass => "pass",
+            Expected::Fail => "fail",
+            Expected::Unknown => "-",
+        };
+        let note = match (test_rom.expected, &outcome) {
+            (Expected::Pass, Outcome::Pass) | (Expected::Fail, Outcome::Fail(_)) => "",
+            (Expected::Pass, _) => {
+                regressions.push(test_rom.path.as_str());
+                " <- regression"
+            }
+            (Expected::Fail, Outcome::Pass) => " <- now passing, update test_roms.txt",
+            (Expected::Fail, _) => "",
+            (Expected::Unknown, _) => {
+                unrecorded.push(test_rom.path.as_str());
+                " <- unrecorded, set its expected result in test_roms.txt"
+            }
+        };
+        println!(
+            "{:<50} {expected:<10} {hash:016X} {outcome}{note}",
+            test_rom.path
+        );
+    }
+
+    if ran == 0 {
+        let message = format!(
+            "No test rom found in {}, set OXIDEGB_TEST_ROMS to run them or OXIDEGB_SKIP_TEST_ROMS to skip them",
+            directory.display()
+        );
+        assert!(common::skip_missing_roms(), "{message}");
+        println!("{message}");
+    }
+    assert!(regressions.is_empty(), "Regressions in {regressions:?}");
+    assert!(
+        unrecorded.is_empty(),
+        "No expected result recorded for {unrecorded:?}"
+    );
+}